@@ -28,7 +28,23 @@ enum FieldSorting {
 }
 
 impl AlsaVolumeConfig {
-    pub(crate) fn format_volume(&self, volume : f32, mute : bool) -> Result<String,FormattingError> {
+    /// JSON Schema of the config, for `SwayStatusModule::get_config_schema`.
+    pub(crate) fn config_schema() -> serde_json::Value {
+        serde_json::json!({
+            "type" : "object",
+            "properties" : {
+                "device" : { "type" : "string", "description" : "The ALSA device, for instance \"default\"." },
+                "element" : { "type" : "string", "description" : "The mixer element, for instance \"Master\"." },
+                "abstraction" : { "enum" : ["None", "Basic"] },
+                "sorting" : { "enum" : ["MuteVolume", "VolumeMute"] },
+                "mute" : FormatableMute::config_schema(),
+                "volume" : FormatableFloatValue::<VolumeKeyVolume>::config_schema()
+            },
+            "required" : ["device", "element", "abstraction", "sorting", "mute", "volume"]
+        })
+    }
+
+    pub(crate) fn format_volume(&self, volume : f32, mute : bool) -> Result<StatusBlock,FormattingError> {
         let formatted_mute = self.mute.format_mute(mute).unwrap_or(String::new());
         let join_strings = |v : String,m : String| match self.sorting {
             FieldSorting::MuteVolume => m + &v,
@@ -36,7 +52,8 @@ impl AlsaVolumeConfig {
         };
         match self.volume.format_float(volume)
         {
-            Ok(v) => Ok(join_strings(v.unwrap_or_default(), formatted_mute)),
+            Ok(Some(v)) => Ok(StatusBlock { full_text : join_strings(v.text, formatted_mute), state : v.state, ..Default::default() }),
+            Ok(None) => Ok(StatusBlock::from(formatted_mute)),
             Err(FormattingError::EmptyMap { numeric_fallback }) => Err(FormattingError::EmptyMap { numeric_fallback: join_strings(numeric_fallback, formatted_mute) }),
         }
     }
@@ -57,6 +74,23 @@ enum FormatableMute {
 }
 
 impl FormatableMute {
+    fn config_schema() -> serde_json::Value {
+        serde_json::json!({
+            "type" : "object",
+            "oneOf" : [
+                { "properties" : { "Format" : { "const" : "Off" } }, "required" : ["Format"] },
+                {
+                    "properties" : {
+                        "Format" : { "const" : "Symbol" },
+                        "Label" : { "type" : "string" },
+                        "MuteSymbol" : { "type" : "string" },
+                        "UnmuteSymbol" : { "type" : "string" }
+                    },
+                    "required" : ["Format", "Label", "MuteSymbol", "UnmuteSymbol"]
+                }
+            ]
+        })
+    }
     fn format_mute(&self, mute : bool) -> Option<String> {
         match self {
             FormatableMute::Off => { None }
@@ -86,6 +120,9 @@ impl SwayStatusModuleInstance for AlsaVolumeConfig {
             panic!("Pipe creation failed. Call your plumber.")
         }
     }
+    fn validate(&self) -> Vec<String> {
+        self.volume.validate().map(|p| format!("volume: {}", p)).into_iter().collect()
+    }
 }
 
 impl Default for AlsaVolumeConfig {
@@ -94,8 +131,8 @@ impl Default for AlsaVolumeConfig {
             device: "default".into(),
             element: "Master".into(),
             abstraction : SElemAbstraction::None,
-            volume: FormatableFloatValue::Numeric { label: " ".into(), digits: 0 },
-            mute: FormatableMute::Symbol { label : String::new(), mute_symbol : String::from("🔇"), unmute_symbol : String::from("🔊") },
+            volume: FormatableFloatValue::Numeric { label: " ".into(), digits: 0, bin_state_map: Default::default() },
+            mute: FormatableMute::Symbol { label : String::new(), mute_symbol : String::from("{icon:volume_muted}"), unmute_symbol : String::from("{icon:volume_unmuted}") },
             sorting: FieldSorting::MuteVolume,
         }
     }
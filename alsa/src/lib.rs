@@ -6,6 +6,7 @@ mod runnable;
 
 use config::AlsaVolumeConfig;
 
+#[derive(Default)]
 pub struct AlsaVolumePlugin;
 impl SwayStatusModule for AlsaVolumePlugin {
     fn get_name(&self) -> &str {
@@ -26,12 +27,22 @@ This is a volume display for ALSA. Currently quite limited, but hey, you're free
 Blanace is not supported at the moment, just volume of a single element."#
         );
     }
+    fn get_config_schema(&self) -> Option<String> {
+        Some(AlsaVolumeConfig::config_schema().to_string())
+    }
 }
 
 impl AlsaVolumePlugin {
-    fn new() -> Self {
+    /// The optional capabilities of this plugin.
+    pub const FEATURES : PluginFeatures = PluginFeatures::STRUCTURED_UPDATES;
+
+    pub fn new() -> Self {
         Self
     }
 }
 
-declare_swaystatus_module!(AlsaVolumePlugin, AlsaVolumePlugin::new);
+//Left out if the plugin is compiled into the main program, as the exports would clash there.
+#[cfg(not(feature = "builtin"))]
+declare_swaystatus_abi_module!(AlsaVolumePlugin, AlsaVolumePlugin::new, AlsaVolumePlugin::FEATURES);
+#[cfg(not(feature = "builtin"))]
+declare_swaystatus_module_name!("AlsaVolume");
@@ -116,7 +116,7 @@ impl<'r> AlsaVolumeRunnable<'r> {
             Some(volume) => {
                 let formatted_volume = self.config.format_volume(volume.volume, volume.mute);
                 match formatted_volume {
-                    Ok(msg) => { self.to_main.send_update(Ok(msg)) }
+                    Ok(block) => { self.to_main.send_block_update(Ok(block)) }
                     Err(e) => {
                         let full_message = e.to_string();
                         match e {
@@ -2,6 +2,7 @@ use serde::{Serialize, Deserialize};
 use swaystatus_plugin::*;
 use std::sync::mpsc::*;
 
+#[derive(Default)]
 pub struct ClockPlugin;
 pub struct ClockRunnable<'c> {
     config : &'c ClockConfig,
@@ -145,6 +146,15 @@ impl SwayStatusModuleInstance for ClockConfig {
          let s = SenderForMain(sender_from_main);
          (Box::new(runnable), Box::new(s))
      }
+     fn validate(&self) -> Vec<String> {
+         let invalid = chrono::format::StrftimeItems::new(&self.format).any(|i| i == chrono::format::Item::Error);
+         if invalid {
+             vec![format!("The Format \"{}\" is not a valid strftime format string.", self.format)]
+         }
+         else {
+             Vec::new()
+         }
+     }
 }
 
 impl SwayStatusModule for ClockPlugin {
@@ -189,10 +199,40 @@ Since it synchronizes with UTC, and time zones are in general offset by multiple
 The other option, "NotSynchronized" is for cases where 30/n minutes as an update rate is not desired. This simply waits approximately Seconds seconds between updates, but does not care about any synchronization to UTC. In other words, if you set this to 24 hours update rate, but launch the program at noon, that's when your date will update instead of midnight. Long story short, this mode only exists because it was easy to implement, and will probably never be useful to anybody."#
 );
     }
+    fn get_config_schema(&self) -> Option<String> {
+        Some(serde_json::json!({
+            "type" : "object",
+            "properties" : {
+                "Format" : { "type" : "string", "description" : "strftime format string of the time." },
+                "RefreshRate" : {
+                    "type" : "object",
+                    "oneOf" : [
+                        {
+                            "properties" : {
+                                "Synchronization" : { "const" : "UtcSynchronized" },
+                                "PerThirtyMinutes" : { "type" : "integer", "minimum" : 0, "maximum" : 65535, "description" : "Number of updates every thirty minutes." }
+                            },
+                            "required" : ["Synchronization", "PerThirtyMinutes"]
+                        },
+                        {
+                            "properties" : {
+                                "Synchronization" : { "const" : "NotSynchronized" },
+                                "Seconds" : { "type" : "number", "description" : "Seconds between updates." }
+                            },
+                            "required" : ["Synchronization", "Seconds"]
+                        }
+                    ]
+                }
+            }
+        }).to_string())
+    }
 }
 
 impl ClockPlugin {
-    fn new() -> ClockPlugin {
+    /// The optional capabilities of this plugin.
+    pub const FEATURES : PluginFeatures = PluginFeatures::NONE;
+
+    pub fn new() -> ClockPlugin {
         ClockPlugin
     }
 }
@@ -213,4 +253,8 @@ impl MsgMainToModule for SenderForMain {
     }
 }
 
-declare_swaystatus_module!(ClockPlugin, ClockPlugin::new);
+//Left out if the plugin is compiled into the main program, as the exports would clash there.
+#[cfg(not(feature = "builtin"))]
+declare_swaystatus_abi_module!(ClockPlugin, ClockPlugin::new, ClockPlugin::FEATURES);
+#[cfg(not(feature = "builtin"))]
+declare_swaystatus_module_name!("ClockPlugin");
@@ -5,6 +5,7 @@ use std::collections::BTreeMap;
 use std::ops::{Add, Sub};
 use std::str::FromStr;
 use std::num::{ParseIntError,IntErrorKind};
+use swaystatus_plugin::BlockState;
 
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "Format")]
@@ -14,16 +15,59 @@ pub enum FormatableFloatValue<KeyTypeMetadata : KeyBackingTypeMetadata> {
         #[serde(rename = "Label")]
         label : String,
         #[serde(rename = "DecimalDigits")]
-        digits : u8
+        digits : u8,
+        #[serde(rename = "PercentToStateMap", default = "BTreeMap::new", skip_serializing_if = "BTreeMap::is_empty")]
+        bin_state_map : BTreeMap<FormatableFloatKey<KeyTypeMetadata>,BlockState>
     },
     Binned {
         #[serde(rename = "Label")]
         label: String,
         #[serde(rename = "PercentToSymbolMap")]
-        bin_symbol_map : BTreeMap<FormatableFloatKey<KeyTypeMetadata>,String>
+        bin_symbol_map : BTreeMap<FormatableFloatKey<KeyTypeMetadata>,BinnedSymbol>
     }
 }
 
+/// Value of a bin. Either just the symbol, or the symbol and the state the element should be in
+/// while the value is in this bin.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum BinnedSymbol {
+    Symbol(String),
+    WithState {
+        #[serde(rename = "Symbol")]
+        symbol : String,
+        #[serde(rename = "State")]
+        state : BlockState
+    }
+}
+
+impl BinnedSymbol {
+    fn symbol(&self) -> &str {
+        match self {
+            BinnedSymbol::Symbol(symbol) | BinnedSymbol::WithState { symbol, .. } => symbol
+        }
+    }
+    fn state(&self) -> BlockState {
+        match self {
+            BinnedSymbol::Symbol(_) => BlockState::default(),
+            BinnedSymbol::WithState { state, .. } => *state
+        }
+    }
+}
+
+impl From<&str> for BinnedSymbol {
+    fn from(symbol : &str) -> Self {
+        BinnedSymbol::Symbol(String::from(symbol))
+    }
+}
+
+/// A formatted value, together with the state the thresholds assign to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormattedFloat {
+    pub text : String,
+    pub state : BlockState
+}
+
 #[derive(Debug)]
 pub enum FormattingError {
     EmptyMap {
@@ -40,30 +84,93 @@ impl std::fmt::Display for FormattingError {
 impl std::error::Error for FormattingError {}
 
 impl<KeyTypeMetadata : KeyBackingTypeMetadata> FormatableFloatValue<KeyTypeMetadata> {
-    pub fn format_float(&self, float : f32) -> Result<Option<String>, FormattingError> {
+    pub fn format_float(&self, float : f32) -> Result<Option<FormattedFloat>, FormattingError> {
         match self {
-            FormatableFloatValue::Numeric{ label, digits } => { Ok(Some(Self::format_float_numeric(float, label, *digits))) }
+            FormatableFloatValue::Numeric{ label, digits, bin_state_map } => { 
+                let state = Self::find_bin(float, bin_state_map).copied().unwrap_or_default();
+                Ok(Some(FormattedFloat { text : Self::format_float_numeric(float, label, *digits), state }))
+            }
             FormatableFloatValue::Binned{ label, bin_symbol_map } => { Some(Self::format_float_binned(float, label, bin_symbol_map)).transpose()}
             FormatableFloatValue::Off => {Ok(None)}
         }
     }
-    pub fn format_float_binned(float : f32, label : &str, bin_symbol_map : &BTreeMap<FormatableFloatKey<KeyTypeMetadata>, String>) -> Result<String,FormattingError> {
-        let value_to_match = FormatableFloatKey::<KeyTypeMetadata>::match_float(float);
-        //first try to find the next lower value.
-        if let Some((_,msg)) = bin_symbol_map.range(..=value_to_match).next_back() {
-            Ok(format!("{}{}",label,msg))
-        }
-        else if let Some((_,msg)) = bin_symbol_map.iter().next() {
-            Ok(format!("{}{}",label,msg))
+    /// JSON Schema of the value, for `SwayStatusModule::get_config_schema`.
+    pub fn config_schema() -> serde_json::Value {
+        let keys = serde_json::json!({
+            "pattern" : "^-?[0-9]+$",
+            "description" : format!("A whole percentage from {} to {}.", KeyTypeMetadata::MIN, KeyTypeMetadata::MAX)
+        });
+        let state = serde_json::json!({ "enum" : ["Idle", "Info", "Good", "Warning", "Critical"] });
+        serde_json::json!({
+            "type" : "object",
+            "oneOf" : [
+                {
+                    "properties" : { "Format" : { "const" : "Off" } },
+                    "required" : ["Format"]
+                },
+                {
+                    "properties" : {
+                        "Format" : { "const" : "Numeric" },
+                        "Label" : { "type" : "string" },
+                        "DecimalDigits" : { "type" : "integer", "minimum" : 0, "maximum" : 255 },
+                        "PercentToStateMap" : {
+                            "type" : "object",
+                            "description" : "The state of the element from each percentage on.",
+                            "propertyNames" : keys,
+                            "additionalProperties" : state
+                        }
+                    },
+                    "required" : ["Format", "Label", "DecimalDigits"]
+                },
+                {
+                    "properties" : {
+                        "Format" : { "const" : "Binned" },
+                        "Label" : { "type" : "string" },
+                        "PercentToSymbolMap" : {
+                            "type" : "object",
+                            "description" : "The symbol shown from each percentage on.",
+                            "propertyNames" : keys,
+                            "additionalProperties" : {
+                                "anyOf" : [
+                                    { "type" : "string" },
+                                    {
+                                        "type" : "object",
+                                        "properties" : { "Symbol" : { "type" : "string" }, "State" : state },
+                                        "required" : ["Symbol", "State"]
+                                    }
+                                ]
+                            }
+                        }
+                    },
+                    "required" : ["Format", "Label", "PercentToSymbolMap"]
+                }
+            ]
+        })
+    }
+    /// Finds configurations that can't be formatted, for `SwayStatusModuleInstance::validate`.
+    pub fn validate(&self) -> Option<String> {
+        match self {
+            FormatableFloatValue::Binned { bin_symbol_map, .. } if bin_symbol_map.is_empty() => Some(String::from("The PercentToSymbolMap is empty.")),
+            _ => None
         }
-        else {
-            Err(FormattingError::EmptyMap{numeric_fallback : Self::format_float_numeric(float, label, 0) })
+    }
+    pub fn format_float_binned(float : f32, label : &str, bin_symbol_map : &BTreeMap<FormatableFloatKey<KeyTypeMetadata>, BinnedSymbol>) -> Result<FormattedFloat,FormattingError> {
+        //values below the lowest bin are shown with the symbol of the lowest bin.
+        match Self::find_bin(float, bin_symbol_map).or_else(|| bin_symbol_map.values().next()) {
+            Some(bin) => Ok(FormattedFloat { text : format!("{}{}",label,bin.symbol()), state : bin.state() }),
+            None => Err(FormattingError::EmptyMap{numeric_fallback : Self::format_float_numeric(float, label, 0) })
         }
     }
     pub fn format_float_numeric(float : f32, label : &str, digits : u8) -> String {
         let percentage = 100.0*float;
         format!("{}{:.*}%", label, digits as usize, percentage)
     }
+    /// Finds the bin with the largest key that's smaller than or equal to the value. Returns None
+    /// if the value is below the smallest key.
+    fn find_bin<V>(float : f32, bin_map : &BTreeMap<FormatableFloatKey<KeyTypeMetadata>, V>) -> Option<&V> {
+        let value_to_match = FormatableFloatKey::<KeyTypeMetadata>::match_float(float);
+        bin_map.range(..=value_to_match).next_back().map(|(_, value)| value)
+    }
 }
 
 ///Helper trait for conversion from float to integer backing type for binning keys.
@@ -152,3 +259,56 @@ macro_rules! impl_key_backing_type_from_float_for {
     }
 }
 impl_key_backing_type_from_float_for!(i8, u8, i16, u16, i32, u32, i64, u64, isize, usize, i128, u128);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(PartialEq, Eq, PartialOrd, Ord, Serialize)]
+    struct Percent;
+    impl KeyBackingTypeMetadata for Percent {
+        type BackingType = u8;
+        const MIN : Self::BackingType = 0;
+        const MAX : Self::BackingType = 100;
+        const FLOAT_MIN : f32 = 0.0;
+        const FLOAT_MAX : f32 = 1.0;
+    }
+
+    #[test]
+    fn binned_symbol_carries_state() {
+        let mut bin_symbol_map = BTreeMap::new();
+        bin_symbol_map.insert(FormatableFloatKey::<Percent>(0), BinnedSymbol::WithState { symbol : String::from("low"), state : BlockState::Critical });
+        bin_symbol_map.insert(FormatableFloatKey(10), "ok".into());
+        let value = FormatableFloatValue::Binned { label : String::new(), bin_symbol_map };
+        assert_eq!(value.format_float(0.05).unwrap(), Some(FormattedFloat { text : String::from("low"), state : BlockState::Critical }));
+        assert_eq!(value.format_float(0.5).unwrap(), Some(FormattedFloat { text : String::from("ok"), state : BlockState::Idle }));
+    }
+
+    #[test]
+    fn numeric_state_thresholds() {
+        let mut bin_state_map = BTreeMap::new();
+        bin_state_map.insert(FormatableFloatKey::<Percent>(90), BlockState::Warning);
+        let value = FormatableFloatValue::Numeric { label : String::new(), digits : 0, bin_state_map };
+        assert_eq!(value.format_float(0.5).unwrap().unwrap().state, BlockState::Idle);
+        assert_eq!(value.format_float(0.95).unwrap().unwrap(), FormattedFloat { text : String::from("95%"), state : BlockState::Warning });
+    }
+
+    #[test]
+    fn schema_knows_all_fields() {
+        let schema = FormatableFloatValue::<Percent>::config_schema();
+        let mut bin_state_map = BTreeMap::new();
+        bin_state_map.insert(FormatableFloatKey::<Percent>(90), BlockState::Warning);
+        let value = serde_json::to_value(FormatableFloatValue::Numeric { label : String::new(), digits : 0, bin_state_map }).unwrap();
+        let properties = schema["oneOf"][1]["properties"].as_object().unwrap();
+        assert!(value.as_object().unwrap().keys().all(|k| properties.contains_key(k)));
+        assert_eq!(properties["PercentToStateMap"]["propertyNames"]["description"], "A whole percentage from 0 to 100.");
+    }
+
+    #[test]
+    fn empty_symbol_map_is_reported() {
+        let value = FormatableFloatValue::<Percent>::Binned { label : String::new(), bin_symbol_map : BTreeMap::new() };
+        assert!(value.validate().is_some());
+        let value = FormatableFloatValue::<Percent>::Numeric { label : String::new(), digits : 0, bin_state_map : BTreeMap::new() };
+        assert!(value.validate().is_none());
+    }
+}
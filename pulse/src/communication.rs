@@ -4,7 +4,8 @@ use swaystatus_plugin::*;
 
 pub enum MessagesFromMain {
     Quit,
-    Refresh
+    Refresh,
+    ToggleMute
 }
 
 pub struct SenderForMain {
@@ -40,6 +41,12 @@ impl MsgMainToModule for SenderForMain {
     fn send_refresh(&self) -> Result<(), PluginCommunicationError> {
         self.send(MessagesFromMain::Refresh)
     }
+    fn send_click_event(&self, event : &ClickEvent) -> Result<(), PluginCommunicationError> {
+        match event.button {
+            MouseButton::Left => self.send(MessagesFromMain::ToggleMute),
+            _ => Ok(())
+        }
+    }
 }
 
 impl From<PulseWakeUpError> for PluginCommunicationError {
@@ -2,7 +2,7 @@ use std::collections::BTreeMap;
 
 use serde::{Serialize,Deserialize};
 use swaystatus_plugin::*;
-use formatable_float::{FormatableFloatValue, FormattingError, KeyBackingTypeMetadata, FormatableFloatKey};
+use formatable_float::{FormatableFloatValue, FormattingError, FormattedFloat, KeyBackingTypeMetadata, FormatableFloatKey};
 
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "Sink")]
@@ -49,21 +49,42 @@ pub struct PulseVolumeConfig {
 }
 
 impl PulseVolumeConfig {
-    pub(crate) fn format_volume(&self, volume : f32, balance : f32, mute : bool) -> Result<String,FormattingError> {
+    /// JSON Schema of the config, for `SwayStatusModule::get_config_schema`.
+    pub(crate) fn config_schema() -> serde_json::Value {
+        serde_json::json!({
+            "type" : "object",
+            "properties" : {
+                "Sorting" : { "enum" : ["MuteVolumeBalance", "MuteBalanceVolume", "VolumeMuteBalance", "VolumeBalanceMute", "BalanceMuteVolume", "BalanceVolumeMute"] },
+                "Sink" : {
+                    "type" : "object",
+                    "oneOf" : [
+                        { "properties" : { "Sink" : { "const" : "Default" } }, "required" : ["Sink"] },
+                        { "properties" : { "Sink" : { "const" : "Specific" }, "SinkName" : { "type" : "string" } }, "required" : ["Sink", "SinkName"] }
+                    ]
+                },
+                "Volume" : FormatableFloatValue::<VolumeKeyVolume>::config_schema(),
+                "Balance" : FormatableFloatValue::<VolumeKeyBalance>::config_schema(),
+                "Mute" : FormatableMute::config_schema()
+            }
+        })
+    }
+
+    pub(crate) fn format_volume(&self, volume : f32, balance : f32, mute : bool) -> Result<StatusBlock,FormattingError> {
         let formatted_volume = self.volume.format_float(volume);
         let formatted_balance = self.balance.format_float(balance);
         let have_errors_occured = formatted_volume.is_err() || formatted_balance.is_err();
         let formatted_mute_option = self.mute.format_mute(mute);
         let formatted_mute = formatted_mute_option.as_deref().unwrap_or("");
-        let get_numeric_fallback = |x| -> Option<String> { 
+        let get_numeric_fallback = |x| -> Option<FormattedFloat> { 
             match x {
-                FormattingError::EmptyMap{ numeric_fallback } => { Some(numeric_fallback) } 
+                FormattingError::EmptyMap{ numeric_fallback } => { Some(FormattedFloat { text : numeric_fallback, state : BlockState::default() }) } 
             }
         };
         let formatted_volume = formatted_volume.unwrap_or_else(get_numeric_fallback);
-        let formatted_volume = formatted_volume.as_deref().unwrap_or("");
         let formatted_balance = formatted_balance.unwrap_or_else(get_numeric_fallback);
-        let formatted_balance = formatted_balance.as_deref().unwrap_or("");
+        let state = formatted_volume.iter().chain(formatted_balance.iter()).map(|f| f.state).max().unwrap_or_default();
+        let formatted_volume = formatted_volume.as_ref().map_or("", |f| &f.text);
+        let formatted_balance = formatted_balance.as_ref().map_or("", |f| &f.text);
 
         let sorted_values = match self.sorting {
             FieldSorting::BalanceMuteVolume => {[formatted_balance, formatted_mute, formatted_volume]}
@@ -78,7 +99,7 @@ impl PulseVolumeConfig {
             Err(FormattingError::EmptyMap{ numeric_fallback : formatted_string })
         }
         else {
-            Ok(formatted_string)
+            Ok(StatusBlock { full_text : formatted_string, state, ..Default::default() })
         }
     }
 }
@@ -87,18 +108,26 @@ impl Default for PulseVolumeConfig {
     fn default() -> Self {
         PulseVolumeConfig {
             sink : Sink::Default,
-            volume : FormatableFloatValue::Numeric { label : String::from(""), digits : 0 },
+            volume : FormatableFloatValue::Numeric { 
+                label : String::from(""), 
+                digits : 0,
+                bin_state_map : {
+                    let mut a = BTreeMap::new();
+                    a.insert(FormatableFloatKey(101), BlockState::Warning);
+                    a
+                }
+            },
             balance : FormatableFloatValue::Binned { 
                 label : String::from(" "), 
                 bin_symbol_map : {
                     let mut a = BTreeMap::new(); 
-                    a.insert(FormatableFloatKey(-100),String::from("|.."));
-                    a.insert(FormatableFloatKey(-10), String::from(".|."));
-                    a.insert(FormatableFloatKey(10), String::from("..|"));
+                    a.insert(FormatableFloatKey(-100),"{icon:balance_left}".into());
+                    a.insert(FormatableFloatKey(-10), "{icon:balance_center}".into());
+                    a.insert(FormatableFloatKey(10), "{icon:balance_right}".into());
                     a
                 }
             },
-            mute : FormatableMute::Symbol { label : String::new(), mute_symbol : String::from("🔇"), unmute_symbol : String::from("🔊") },
+            mute : FormatableMute::Symbol { label : String::new(), mute_symbol : String::from("{icon:volume_muted}"), unmute_symbol : String::from("{icon:volume_unmuted}") },
             sorting : FieldSorting::MuteVolumeBalance,
         }
     }
@@ -109,9 +138,31 @@ impl SwayStatusModuleInstance for PulseVolumeConfig {
         let (runnable, sender_for_main) = crate::runnable::PulseVolumeRunnable::new(self, to_main);
         (Box::new(runnable), Box::new(sender_for_main))
     }
+    fn validate(&self) -> Vec<String> {
+        self.volume.validate().map(|p| format!("Volume: {}", p)).into_iter()
+            .chain(self.balance.validate().map(|p| format!("Balance: {}", p)))
+            .collect()
+    }
 }
 
 impl FormatableMute {
+    fn config_schema() -> serde_json::Value {
+        serde_json::json!({
+            "type" : "object",
+            "oneOf" : [
+                { "properties" : { "Format" : { "const" : "Off" } }, "required" : ["Format"] },
+                {
+                    "properties" : {
+                        "Format" : { "const" : "Symbol" },
+                        "Label" : { "type" : "string" },
+                        "MuteSymbol" : { "type" : "string" },
+                        "UnmuteSymbol" : { "type" : "string" }
+                    },
+                    "required" : ["Format", "Label", "MuteSymbol", "UnmuteSymbol"]
+                }
+            ]
+        })
+    }
     fn format_mute(&self, mute : bool) -> Option<String> {
         match self {
             FormatableMute::Off => { None }
@@ -124,12 +175,13 @@ struct VolumeKeyVolume;
 #[derive(PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 struct VolumeKeyBalance;
 
+//Pulse allows to amplify above 100%. Keys up to 255% are supported, above that the last bin is used.
 impl KeyBackingTypeMetadata for VolumeKeyVolume{
     type BackingType = u8;
     const MIN : Self::BackingType = 0;
-    const MAX : Self::BackingType = 100;
+    const MAX : Self::BackingType = 255;
     const FLOAT_MIN : f32 = 0.0;
-    const FLOAT_MAX : f32 = 1.0;
+    const FLOAT_MAX : f32 = 2.55;
 }
 
 impl KeyBackingTypeMetadata for VolumeKeyBalance{
@@ -6,6 +6,7 @@ mod communication;
 
 use config::*;
 
+#[derive(Default)]
 pub struct PulseVolumePlugin;
 impl SwayStatusModule for PulseVolumePlugin {
     fn get_name(&self) -> &str {
@@ -39,7 +40,10 @@ Label = <if Format != "Off" string to print in front of actual value. Omit if Fo
 DecimalDigits = <if Format = "Numeric" the number of digits after the comma. Omit otherwise.>
 
 [Element.Config.Volume.PercentToSymbolMap]
-<if Format = "Binned" this map has to be filled in. It's a set of key-value-pairs where the key denotes the lower limit of a range. For instance writing 20 = "AAA" makes volumes above 20% print AAA. See sample config -s for an example.>
+<if Format = "Binned" this map has to be filled in. It's a set of key-value-pairs where the key denotes the lower limit of a range. For instance writing 20 = "AAA" makes volumes above 20% print AAA. See sample config -s for an example. Keys up to 255 are allowed, as pulseaudio can amplify above 100%. Instead of just a symbol, the value can also be a table like {{ Symbol = "AAA", State = "Critical" }}. The State (one of "Idle", "Info", "Good", "Warning", "Critical") is used by swaystatus to pick a color.>
+
+[Element.Config.Volume.PercentToStateMap]
+<optional, only if Format = "Numeric". Works like the PercentToSymbolMap, but the values are states. For instance 101 = "Warning" colors the volume if it's above 100%.>
 
 [Element.Config.Balance]
 Format = ["Off", "Numeric", "Binned"]
@@ -47,7 +51,9 @@ Label = <if Format != "Off" string to print in front of actual value. Omit if Fo
 DecimalDigits = <if Format = "Numeric" the number of digits after the comma. Omit otherwise.>
 
 [Element.Config.Balance.PercentToSymbolMap]
-<see Element.Config.Volume.PercentToSymbolMap for details. It's the same thing, just allows negative values>
+<see Element.Config.Volume.PercentToSymbolMap for details. It's the same thing, just allows negative values down to -100 and positive values up to 100. The same goes for the PercentToStateMap.>
+
+If both, volume and balance have a state, the more severe one is used.
 
 [Element.Config.Mute]
 Format = ["Off", "Symbol"]
@@ -57,15 +63,27 @@ UnmuteSymbol = <if Format = "Symbol" the string to display if unmuted. Omit if F
 
 Sorry if this is overly complicated. Using the -s command line switch to get a sample configuration should clarify things.
 
+If the output format of swaystatus is set to I3bar, a left click on the element toggles mute of the observed sink.
+
 
 Thanks to Jason White, whose gist https://gist.github.com/jasonwhite/1df6ee4b5039358701d2 was immensely helpful when it comes to interaction with the pulseaudio daemon."#);
     }
+    fn get_config_schema(&self) -> Option<String> {
+        Some(PulseVolumeConfig::config_schema().to_string())
+    }
 }
 
 impl PulseVolumePlugin {
-    fn new() -> Self {
+    /// The optional capabilities of this plugin.
+    pub const FEATURES : PluginFeatures = PluginFeatures::CLICK_EVENTS.union(PluginFeatures::STRUCTURED_UPDATES);
+
+    pub fn new() -> Self {
         Self
     }
 }
 
-declare_swaystatus_module!(PulseVolumePlugin, PulseVolumePlugin::new);
+//Left out if the plugin is compiled into the main program, as the exports would clash there.
+#[cfg(not(feature = "builtin"))]
+declare_swaystatus_abi_module!(PulseVolumePlugin, PulseVolumePlugin::new, PulseVolumePlugin::FEATURES);
+#[cfg(not(feature = "builtin"))]
+declare_swaystatus_module_name!("PulseVolume");
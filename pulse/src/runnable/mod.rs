@@ -35,7 +35,7 @@ impl<'p : 's, 's> PulseVolumeRunnable<'p> {
     fn format_and_send_updated_volume_to_main(&self, volume : &pulse::Volume) -> Result<(),PluginCommunicationError> {
         let formatted_volume = self.config.format_volume(volume.volume, volume.balance, volume.muted);
         match formatted_volume {
-            Ok(msg) => { self.to_main.send_update(Ok(msg)) }
+            Ok(block) => { self.to_main.send_block_update(Ok(block)) }
             Err(e) => {
                 let full_message = e.to_string();
                 match e {
@@ -171,6 +171,13 @@ impl<'p> SwayStatusModuleRunnable for PulseVolumeRunnable<'p> {
                                 }
                             }
                         }
+                        MessagesFromMain::ToggleMute => {
+                            //The resulting change of the sink is reported by the subscription, so
+                            //there's no need to keep track of the operation.
+                            if let (Some(s), Some(v)) = (&sink_we_care_about, &curr_volume) {
+                                drop(context.set_mute(s, !v.muted));
+                            }
+                        }
                     }
                     Err(e) => {
                         if let TryRecvError::Disconnected = e {
@@ -94,6 +94,10 @@ impl<'c> PulseContext<'c> {
         unsafe {pa_context_get_sink_info_by_name(self.context,sink.sink.as_ptr(),Some(Self::on_sink_info_received),self.scratch as *mut ContextScratch as *mut c_void).try_into()}
     }
 
+    pub(super) fn set_mute(&mut self, sink : &SinkHandle, mute : bool) -> Result<PulseOperation, InvalidOperationError> {
+        unsafe {pa_context_set_sink_mute_by_name(self.context,sink.sink.as_ptr(),mute as c_int,None,std::ptr::null_mut()).try_into()}
+    }
+
     extern "C" fn on_context_state_change(context : *mut PaContext, scratch : *mut c_void) {
         unsafe {
             if let PaContextState::Ready = pa_context_get_state(context) {
@@ -533,6 +537,8 @@ extern "C" {
     fn pa_context_get_sink_info_by_index(_: *mut PaContext, sink_index : u32, callback : Option<PaSinkInfoCb>, scratch : *mut c_void) -> *mut PaOperation;
     fn pa_context_get_sink_info_by_name(_: *mut PaContext, sink : *const c_char, callback : Option<PaSinkInfoCb>, scratch : *mut c_void) -> *mut PaOperation;
     #[must_use]
+    fn pa_context_set_sink_mute_by_name(_: *mut PaContext, sink : *const c_char, mute : c_int, callback : Option<PaContextSuccessCb>, scratch : *mut c_void) -> *mut PaOperation;
+    #[must_use]
     fn pa_context_get_server_info(_: *mut PaContext, callback : Option<PaServerInfoCb>, scratch : *mut c_void) -> *mut PaOperation;
 
     fn pa_cvolume_avg(volume : *const PaCVolume) -> u32;
@@ -0,0 +1,165 @@
+/*
+ * The stable swaystatus plugin interface, for plugins written in languages other than Rust.
+ *
+ * This is the C view of the `abi` module of the swaystatus-plugin crate. Please see the
+ * documentation there for the details, in short:
+ * - A plugin exports `swaystatus_module_name`, `swaystatus_abi_plugin_info` and
+ *   `swaystatus_abi_module_create` (with a leading underscore, see the declarations at the end of
+ *   this file). The main program accepts plugins with the same major and a lower or equal minor
+ *   version.
+ * - Every object carries its size, an opaque data pointer, and function pointers that get the
+ *   data pointer as first parameter. New functions are only ever appended.
+ * - Objects are never returned by value. The plugin writes them to memory provided by the main
+ *   program, which is zeroed and has the size of the main program's version of the struct. Set
+ *   size to sizeof the struct as declared in this header. Functions marked "Since" are NULL if
+ *   the plugin was built for an older version, and a plugin may leave them NULL.
+ * - Configs are exchanged as JSON text. Strings are UTF-8 and not null-terminated. Strings passed
+ *   as parameters are only valid during the call.
+ * - Objects with a destroy function are owned by whoever received them. The module outlives its
+ *   instances, an instance outlives the runnable and the message objects made from it.
+ */
+
+#ifndef SWAYSTATUS_PLUGIN_H
+#define SWAYSTATUS_PLUGIN_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define SWAYSTATUS_ABI_VERSION_MAJOR 1u
+#define SWAYSTATUS_ABI_VERSION_MINOR 1u
+
+/* Optional capabilities. Main only forwards clicks to plugins with SWAYSTATUS_FEATURE_CLICK_EVENTS,
+ * and only uses more than the text of blocks from plugins with SWAYSTATUS_FEATURE_STRUCTURED_UPDATES. */
+typedef uint64_t SwaystatusFeatures;
+#define SWAYSTATUS_FEATURE_CLICK_EVENTS 1u
+#define SWAYSTATUS_FEATURE_STRUCTURED_UPDATES 2u
+
+/* Fill in SWAYSTATUS_ABI_VERSION_MAJOR and SWAYSTATUS_ABI_VERSION_MINOR. This struct never grows. */
+typedef struct {
+    uint32_t version_major;
+    uint32_t version_minor;
+    SwaystatusFeatures features;
+} SwaystatusPluginInfo;
+
+typedef struct {
+    const uint8_t *ptr;
+    size_t len;
+} SwaystatusStr;
+
+/* Copies a string that needs to outlive the call. May be called several times, the parts are
+ * appended. */
+typedef struct {
+    void *context;
+    void (*write)(void *context, SwaystatusStr text);
+} SwaystatusStringSink;
+
+typedef uint32_t SwaystatusUpdateKind;
+/* The payload is the new text. */
+#define SWAYSTATUS_UPDATE_TEXT 0u
+/* The payload is an i3bar block as JSON, see swaybar-protocol(7). Fields swaystatus sets itself,
+ * like "name" or "separator", are ignored. The additional field "state" accepts "Idle", "Info",
+ * "Good", "Warning" or "Critical". */
+#define SWAYSTATUS_UPDATE_BLOCK 1u
+/* The payload is an error message for stderr. */
+#define SWAYSTATUS_UPDATE_PRINT_TO_STDERR 2u
+/* The payload is an error message to show instead of the text. No further updates are expected. */
+#define SWAYSTATUS_UPDATE_SHOW_INSTEAD_OF_TEXT 3u
+
+typedef struct {
+    /* The X11 button number. */
+    uint32_t button;
+    const SwaystatusStr *modifiers;
+    size_t modifier_count;
+    int32_t x;
+    int32_t y;
+    int32_t relative_x;
+    int32_t relative_y;
+    int32_t width;
+    int32_t height;
+} SwaystatusClickEvent;
+
+/* Implemented by the main program. May be used from any thread. */
+typedef struct {
+    size_t size;
+    void *data;
+    /* Returns false if main isn't listening any more. */
+    bool (*send_update)(void *data, SwaystatusUpdateKind kind, SwaystatusStr payload);
+    /* Call this once done. Keep the object alive until run() returned, as main learns through it
+     * whether the plugin crashed. Destroy it at the latest when the runnable is destroyed. */
+    void (*destroy)(void *data);
+} SwaystatusToMain;
+
+/* Implemented by the plugin. Only used from the main thread. The functions return false if the
+ * runnable can't be reached any more. */
+typedef struct {
+    size_t size;
+    void *data;
+    bool (*send_quit)(void *data);
+    bool (*send_refresh)(void *data);
+    bool (*send_click_event)(void *data, const SwaystatusClickEvent *event);
+    void (*destroy)(void *data);
+    /* Since 1.1. A message the user sent to the element with swaystatus ctl. What it means is up
+     * to the plugin. */
+    bool (*send_custom_message)(void *data, SwaystatusStr message);
+} SwaystatusFromMain;
+
+/* Implemented by the plugin. run() and destroy() are called from a worker thread. run() returns
+ * false if the plugin crashed. */
+typedef struct {
+    size_t size;
+    void *data;
+    bool (*run)(void *data);
+    void (*destroy)(void *data);
+} SwaystatusRunnable;
+
+/* Implemented by the plugin. */
+typedef struct {
+    size_t size;
+    void *data;
+    /* Writes the config as JSON. Returns false on failure. */
+    bool (*serialize_config)(void *data, SwaystatusStringSink sink);
+    /* Takes ownership of to_main. Writes the main loop to runnable, and the way main talks to it to
+     * from_main. */
+    void (*make_runnable)(void *data, SwaystatusToMain to_main, SwaystatusRunnable *runnable, SwaystatusFromMain *from_main);
+    void (*destroy)(void *data);
+    /* Since 1.1. Writes the problems found in the config as a JSON array of strings, for
+     * swaystatus --check-config. Returns false on failure. */
+    bool (*validate)(void *data, SwaystatusStringSink problems);
+} SwaystatusInstance;
+
+/* Implemented by the plugin. */
+typedef struct {
+    size_t size;
+    void *data;
+    /* Must stay valid until the module is destroyed. */
+    SwaystatusStr (*get_name)(void *data);
+    /* Writes the new instance to instance. On failure, writes the reason to error instead, and
+     * returns false. */
+    bool (*create_instance)(void *data, SwaystatusStr config, SwaystatusStringSink error, SwaystatusInstance *instance);
+    void (*default_instance)(void *data, SwaystatusInstance *instance);
+    void (*print_help)(void *data);
+    void (*destroy)(void *data);
+    /* Since 1.1. Writes a JSON Schema of the instance config, for swaystatus --print-config-schema.
+     * Returns false if the plugin has none. */
+    bool (*get_config_schema)(void *data, SwaystatusStringSink schema);
+} SwaystatusModule;
+
+/* The exports of a plugin. */
+/* Optional. The same name as get_name(), as UTF-8, optionally followed by a null byte. Main reads
+ * it from the library file, without loading the library, to find the plugins the configuration
+ * needs. It therefore has to be a constant array, not a pointer, for instance
+ * const char _swaystatus_module_name[] = "MyPlugin"; */
+extern const char _swaystatus_module_name[];
+SwaystatusPluginInfo _swaystatus_abi_plugin_info(void);
+void _swaystatus_abi_module_create(SwaystatusModule *module);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
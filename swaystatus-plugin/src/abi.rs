@@ -0,0 +1,491 @@
+//! A stable, C compatible plugin interface.
+//!
+//! The traits of this crate are Rust trait objects, and Rust does not guarantee their layout to be
+//! the same between compiler versions. Plugins using `declare_swaystatus_module!()` therefore need
+//! to be built with exactly the compiler the main program was built with. This module offers an
+//! alternative: The same objects, but expressed as `#[repr(C)]` tables of function pointers. A
+//! plugin that exports these keeps working if the main program is built with a different compiler,
+//! and it doesn't even need to be written in Rust. The C header `include/swaystatus_plugin.h`
+//! describes the same interface for other languages.
+//!
+//! Rust plugins don't need to deal with any of this. They implement the usual traits, and export
+//! the plugin using `declare_swaystatus_abi_module!()` instead of `declare_swaystatus_module!()`.
+//!
+//! ## Conventions
+//! - Every object is a struct holding its size in bytes, an opaque `data` pointer, and function
+//!   pointers that get `data` as their first parameter. New functions are only ever appended, so a
+//!   reader can tell from the size which functions are present.
+//! - Objects are never returned by value. The plugin writes them to memory the main program
+//!   provides, which is zeroed and has the size of the main program's version of the struct.
+//!   Functions appended after version 1.0 stay null if the plugin doesn't know them, which is why
+//!   they are `Option`s.
+//! - Configs are exchanged as JSON text.
+//! - Strings are UTF-8, passed as pointer and length, and are not null-terminated. Strings passed
+//!   as parameters are only valid during the call. Strings that need to outlive the call are
+//!   written to an `AbiStringSink`, which copies them.
+//! - Every object with a `destroy` function is owned by whoever received it, and must be
+//!   destroyed exactly once. The same lifetime rules as for the Rust traits apply: The module
+//!   outlives its instances, and an instance outlives the runnable and the message objects made
+//!   from it.
+
+use std::borrow::Cow;
+use std::ffi::c_void;
+use crate::{PluginFeatures, SwayStatusModule, SwayStatusModuleInstance, SwayStatusModuleRunnable, MsgMainToModule, MsgModuleToMain, StatusBlock, PluginError, PluginCommunicationError, ClickEvent};
+
+/// Major version of the interface described in this module. Changes if the interface changes
+/// in an incompatible way.
+pub const ABI_VERSION_MAJOR : u32 = 1;
+/// Minor version of the interface described in this module. Changes if functions are appended.
+/// The main program accepts plugins with the same major and a lower or equal minor version.
+pub const ABI_VERSION_MINOR : u32 = 1;
+
+/// What a plugin tells about itself before anything else. Exported as
+/// `_swaystatus_abi_plugin_info()`. Unlike the other structs this one can't ever grow.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AbiPluginInfo {
+    /// The `ABI_VERSION_MAJOR` the plugin was built with.
+    pub version_major : u32,
+    /// The `ABI_VERSION_MINOR` the plugin was built with.
+    pub version_minor : u32,
+    /// The optional capabilities the plugin supports.
+    pub features : PluginFeatures,
+}
+
+/// A borrowed UTF-8 string.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct AbiStr {
+    /// Pointer to the first byte. May be dangling if `len` is 0.
+    pub ptr : *const u8,
+    /// Length in bytes.
+    pub len : usize,
+}
+
+impl AbiStr {
+    /// Borrows a Rust string. The result must not outlive `s`.
+    pub fn new(s : &str) -> Self {
+        AbiStr { ptr : s.as_ptr(), len : s.len() }
+    }
+    /// Reads the string. Invalid UTF-8 is replaced, as it is most likely meant to be shown anyhow.
+    ///
+    /// # Safety
+    /// `ptr` must point to `len` readable bytes, which stay valid for `'a`.
+    pub unsafe fn to_str_lossy<'a>(self) -> Cow<'a, str> {
+        if self.len == 0 {
+            return Cow::Borrowed("");
+        }
+        String::from_utf8_lossy(std::slice::from_raw_parts(self.ptr, self.len))
+    }
+}
+
+/// Receives a string that needs to outlive the call it was produced in.
+#[repr(C)]
+pub struct AbiStringSink {
+    /// Passed to `write`.
+    pub context : *mut c_void,
+    /// Copies the string. May be called more than once, in which case the parts are appended.
+    pub write : unsafe extern "C" fn(context : *mut c_void, text : AbiStr),
+}
+
+impl AbiStringSink {
+    /// A sink that appends to the given string. The result must not outlive `target`.
+    pub fn for_string(target : &mut String) -> Self {
+        AbiStringSink { context : target as *mut String as *mut c_void, write : append_to_string }
+    }
+}
+
+unsafe extern "C" fn append_to_string(context : *mut c_void, text : AbiStr) {
+    (*(context as *mut String)).push_str(&text.to_str_lossy());
+}
+
+/// What kind of update `AbiToMain::send_update` carries. Not an enum, because receiving an
+/// unknown value for a Rust enum is undefined behaviour.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AbiUpdateKind(pub u32);
+
+impl AbiUpdateKind {
+    /// The payload is the new text.
+    pub const TEXT : AbiUpdateKind = AbiUpdateKind(0);
+    /// The payload is a `StatusBlock` as JSON.
+    pub const BLOCK : AbiUpdateKind = AbiUpdateKind(1);
+    /// The payload is an error, see `PluginError::PrintToStdErr`.
+    pub const PRINT_TO_STDERR : AbiUpdateKind = AbiUpdateKind(2);
+    /// The payload is an error, see `PluginError::ShowInsteadOfText`.
+    pub const SHOW_INSTEAD_OF_TEXT : AbiUpdateKind = AbiUpdateKind(3);
+}
+
+/// A click event. See `ClickEvent` for the meaning of the fields.
+#[repr(C)]
+pub struct AbiClickEvent {
+    /// The X11 button number.
+    pub button : u32,
+    /// Array of `modifier_count` modifier names.
+    pub modifiers : *const AbiStr,
+    /// Length of `modifiers`.
+    pub modifier_count : usize,
+    /// See `ClickEvent::x`.
+    pub x : i32,
+    /// See `ClickEvent::y`.
+    pub y : i32,
+    /// See `ClickEvent::relative_x`.
+    pub relative_x : i32,
+    /// See `ClickEvent::relative_y`.
+    pub relative_y : i32,
+    /// See `ClickEvent::width`.
+    pub width : i32,
+    /// See `ClickEvent::height`.
+    pub height : i32,
+}
+
+/// Implemented by the main program, the counterpart of `MsgModuleToMain`. May be used from any
+/// thread, also concurrently.
+#[repr(C)]
+pub struct AbiToMain {
+    /// Size of this struct in bytes.
+    pub size : usize,
+    /// Opaque data of the main program.
+    pub data : *mut c_void,
+    /// Sends an update. Returns false if main isn't listening any more, see
+    /// `MsgModuleToMain::send_block_update`.
+    pub send_update : unsafe extern "C" fn(data : *mut c_void, kind : AbiUpdateKind, payload : AbiStr) -> bool,
+    /// Must be called once the plugin doesn't need this object any more. The object has to stay
+    /// alive until the runnable's `run` returned, as the main program learns through it whether
+    /// the plugin crashed. Destroy it at the latest when the runnable is destroyed.
+    pub destroy : unsafe extern "C" fn(data : *mut c_void),
+}
+
+/// Implemented by the plugin, the counterpart of `MsgMainToModule`. Only used from the main
+/// thread. The functions return false if the runnable can't be reached any more.
+#[repr(C)]
+pub struct AbiFromMain {
+    /// Size of this struct in bytes.
+    pub size : usize,
+    /// Opaque data of the plugin.
+    pub data : *mut c_void,
+    /// See `MsgMainToModule::send_quit`.
+    pub send_quit : unsafe extern "C" fn(data : *mut c_void) -> bool,
+    /// See `MsgMainToModule::send_refresh`.
+    pub send_refresh : unsafe extern "C" fn(data : *mut c_void) -> bool,
+    /// See `MsgMainToModule::send_click_event`.
+    pub send_click_event : unsafe extern "C" fn(data : *mut c_void, event : *const AbiClickEvent) -> bool,
+    /// Called once main doesn't need this object any more.
+    pub destroy : unsafe extern "C" fn(data : *mut c_void),
+    /// See `MsgMainToModule::send_custom_message`. Added in version 1.1.
+    pub send_custom_message : Option<unsafe extern "C" fn(data : *mut c_void, message : AbiStr) -> bool>,
+}
+
+/// Implemented by the plugin, the counterpart of `SwayStatusModuleRunnable`.
+#[repr(C)]
+pub struct AbiRunnable {
+    /// Size of this struct in bytes.
+    pub size : usize,
+    /// Opaque data of the plugin.
+    pub data : *mut c_void,
+    /// The plugin's main loop. Called from a worker thread. Returns false if the plugin crashed,
+    /// in which case main treats it like any other crashed element.
+    pub run : unsafe extern "C" fn(data : *mut c_void) -> bool,
+    /// Called from the worker thread after `run` returned.
+    pub destroy : unsafe extern "C" fn(data : *mut c_void),
+}
+
+/// Implemented by the plugin, the counterpart of `SwayStatusModuleInstance`. Created and used on
+/// the main thread only.
+#[repr(C)]
+pub struct AbiInstance {
+    /// Size of this struct in bytes.
+    pub size : usize,
+    /// Opaque data of the plugin.
+    pub data : *mut c_void,
+    /// Writes the instance's config as JSON to the sink. Returns false on failure.
+    pub serialize_config : unsafe extern "C" fn(data : *mut c_void, sink : AbiStringSink) -> bool,
+    /// See `SwayStatusModuleInstance::make_runnable`. Writes the main loop to `runnable`, and the
+    /// way main talks to it to `from_main`. The plugin takes ownership of `to_main`.
+    pub make_runnable : unsafe extern "C" fn(data : *mut c_void, to_main : AbiToMain, runnable : *mut AbiRunnable, from_main : *mut AbiFromMain),
+    /// Called once main doesn't need this instance any more.
+    pub destroy : unsafe extern "C" fn(data : *mut c_void),
+    /// See `SwayStatusModuleInstance::validate`. Writes the problems as a JSON array of strings to
+    /// the sink. Returns false on failure. Added in version 1.1.
+    pub validate : Option<unsafe extern "C" fn(data : *mut c_void, problems : AbiStringSink) -> bool>,
+}
+
+/// Implemented by the plugin, the counterpart of `SwayStatusModule`. Written by
+/// `_swaystatus_abi_module_create()`. Used on the main thread only.
+#[repr(C)]
+pub struct AbiModule {
+    /// Size of this struct in bytes.
+    pub size : usize,
+    /// Opaque data of the plugin.
+    pub data : *mut c_void,
+    /// The plugin's name. Must stay valid until the module is destroyed.
+    pub get_name : unsafe extern "C" fn(data : *mut c_void) -> AbiStr,
+    /// Creates an instance from a JSON config, and writes it to `instance`. On failure it returns
+    /// false, and writes a description of the problem to `error` instead.
+    pub create_instance : unsafe extern "C" fn(data : *mut c_void, config : AbiStr, error : AbiStringSink, instance : *mut AbiInstance) -> bool,
+    /// Writes an instance with the default config to `instance`. See
+    /// `SwayStatusModule::get_default_config`.
+    pub default_instance : unsafe extern "C" fn(data : *mut c_void, instance : *mut AbiInstance),
+    /// See `SwayStatusModule::print_help`.
+    pub print_help : unsafe extern "C" fn(data : *mut c_void),
+    /// Called once main doesn't need the module any more.
+    pub destroy : unsafe extern "C" fn(data : *mut c_void),
+    /// See `SwayStatusModule::get_config_schema`. Writes the schema as JSON to the sink. Returns
+    /// false if the plugin has no schema. Added in version 1.1.
+    pub get_config_schema : Option<unsafe extern "C" fn(data : *mut c_void, schema : AbiStringSink) -> bool>,
+}
+
+/// Declares the exports of a plugin that uses the stable interface. Parameters are the same as for
+/// `declare_swaystatus_module!()`: The plugin's concrete type, the constructor function for it,
+/// and optionally the `PluginFeatures` the plugin supports.
+#[macro_export]
+macro_rules! declare_swaystatus_abi_module {
+    ($plugin_type:ty, $constructor:path) => {
+        $crate::declare_swaystatus_abi_module!($plugin_type, $constructor, $crate::PluginFeatures::NONE);
+    };
+    ($plugin_type:ty, $constructor:path, $features:expr) => {
+        #[no_mangle]
+        pub extern "C" fn _swaystatus_abi_plugin_info() -> $crate::abi::AbiPluginInfo {
+            $crate::abi::AbiPluginInfo {
+                version_major : $crate::abi::ABI_VERSION_MAJOR,
+                version_minor : $crate::abi::ABI_VERSION_MINOR,
+                features : $features,
+            }
+        }
+        /// # Safety
+        /// `module` must point to memory for an `AbiModule`.
+        #[no_mangle]
+        pub unsafe extern "C" fn _swaystatus_abi_module_create(module : *mut $crate::abi::AbiModule) {
+            // make sure the constructor is the correct type.
+            let constructor: fn() -> $plugin_type = $constructor;
+            module.write($crate::abi::export_module(Box::new(constructor())));
+        }
+    };
+}
+
+// What follows are the plugin side wrappers. Each one boxes the Rust trait object, and hands out
+// the box as the `data` pointer. The trait objects' lifetimes are erased in the process. That's
+// fine, as the lifetime rules are part of the interface contract.
+
+/// Wraps a Rust module into the stable interface.
+pub fn export_module(module : Box<dyn SwayStatusModule>) -> AbiModule {
+    AbiModule {
+        size : std::mem::size_of::<AbiModule>(),
+        data : Box::into_raw(Box::new(module)) as *mut c_void,
+        get_name : module_get_name,
+        create_instance : module_create_instance,
+        default_instance : module_default_instance,
+        print_help : module_print_help,
+        destroy : module_destroy,
+        get_config_schema : Some(module_get_config_schema),
+    }
+}
+
+unsafe fn module<'a>(data : *mut c_void) -> &'a dyn SwayStatusModule {
+    &**(data as *const Box<dyn SwayStatusModule>)
+}
+
+unsafe extern "C" fn module_get_name(data : *mut c_void) -> AbiStr {
+    AbiStr::new(module(data).get_name())
+}
+
+unsafe extern "C" fn module_create_instance(data : *mut c_void, config : AbiStr, error : AbiStringSink, instance : *mut AbiInstance) -> bool {
+    let config = config.to_str_lossy();
+    let mut deserializer = serde_json::Deserializer::from_str(&config);
+    match module(data).deserialize_config(&mut <dyn erased_serde::Deserializer>::erase(&mut deserializer)) {
+        Ok(i) => {
+            instance.write(export_instance(i));
+            true
+        },
+        Err(e) => {
+            (error.write)(error.context, AbiStr::new(&e.to_string()));
+            false
+        }
+    }
+}
+
+unsafe extern "C" fn module_default_instance(data : *mut c_void, instance : *mut AbiInstance) {
+    instance.write(export_instance(module(data).get_default_config()));
+}
+
+unsafe extern "C" fn module_print_help(data : *mut c_void) {
+    module(data).print_help();
+}
+
+unsafe extern "C" fn module_get_config_schema(data : *mut c_void, schema : AbiStringSink) -> bool {
+    match module(data).get_config_schema() {
+        Some(json) => {
+            (schema.write)(schema.context, AbiStr::new(&json));
+            true
+        },
+        None => false
+    }
+}
+
+unsafe extern "C" fn module_destroy(data : *mut c_void) {
+    drop(Box::from_raw(data as *mut Box<dyn SwayStatusModule>));
+}
+
+fn export_instance(instance : Box<dyn SwayStatusModuleInstance + '_>) -> AbiInstance {
+    let instance : Box<dyn SwayStatusModuleInstance> = unsafe { std::mem::transmute(instance) };
+    AbiInstance {
+        size : std::mem::size_of::<AbiInstance>(),
+        data : Box::into_raw(Box::new(instance)) as *mut c_void,
+        serialize_config : instance_serialize_config,
+        make_runnable : instance_make_runnable,
+        destroy : instance_destroy,
+        validate : Some(instance_validate),
+    }
+}
+
+unsafe fn instance<'a>(data : *mut c_void) -> &'a dyn SwayStatusModuleInstance {
+    &**(data as *const Box<dyn SwayStatusModuleInstance>)
+}
+
+unsafe extern "C" fn instance_serialize_config(data : *mut c_void, sink : AbiStringSink) -> bool {
+    match serde_json::to_string(instance(data)) {
+        Ok(json) => {
+            (sink.write)(sink.context, AbiStr::new(&json));
+            true
+        },
+        Err(_) => false
+    }
+}
+
+unsafe extern "C" fn instance_validate(data : *mut c_void, problems : AbiStringSink) -> bool {
+    match serde_json::to_string(&instance(data).validate()) {
+        Ok(json) => {
+            (problems.write)(problems.context, AbiStr::new(&json));
+            true
+        },
+        Err(_) => false
+    }
+}
+
+unsafe extern "C" fn instance_make_runnable(data : *mut c_void, to_main : AbiToMain, runnable : *mut AbiRunnable, from_main : *mut AbiFromMain) {
+    let (r, f) = instance(data).make_runnable(Box::new(ImportedToMain(to_main)));
+    let r : Box<dyn SwayStatusModuleRunnable> = std::mem::transmute(r);
+    let f : Box<dyn MsgMainToModule> = std::mem::transmute(f);
+    runnable.write(AbiRunnable {
+        size : std::mem::size_of::<AbiRunnable>(),
+        data : Box::into_raw(Box::new(r)) as *mut c_void,
+        run : runnable_run,
+        destroy : runnable_destroy,
+    });
+    from_main.write(AbiFromMain {
+        size : std::mem::size_of::<AbiFromMain>(),
+        data : Box::into_raw(Box::new(f)) as *mut c_void,
+        send_quit : from_main_send_quit,
+        send_refresh : from_main_send_refresh,
+        send_click_event : from_main_send_click_event,
+        destroy : from_main_destroy,
+        send_custom_message : Some(from_main_send_custom_message),
+    });
+}
+
+unsafe extern "C" fn instance_destroy(data : *mut c_void) {
+    drop(Box::from_raw(data as *mut Box<dyn SwayStatusModuleInstance>));
+}
+
+unsafe extern "C" fn runnable_run(data : *mut c_void) -> bool {
+    let runnable = &**(data as *const Box<dyn SwayStatusModuleRunnable>);
+    //Unwinding out of an extern "C" function aborts the whole program. The panic is reported
+    //instead, so main can deal with it as with a crashed thread.
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| runnable.run())).is_ok()
+}
+
+unsafe extern "C" fn runnable_destroy(data : *mut c_void) {
+    drop(Box::from_raw(data as *mut Box<dyn SwayStatusModuleRunnable>));
+}
+
+unsafe fn from_main<'a>(data : *mut c_void) -> &'a dyn MsgMainToModule {
+    &**(data as *const Box<dyn MsgMainToModule>)
+}
+
+unsafe extern "C" fn from_main_send_quit(data : *mut c_void) -> bool {
+    from_main(data).send_quit().is_ok()
+}
+
+unsafe extern "C" fn from_main_send_refresh(data : *mut c_void) -> bool {
+    from_main(data).send_refresh().is_ok()
+}
+
+unsafe extern "C" fn from_main_send_click_event(data : *mut c_void, event : *const AbiClickEvent) -> bool {
+    from_main(data).send_click_event(&import_click_event(&*event)).is_ok()
+}
+
+unsafe extern "C" fn from_main_send_custom_message(data : *mut c_void, message : AbiStr) -> bool {
+    from_main(data).send_custom_message(&message.to_str_lossy()).is_ok()
+}
+
+unsafe extern "C" fn from_main_destroy(data : *mut c_void) {
+    drop(Box::from_raw(data as *mut Box<dyn MsgMainToModule>));
+}
+
+/// The main program's `AbiToMain`, as seen by a Rust plugin.
+struct ImportedToMain(AbiToMain);
+
+// The interface requires AbiToMain to be usable from any thread.
+unsafe impl Send for ImportedToMain {}
+
+impl MsgModuleToMain for ImportedToMain {
+    fn send_block_update(&self, block : Result<StatusBlock, PluginError>) -> Result<(),PluginCommunicationError> {
+        let (kind, payload) = match block {
+            Ok(block) if block == StatusBlock::from(block.full_text.clone()) => (AbiUpdateKind::TEXT, block.full_text),
+            Ok(block) => (AbiUpdateKind::BLOCK, serde_json::to_string(&block).map_err(|_| PluginCommunicationError)?),
+            Err(PluginError::PrintToStdErr(e)) => (AbiUpdateKind::PRINT_TO_STDERR, e),
+            Err(PluginError::ShowInsteadOfText(e)) => (AbiUpdateKind::SHOW_INSTEAD_OF_TEXT, e),
+        };
+        if unsafe { (self.0.send_update)(self.0.data, kind, AbiStr::new(&payload)) } {
+            Ok(())
+        }
+        else {
+            Err(PluginCommunicationError)
+        }
+    }
+}
+
+impl Drop for ImportedToMain {
+    fn drop(&mut self) {
+        unsafe { (self.0.destroy)(self.0.data) }
+    }
+}
+
+/// Converts a click event received through the stable interface.
+///
+/// # Safety
+/// `event.modifiers` must point to `event.modifier_count` valid strings.
+pub unsafe fn import_click_event(event : &AbiClickEvent) -> ClickEvent {
+    let modifiers = if event.modifier_count == 0 {
+        Vec::new()
+    }
+    else {
+        std::slice::from_raw_parts(event.modifiers, event.modifier_count).iter().map(|m| m.to_str_lossy().into_owned()).collect()
+    };
+    ClickEvent {
+        button : event.button.into(),
+        modifiers,
+        x : event.x,
+        y : event.y,
+        relative_x : event.relative_x,
+        relative_y : event.relative_y,
+        width : event.width,
+        height : event.height,
+    }
+}
+
+/// Converts a click event for the stable interface. The result borrows the modifier names from
+/// `modifiers`, which should be made with `AbiStr::new()` from `event.modifiers`.
+pub fn export_click_event(event : &ClickEvent, modifiers : &[AbiStr]) -> AbiClickEvent {
+    AbiClickEvent {
+        button : event.button.into(),
+        modifiers : modifiers.as_ptr(),
+        modifier_count : modifiers.len(),
+        x : event.x,
+        y : event.y,
+        relative_x : event.relative_x,
+        relative_y : event.relative_y,
+        width : event.width,
+        height : event.height,
+    }
+}
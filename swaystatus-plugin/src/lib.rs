@@ -8,6 +8,16 @@
 //!
 //! In addition the plugin must have a constructor function that returns a valid SwayStatusModule.
 //! This can then be exported using the `declare_swaystatus_module()` macro.
+//! Plugins exported that way need to be built with exactly the same compiler version as the main
+//! program, and against exactly the same version of this crate. Any change to the traits, even
+//! adding a method with a default implementation, changes the layout of the trait objects, so the
+//! version of this crate has to be bumped whenever a trait changes.
+//! Alternatively, export the plugin with `declare_swaystatus_abi_module()`. This wraps your types
+//! in the stable interface described in the `abi` module, and frees your plugin from the compiler
+//! version restriction.
+//!
+//! Both macros optionally take the `PluginFeatures` your plugin supports. The main program only
+//! forwards click events, and only uses more than the text of your updates, if you list them.
 //!
 //! The swaystatus main program will call methods on your `SwayStatusModule` during initialization.
 //! All calls to this interface will come from the main thread.
@@ -42,6 +52,9 @@
 //! to do so. The easiest way is to set rustc compiler flags using .cargo/config in the project.
 
 use erased_serde::serialize_trait_object;
+use serde::{Serialize, Deserialize};
+
+pub mod abi;
 
 #[doc(hidden)]
 pub static RUSTC_VERSION : &str = env!("RUSTC_VERSION");
@@ -52,12 +65,16 @@ pub static MODULE_VERSION : &str = env!("CARGO_PKG_VERSION");
 static GLOBAL : std::alloc::System = std::alloc::System;
 
 /// Declares a public export C function that creates your plugin's main object.
-/// parameters are: The plugin's concrete type, and the constructor function for it.
+/// parameters are: The plugin's concrete type, the constructor function for it, and optionally
+/// the `PluginFeatures` the plugin supports.
 /// This is blatantly stolen from
 /// https://michael-f-bryan.github.io/rust-ffi-guide/dynamic_loading.html
 #[macro_export]
 macro_rules! declare_swaystatus_module {
     ($plugin_type:ty, $constructor:path) => {
+        $crate::declare_swaystatus_module!($plugin_type, $constructor, $crate::PluginFeatures::NONE);
+    };
+    ($plugin_type:ty, $constructor:path, $features:expr) => {
         #[no_mangle]
         pub extern "C" fn _swaystatus_module_create() -> *mut dyn $crate::SwayStatusModule {
             // make sure the constructor is the correct type.
@@ -74,9 +91,73 @@ macro_rules! declare_swaystatus_module {
         pub extern "C" fn _swaystatus_rustc_version() -> *const str {
             $crate::RUSTC_VERSION
         }
+        #[no_mangle]
+        pub extern "C" fn _swaystatus_module_features() -> u64 {
+            let features : $crate::PluginFeatures = $features;
+            features.0
+        }
+    };
+}
+
+/// Exports the name of your plugin, which has to be the one `SwayStatusModule::get_name()`
+/// returns, as the byte array `_swaystatus_module_name`. Use it next to either of the other export
+/// macros. The main program reads the name from the library file without loading it, so libraries
+/// the configuration doesn't need never run any of their code. Libraries without it are always
+/// loaded. This symbol is the same for both kinds of plugins, and will never change.
+#[macro_export]
+macro_rules! declare_swaystatus_module_name {
+    ($name:literal) => {
+        #[no_mangle]
+        #[allow(non_upper_case_globals)]
+        pub static _swaystatus_module_name : [u8; $name.len()] = $crate::name_bytes($name);
     };
 }
 
+#[doc(hidden)]
+pub const fn name_bytes<const N : usize>(name : &str) -> [u8; N] {
+    let mut result = [0; N];
+    let mut i = 0;
+    while i < N {
+        result[i] = name.as_bytes()[i];
+        i += 1;
+    }
+    result
+}
+
+/// Optional capabilities a plugin can advertise. The main program only makes use of a capability
+/// if the plugin lists it, so plugins don't need to care about capabilities added after they were
+/// written. Combine them with `|`.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PluginFeatures(pub u64);
+
+impl PluginFeatures {
+    /// No optional capabilities.
+    pub const NONE : PluginFeatures = PluginFeatures(0);
+    /// The plugin handles click events. Without this, the main program doesn't forward clicks.
+    pub const CLICK_EVENTS : PluginFeatures = PluginFeatures(1);
+    /// The plugin sends more than just text with `send_block_update()`. Without this, the main
+    /// program only uses the `full_text` of the blocks it gets.
+    pub const STRUCTURED_UPDATES : PluginFeatures = PluginFeatures(2);
+
+    /// True if all capabilities in `other` are present.
+    pub const fn contains(self, other : PluginFeatures) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The capabilities of both. Same as `|`, but usable in constants.
+    pub const fn union(self, other : PluginFeatures) -> PluginFeatures {
+        PluginFeatures(self.0 | other.0)
+    }
+}
+
+impl std::ops::BitOr for PluginFeatures {
+    type Output = PluginFeatures;
+    fn bitor(self, other : PluginFeatures) -> PluginFeatures {
+        self.union(other)
+    }
+}
+
 /// You need to implement this trait, as creating a runnable needs to return this type as well.
 /// A typical implementation would be a thin wrapper around a channel's sender end.
 /// Please don't make this blocking to prevent deadlocks.
@@ -91,13 +172,112 @@ pub trait MsgMainToModule {
     /// ignoring this or implementing it empty is perfectly fine if you know that your module's
     /// output cannot possibly change between updates it sends anyhow.
     fn send_refresh(&self) -> Result<(),PluginCommunicationError>;
+
+    /// Called from the main thread if the user clicked on (or scrolled over) the text of this
+    /// element. This only happens if the status bar supports it, meaning if the output format is
+    /// set to I3bar. The default implementation ignores clicks, so only implement this if your
+    /// module has something meaningful to do with them.
+    fn send_click_event(&self, _event : &ClickEvent) -> Result<(),PluginCommunicationError> {
+        Ok(())
+    }
+
+    /// Called from the main thread if the user sent a message to this element, with
+    /// `swaystatus ctl message <element> <text>`. What the text means is up to the module. The
+    /// default implementation ignores messages.
+    fn send_custom_message(&self, _message : &str) -> Result<(),PluginCommunicationError> {
+        Ok(())
+    }
+}
+
+/// The mouse button that was pressed. Scrolling is reported as button presses as well.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MouseButton {
+    /// Usually the primary button.
+    Left,
+    /// Pressing the scroll wheel.
+    Middle,
+    /// Usually the secondary button.
+    Right,
+    /// Scroll wheel moved up.
+    ScrollUp,
+    /// Scroll wheel moved down.
+    ScrollDown,
+    /// Horizontal scrolling to the left.
+    ScrollLeft,
+    /// Horizontal scrolling to the right.
+    ScrollRight,
+    /// The "back" thumb button.
+    Back,
+    /// The "forward" thumb button.
+    Forward,
+    /// Any other button, holding the X11 button number the status bar reported.
+    Other(u32)
+}
+
+impl From<u32> for MouseButton {
+    /// Converts from the X11 button numbers that i3bar and swaybar use.
+    fn from(button : u32) -> Self {
+        match button {
+            1 => MouseButton::Left,
+            2 => MouseButton::Middle,
+            3 => MouseButton::Right,
+            4 => MouseButton::ScrollUp,
+            5 => MouseButton::ScrollDown,
+            6 => MouseButton::ScrollLeft,
+            7 => MouseButton::ScrollRight,
+            8 => MouseButton::Back,
+            9 => MouseButton::Forward,
+            x => MouseButton::Other(x)
+        }
+    }
+}
+
+impl From<MouseButton> for u32 {
+    /// Converts to the X11 button numbers that i3bar and swaybar use.
+    fn from(button : MouseButton) -> Self {
+        match button {
+            MouseButton::Left => 1,
+            MouseButton::Middle => 2,
+            MouseButton::Right => 3,
+            MouseButton::ScrollUp => 4,
+            MouseButton::ScrollDown => 5,
+            MouseButton::ScrollLeft => 6,
+            MouseButton::ScrollRight => 7,
+            MouseButton::Back => 8,
+            MouseButton::Forward => 9,
+            MouseButton::Other(x) => x
+        }
+    }
+}
+
+/// A click on an element, as reported by the status bar. Positions are in pixels. The relative
+/// positions are measured from the top left corner of the element, the others from the top left
+/// corner of the bar's output.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClickEvent {
+    /// Which button was pressed.
+    pub button : MouseButton,
+    /// Names of the held modifier keys, for instance "Shift" or "Mod4".
+    pub modifiers : Vec<String>,
+    /// Horizontal position of the click.
+    pub x : i32,
+    /// Vertical position of the click.
+    pub y : i32,
+    /// Horizontal position of the click relative to the element.
+    pub relative_x : i32,
+    /// Vertical position of the click relative to the element.
+    pub relative_y : i32,
+    /// Width of the element.
+    pub width : i32,
+    /// Height of the element.
+    pub height : i32,
 }
 
 /// When communicating an error to the main program, this allows to choose an appropriate handling
 /// method. If the error does not prevent text updates, you likely just want to print it to stderr.
 /// If it makes further processing impossible but doesn't cause an outright crash, consider
 /// showing it instead of the usual text instead.
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum PluginError {
     /// Use this variant if your error is not critical for the plugin's operation, but should still
     /// be communicated to the main program. The main program currently just calls eprintln! with
@@ -131,7 +311,107 @@ pub trait MsgModuleToMain : Send {
     /// stdout output. While this can in theory return an error, that should practically never
     /// happen. If this errors, you should probably clean up your resources and return from the
     /// run() function. In other words, act as if main had sent you a quit command.
-    fn send_update(&self, text : Result<String, PluginError>) -> Result<(),PluginCommunicationError>;
+    /// This is a shorthand for `send_block_update()` with a block that only has text set.
+    fn send_update(&self, text : Result<String, PluginError>) -> Result<(),PluginCommunicationError> {
+        self.send_block_update(text.map(StatusBlock::from))
+    }
+
+    /// Like `send_update()`, but allows to set more than just the text. What the main program
+    /// does with the additional information depends on the output format. With plain text output
+    /// everything except the full text is ignored.
+    fn send_block_update(&self, block : Result<StatusBlock, PluginError>) -> Result<(),PluginCommunicationError>;
+}
+
+/// A text update with additional information on how to display it. The fields follow the block
+/// object of the i3bar protocol, see swaybar-protocol(7) for details, and so does the JSON form of
+/// this type. Fields of the protocol that are missing here are ignored when deserializing. Colors
+/// are strings in the format "#RRGGBB" or "#RRGGBBAA". Everything that's `None` is left for the
+/// main program (or the status bar) to decide.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatusBlock {
+    /// The text to display.
+    pub full_text : String,
+    /// A shorter version of the text, used by the bar if space is tight.
+    pub short_text : Option<String>,
+    /// Text color.
+    pub color : Option<String>,
+    /// Background color.
+    pub background : Option<String>,
+    /// Border color.
+    pub border : Option<String>,
+    /// Asks the bar to draw attention to this element.
+    pub urgent : bool,
+    /// If set, the texts are interpreted as Pango markup. In JSON this is the string "pango" or
+    /// "none", as in the i3bar protocol.
+    #[serde(with = "markup_as_string")]
+    pub markup : bool,
+    /// The minimum width of the element.
+    pub min_width : Option<MinWidth>,
+    /// Alignment of the text if the element is wider than the text.
+    pub align : Option<Alignment>,
+    /// What the value shown means to the user. The main program picks a color for it from its
+    /// theme, unless `color` is set explicitly.
+    pub state : BlockState,
+}
+
+/// The state of an element. Used by the main program to pick a color from its theme. The
+/// ordering is by severity, so the maximum of several states is the most important one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+pub enum BlockState {
+    /// Nothing special. This is the default.
+    #[default]
+    Idle,
+    /// Something the user might want to know, but nothing good or bad.
+    Info,
+    /// Everything is fine, and this should be visible.
+    Good,
+    /// Something needs attention soon.
+    Warning,
+    /// Something needs attention right now.
+    Critical
+}
+
+/// The i3bar protocol names the markup language instead of using a flag. Only "pango" is known.
+mod markup_as_string {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S : Serializer>(markup : &bool, serializer : S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(if *markup { "pango" } else { "none" })
+    }
+
+    pub fn deserialize<'de, D : Deserializer<'de>>(deserializer : D) -> Result<bool, D::Error> {
+        Ok(String::deserialize(deserializer)? == "pango")
+    }
+}
+
+impl From<String> for StatusBlock {
+    fn from(full_text : String) -> Self {
+        StatusBlock { full_text, ..Default::default() }
+    }
+}
+
+/// The minimum width of an element. Either given in pixels, or as a text whose width is used.
+/// In JSON it's just the number or the text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MinWidth {
+    /// Width in pixels.
+    Pixels(u32),
+    /// The element will be at least as wide as this text would be.
+    Text(String)
+}
+
+/// Horizontal alignment of the text within the element. In JSON it's "left", "center" or "right".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Alignment {
+    /// Align left.
+    Left,
+    /// Center the text.
+    Center,
+    /// Align right.
+    Right
 }
 
 /// Interface your module should implement. All functions of this will be called in the main thread.
@@ -153,6 +433,14 @@ pub trait SwayStatusModule {
     ///This is the place to explain enum variants not present in your sample config, or info on
     ///advanced features.
     fn print_help(&self);
+
+    ///Describes the config of an instance, the `[Element.Config]` section, as a JSON Schema
+    ///(https://json-schema.org/), in JSON text. Editors use it to offer completion and validation.
+    ///Used by `swaystatus --print-config-schema`. The default has no schema, which means any
+    ///config is accepted by editors.
+    fn get_config_schema(&self) -> Option<String> {
+        None
+    }
 }
 
 ///This is what `SwayStatusModuleInstance::make_runnable()` returns. The main function of your module.
@@ -171,6 +459,12 @@ pub trait SwayStatusModuleInstance : erased_serde::Serialize {
     ///The config is a trait object of the same type you provide in `get_default_config()` and 
     ///`deserialize_config()`.
     fn make_runnable<'p>(&'p self, to_main : Box<dyn MsgModuleToMain + 'p>) -> (Box<dyn SwayStatusModuleRunnable + 'p>, Box<dyn MsgMainToModule + 'p>);
+    ///Checks the configuration for problems that deserialization can't find, for instance an
+    ///invalid format string. Returns a description of each problem, or nothing if all is fine.
+    ///Used by `swaystatus --check-config`. The default finds no problems.
+    fn validate(&self) -> Vec<String> {
+        Vec::new()
+    }
 }
 serialize_trait_object!(SwayStatusModuleInstance);
 
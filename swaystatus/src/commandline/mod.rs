@@ -3,6 +3,7 @@ extern crate dirs;
 use clap::*;
 use std::path;
 use gettextrs::*;
+use crate::config::{ConfigFile, ConfigFormat};
 
 pub enum PluginHelpOption{
     All,
@@ -10,14 +11,32 @@ pub enum PluginHelpOption{
 }
 pub enum CommandlineAction {
     Run {
-        config_file : path::PathBuf,
+        config_file : ConfigFile,
+    },
+    PrintSampleConfig {
+        format : ConfigFormat
+    },
+    /// Prints a JSON Schema of the config file, including the configs of all plugins found.
+    PrintConfigSchema,
+    /// Reads the config file and reports every problem in it, without running anything.
+    CheckConfig {
+        config_file : ConfigFile,
     },
-    PrintSampleConfig,
     PluginHelp(PluginHelpOption),
-    ListPlugins
+    ListPlugins,
+    /// Sends a command to the control socket of a running swaystatus, or of all of them.
+    Control {
+        socket : Option<path::PathBuf>,
+        command : String
+    },
+    /// Internal. Runs a single element in this process, on behalf of a main process.
+    PluginHost {
+        library : path::PathBuf
+    }
 }
 pub struct CommandlineParameters{
-    pub plugin_folder : path::PathBuf,
+    /// Where to look for plugins, in order of precedence.
+    pub plugin_folders : Vec<path::PathBuf>,
     pub action : CommandlineAction
 }
 
@@ -48,14 +67,23 @@ pub fn parse_commandline() -> CommandlineParameters {
             .help(&*gettext("Path to the configuration file"))
             .display_order(0)
             .takes_value(true))
+        .arg(
+            Arg::new("configformat")
+            .long("config-format")
+            .value_name(&gettext("FORMAT"))
+            .help(&*gettext("Format of the configuration file, or of the printed sample config. One of toml, json and yaml. By default the format is taken from the file extension, and files without a known extension are read as toml."))
+            .possible_values(["toml", "json", "yaml"])
+            .display_order(0)
+            .takes_value(true))
         .arg(
             Arg::new("plugins")
             .short('p')
             .long("plugins")
             .value_name(&*gettext("FOLDER"))
-            .help(&*gettext("Directory from which the plugins should be loaded"))
+            .help(&*gettext("Directory from which the plugins should be loaded. Can be given several times, earlier folders take precedence."))
             .display_order(0)
-            .takes_value(true))
+            .takes_value(true)
+            .multiple_occurrences(true))
         .arg(
             Arg::new("sampleconfig")
             .long("print-sample-config")
@@ -64,6 +92,20 @@ pub fn parse_commandline() -> CommandlineParameters {
             .display_order(2)
             .takes_value(false)
             .conflicts_with_all(&["pluginhelp","pluginlist"]))
+        .arg(
+            Arg::new("configschema")
+            .long("print-config-schema")
+            .help(&*gettext("Prints a JSON Schema of the configuration file, for editors that offer completion and validation. Includes the configuration of all plugins found, so don't forget to supply the plugins parameter as needed."))
+            .display_order(2)
+            .takes_value(false)
+            .conflicts_with_all(&["sampleconfig","pluginhelp","pluginlist"]))
+        .arg(
+            Arg::new("checkconfig")
+            .long("check-config")
+            .help(&*gettext("Checks the configuration file and prints all problems found in it, without starting anything. Exits with a non-zero status if there are problems."))
+            .display_order(2)
+            .takes_value(false)
+            .conflicts_with_all(&["sampleconfig","pluginhelp","pluginlist"]))
         .arg(
             Arg::new("pluginhelp")
             .long("plugin-help")
@@ -80,7 +122,31 @@ pub fn parse_commandline() -> CommandlineParameters {
             .help(&*gettext("Prints a list of plugin names in the plugin folder."))
             .display_order(1)
             .takes_value(false))
-        .after_help(&*gettext!("If no config path is given, the code looks for the \"swaystatus/config\" file in your XDG config folder (typically \"$HOME/.config/\"). If that lookup fails, loading of \"/etc/swaystatus/config\" is attempted. Similarly, if no plugin folder is given, first the existence of a folder named \"$HOME/.local/lib/swaystatus\" is checked. If this folder does not exist, a default path set at compile time is used, which in your case is \"{}\"." , get_hardcoded_default_library_path()))
+        .arg(
+            Arg::new("pluginhost")
+            .long("plugin-host")
+            .value_name(&gettext("LIBRARY"))
+            .help(&*gettext("Used internally to run elements in their own process."))
+            .hide(true)
+            .takes_value(true))
+        .subcommand(
+            App::new("ctl")
+            .about(&*gettext("Sends a command to the running swaystatus instances."))
+            .setting(AppSettings::TrailingVarArg)
+            .arg(
+                Arg::new("socket")
+                .long("socket")
+                .value_name(&gettext("SOCKET"))
+                .help(&*gettext("Only sends the command to the instance listening on this socket, instead of all instances of the user."))
+                .takes_value(true))
+            .arg(
+                Arg::new("command")
+                .value_name(&gettext("COMMAND"))
+                .help(&*gettext("One of: \"refresh\" to refresh all elements, \"refresh <element>\" to refresh one, \"reload\" to reload the configuration, \"quit\", \"dump\" to print the text of each element, or \"message <element> <text>\" to send a text to an element. Elements are given by their name, or by their position if they don't have one."))
+                .required(true)
+                .multiple_values(true)
+                .allow_hyphen_values(true)))
+        .after_help(&*gettext!("If no config path is given, the code looks for the \"swaystatus/config\" file in your XDG config folder (typically \"$HOME/.config/\"), also with a \".toml\", \".yaml\", \".yml\" or \".json\" extension. If that lookup fails, loading of \"/etc/swaystatus/config\" is attempted. Plugins are searched in all subfolders of the folders given on the command line, then in the folders listed in the SWAYSTATUS_PLUGIN_PATH environment variable (separated by colons), then in \"$HOME/.local/lib/swaystatus\", then in the \"swaystatus/plugins\" folder of each XDG data folder (typically \"$HOME/.local/share/\", \"/usr/local/share/\" and \"/usr/share/\"), and finally in a default path set at compile time, which in your case is \"{}\". If several folders contain a plugin with the same name, the first one is used." , get_hardcoded_default_library_path()))
         .help_template(&*gettext("\
 {before-help}{bin} {version}\n\
 {author}\n
@@ -89,34 +155,63 @@ USAGE\n    {usage}\n\
 \n\
 OPTIONS:
 {options}\n
+SUBCOMMANDS:
+{subcommands}\n
 {after-help}")).get_matches();
 
-    let plugin_folder = matches.value_of("plugins").map(path::PathBuf::from).unwrap_or_else(get_default_plugin_directory);
-    if matches.is_present("sampleconfig") {
-        CommandlineParameters { plugin_folder, action : CommandlineAction::PrintSampleConfig }
+    let plugin_folders = get_plugin_search_path(
+        matches.values_of("plugins").into_iter().flatten().map(path::PathBuf::from).collect(),
+        std::env::var_os("SWAYSTATUS_PLUGIN_PATH").as_deref(),
+        std::env::var_os("XDG_DATA_DIRS").as_deref());
+    let format = matches.value_of("configformat").and_then(ConfigFormat::from_name);
+    let config_file = || {
+        let path = matches.value_of("config").map(path::PathBuf::from).unwrap_or_else(get_default_config);
+        match format {
+            Some(format) => ConfigFile { path, format },
+            None => ConfigFile::new(path)
+        }
+    };
+    if let Some(control) = matches.subcommand_matches("ctl") {
+        CommandlineParameters { plugin_folders, action : CommandlineAction::Control {
+            socket : control.value_of("socket").map(path::PathBuf::from),
+            command : control.values_of("command").into_iter().flatten().collect::<Vec<_>>().join(" ")
+        }}
+    }
+    else if let Some(library) = matches.value_of("pluginhost") {
+        CommandlineParameters { plugin_folders, action : CommandlineAction::PluginHost { library : path::PathBuf::from(library) } }
+    }
+    else if matches.is_present("checkconfig") {
+        CommandlineParameters { plugin_folders, action : CommandlineAction::CheckConfig { config_file : config_file() } }
+    }
+    else if matches.is_present("configschema") {
+        CommandlineParameters { plugin_folders, action : CommandlineAction::PrintConfigSchema }
+    }
+    else if matches.is_present("sampleconfig") {
+        CommandlineParameters { plugin_folders, action : CommandlineAction::PrintSampleConfig { format : format.unwrap_or_default() } }
     }
     else if matches.is_present("pluginlist") {
-        CommandlineParameters {plugin_folder, action : CommandlineAction::ListPlugins }
+        CommandlineParameters {plugin_folders, action : CommandlineAction::ListPlugins }
     }
     else if let Some(iter) = matches.values_of("pluginhelp") {
-        CommandlineParameters {plugin_folder, action : CommandlineAction::PluginHelp(
+        CommandlineParameters {plugin_folders, action : CommandlineAction::PluginHelp(
             if iter.len() == 0 { PluginHelpOption::All }
             else {PluginHelpOption::List(iter.map(String::from).collect())}
         )}
     }
     else {
-        let config_file = matches.value_of("config").map(path::PathBuf::from).unwrap_or_else(get_default_config);
-        CommandlineParameters {plugin_folder, action : CommandlineAction::Run { config_file }}
+        CommandlineParameters {plugin_folders, action : CommandlineAction::Run { config_file : config_file() }}
     }
 }
 
-/// Searches for the config file in XDG paths. If not found there, instead the
-/// /etc/swaystatus/config path is returned.
+/// Searches for the config file in XDG paths, without extension or with the one of a supported
+/// format. If not found there, instead the /etc/swaystatus/config path is returned.
 fn get_default_config() -> path::PathBuf {
-    if let Some(mut xdg) = dirs::config_dir() {
-        xdg.push("swaystatus/config");
-        if xdg.exists() {
-            return xdg;
+    if let Some(xdg) = dirs::config_dir() {
+        for name in ["config", "config.toml", "config.yaml", "config.yml", "config.json"] {
+            let candidate = xdg.join("swaystatus").join(name);
+            if candidate.exists() {
+                return candidate;
+            }
         }
     }
     //no XDG data dir config file found.
@@ -127,15 +222,41 @@ fn get_hardcoded_default_library_path() -> &'static str {
     option_env!("DEFAULT_PLUGIN_DIR").unwrap_or("/usr/lib/swaystatus/")
 }
 
-/// Searches for plugins. First checks the user's ~/.local/lib/swaystatus/ folder, then the folder
-/// passed as build parameter (DEFAULT_PLUGIN_DIR)
-fn get_default_plugin_directory() -> path::PathBuf {
+/// The folders to search for plugins, in order of precedence: The command line, the
+/// SWAYSTATUS_PLUGIN_PATH environment variable, the user's ~/.local/lib/swaystatus/ folder, the
+/// swaystatus/plugins folder in the XDG data directories, and finally the folder passed as build
+/// parameter (DEFAULT_PLUGIN_DIR). Folders that don't exist are left out.
+fn get_plugin_search_path(commandline : Vec<path::PathBuf>, env_path : Option<&std::ffi::OsStr>, xdg_data_dirs : Option<&std::ffi::OsStr>) -> Vec<path::PathBuf> {
+    let mut candidates = commandline;
+    candidates.extend(env_path.into_iter().flat_map(std::env::split_paths));
     if let Some(mut user_folder) = dirs::home_dir() {
         user_folder.push(".local/lib/swaystatus/");
-        if user_folder.is_dir() {
-            return user_folder;
+        candidates.push(user_folder);
+    }
+    candidates.extend(dirs::data_dir().map(|mut d| { d.push("swaystatus/plugins"); d }));
+    //Same default as in the XDG Base Directory Specification.
+    let xdg_data_dirs = xdg_data_dirs.filter(|d| !d.is_empty()).unwrap_or_else(|| std::ffi::OsStr::new("/usr/local/share/:/usr/share/"));
+    candidates.extend(std::env::split_paths(xdg_data_dirs).map(|mut d| { d.push("swaystatus/plugins"); d }));
+    candidates.push(path::PathBuf::from(get_hardcoded_default_library_path()));
+
+    let mut result : Vec<path::PathBuf> = Vec::new();
+    for candidate in candidates {
+        if candidate.as_os_str().is_empty() || !candidate.is_dir() {
+            continue;
+        }
+        if !result.iter().any(|r| same_folder(r, &candidate)) {
+            result.push(candidate);
         }
     }
+    result
+}
 
-    path::PathBuf::from(get_hardcoded_default_library_path())
+fn same_folder(a : &path::Path, b : &path::Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b
+    }
 }
+
+#[cfg(test)]
+mod tests;
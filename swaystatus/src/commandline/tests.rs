@@ -0,0 +1,28 @@
+use super::*;
+
+fn make_folders(test_name : &str, names : &[&str]) -> path::PathBuf {
+    let root = std::env::temp_dir().join(format!("swaystatus-{}-{}", test_name, std::process::id()));
+    for name in names {
+        std::fs::create_dir_all(root.join(name)).unwrap();
+    }
+    root
+}
+
+#[test]
+fn plugin_search_path_keeps_precedence() {
+    let root = make_folders("search-path", &["cmd", "env1", "env2", "data/swaystatus/plugins"]);
+    let env_path = std::env::join_paths([root.join("env1"), root.join("missing"), root.join("env2")]).unwrap();
+    let result = get_plugin_search_path(vec![root.join("cmd")], Some(&env_path), Some(root.join("data").as_os_str()));
+    assert_eq!(result[..4], [root.join("cmd"), root.join("env1"), root.join("env2"), root.join("data/swaystatus/plugins")]);
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn plugin_search_path_has_no_duplicates() {
+    let root = make_folders("search-path-duplicates", &["a", "b"]);
+    let env_path = std::env::join_paths([root.join("b"), root.join("a/../a")]).unwrap();
+    let result = get_plugin_search_path(vec![root.join("a"), root.join("b")], Some(&env_path), None);
+    assert_eq!(result.iter().filter(|p| p.starts_with(&root)).count(), 2);
+    assert_eq!(result[..2], [root.join("a"), root.join("b")]);
+    std::fs::remove_dir_all(root).unwrap();
+}
@@ -1,24 +1,68 @@
 use swaystatus_plugin as plugin;
 use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
 
-/// Used for internal communication. At the moment only from the signal handler to the main thread.
+/// Used for internal communication, from the signal handler and the control socket to the main
+/// thread.
 pub enum InternalMessage {
     ///Exit gracefully.
     Quit,
     ///Refresh all text.
     Refresh,
-    ///Reload everything. Plugins, config, basically exit and restart.
+    ///Reload the config. Elements that didn't change keep running, unless the new config needs
+    ///other plugins. Then it's basically exit and restart.
     Reload
 }
 
 pub enum Message {
     Internal(InternalMessage),
     External{
-        text :Result<String,plugin::PluginError>,
+        block :Result<plugin::StatusBlock,plugin::PluginError>,
         element_number : usize
     },
     ThreadCrash{
         element_number : usize
+    },
+    /// The thread of an element has ended, be it because the runnable returned or panicked.
+    Finished{
+        element_number : usize
+    },
+    /// The status bar reported a click. The name and instance are the ones we sent with the block
+    /// that was clicked.
+    Click{
+        name : String,
+        instance : String,
+        event : plugin::ClickEvent
+    },
+    /// A real-time signal arrived, SIGRTMIN plus this number. Refreshes the elements that asked
+    /// for it with `Signal`.
+    Signal(u32),
+    /// A command from the control socket that needs the elements. The answer goes to `reply`.
+    Control{
+        request : crate::control::Request,
+        reply : Sender<crate::control::Reply>
+    }
+}
+
+/// Some threads (like the one reading click events from stdin) can't be stopped while they are
+/// blocked, so they are started once and outlive reloads. They use this to reach whichever core
+/// loop is currently running. Messages sent while no core loop is connected are dropped.
+#[derive(Clone, Default)]
+pub struct CurrentMainSender(Arc<Mutex<Option<Sender<Message>>>>);
+
+impl CurrentMainSender {
+    /// Routes all further messages to the given sender. Pass None to disconnect.
+    pub fn connect(&self, sender : Option<Sender<Message>>) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = sender;
+    }
+    /// Returns false if there is no core loop listening.
+    pub fn send(&self, message : Message) -> bool {
+        match &*self.0.lock().unwrap_or_else(|e| e.into_inner()) {
+            Some(sender) => sender.send(message).is_ok(),
+            None => false
+        }
     }
 }
 
@@ -32,6 +76,8 @@ pub enum Message {
 pub struct SenderToMain {
     pub sender : Sender<Message>,
     pub element_number : usize,
+    /// If false, only the text of the blocks is used. See `PluginFeatures::STRUCTURED_UPDATES`.
+    pub structured_updates : bool,
 }
 
 impl Drop for SenderToMain {
@@ -45,9 +91,62 @@ impl Drop for SenderToMain {
     }
 }
 
+/// Lives on the stack of each element thread, and sends Message::Finished when that thread ends.
+/// Must be dropped after the runnable, so a crash is reported before the end of the thread.
+pub struct FinishedNotifier {
+    pub sender : Sender<Message>,
+    pub element_number : usize,
+}
+
+impl Drop for FinishedNotifier {
+    fn drop(&mut self) {
+        //If nobody's listening any more, nobody needs to know.
+        let _ = self.sender.send(Message::Finished { element_number : self.element_number });
+    }
+}
+
 impl plugin::MsgModuleToMain for SenderToMain {
-    fn send_update(&self, text : Result<String, plugin::PluginError>) -> Result<(),plugin::PluginCommunicationError> {
-        let message = Message::External { text , element_number : self.element_number };
+    fn send_block_update(&self, block : Result<plugin::StatusBlock, plugin::PluginError>) -> Result<(),plugin::PluginCommunicationError> {
+        let block = if self.structured_updates { block } else { block.map(|b| plugin::StatusBlock::from(b.full_text)) };
+        let message = Message::External { block , element_number : self.element_number };
         self.sender.send(message).map_err(|_| plugin::PluginCommunicationError)
     }
 }
+
+/// Wraps the sender of an element whose plugin doesn't handle clicks. Clicks are dropped instead
+/// of being forwarded. See `PluginFeatures::CLICK_EVENTS`.
+pub struct IgnoreClickEvents<'p>(pub Box<dyn plugin::MsgMainToModule + 'p>);
+
+impl<'p> plugin::MsgMainToModule for IgnoreClickEvents<'p> {
+    fn send_quit(&self) -> Result<(),plugin::PluginCommunicationError> {
+        self.0.send_quit()
+    }
+    fn send_refresh(&self) -> Result<(),plugin::PluginCommunicationError> {
+        self.0.send_refresh()
+    }
+    fn send_custom_message(&self, message : &str) -> Result<(),plugin::PluginCommunicationError> {
+        self.0.send_custom_message(message)
+    }
+}
+
+/// Writes all data to the socket. The main process has a handler for SIGPIPE, which quits the
+/// whole program, because usually it means that the status bar is gone. A dead child process must
+/// not have that effect, so this uses send() with MSG_NOSIGNAL instead of write(). That's also why
+/// child processes get a socket instead of a pipe as their stdin.
+pub fn send_without_sigpipe(stream : &UnixStream, data : &[u8]) -> std::io::Result<()> {
+    let mut remaining = data;
+    while !remaining.is_empty() {
+        let sent = unsafe {
+            libc::send(stream.as_raw_fd(), remaining.as_ptr() as *const libc::c_void, remaining.len(), libc::MSG_NOSIGNAL)
+        };
+        if sent < 0 {
+            let error = std::io::Error::last_os_error();
+            if error.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(error);
+        }
+        remaining = &remaining[sent as usize..];
+    }
+    Ok(())
+}
@@ -0,0 +1,242 @@
+//! Reads the config the same way the core loop does, but doesn't stop at the first problem. Used
+//! by `--check-config`.
+
+use super::*;
+use gettextrs::*;
+
+/// Something wrong with the config file.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ConfigProblem {
+    /// The file the problem is in. Can be an included file or a drop-in.
+    pub file : Option<std::path::PathBuf>,
+    /// The number of the element the problem is in, counted from 0 like everywhere else.
+    pub element : Option<usize>,
+    /// Line and column in the config file, both counted from 1.
+    pub position : Option<(usize, usize)>,
+    pub message : String,
+}
+
+impl<'p> SwaystatusConfig<'p> {
+    /// Finds all problems in the config file and the files it pulls in, including those only the
+    /// plugins can tell.
+    pub fn check_config(file : &ConfigFile, plugins : &'p PluginDatabase) -> Result<Vec<ConfigProblem>,SwaystatusConfigErrors> {
+        Ok(check(include::read_merged(file)?, plugins))
+    }
+}
+
+/// Problems with the syntax and the includes are already found while merging. What's left are the
+/// tables of each file, and the elements, as only the plugins can tell what's wrong with them.
+pub(super) fn check(merged : include::MergedConfig, plugins : &PluginDatabase) -> Vec<ConfigProblem> {
+    let mut problems = merged.problems;
+    problems.extend(check_tables(&merged.tables, plugins));
+    let elements = match merged.table.get("Element") {
+        Some(toml::Value::Array(elements)) => elements.as_slice(),
+        _ => &[]
+    };
+    for (element_number, (element, source)) in elements.iter().zip(merged.element_sources).enumerate() {
+        problems.extend(check_element(element.clone(), element_number, source, plugins));
+    }
+    //Files that couldn't be parsed might be the reason.
+    if elements.is_empty() && problems.is_empty() {
+        problems.push(ConfigProblem { file : None, element : None, position : None, message : gettext("No elements set up in configuration. Nothing to display.") });
+    }
+    problems
+}
+
+/// Checks the top level tables of each file with the deserializer the config is read with. If a
+/// table has problems, its keys are checked one by one, to point at the ones causing them.
+pub(super) fn check_tables(tables : &[include::TableSource], plugins : &PluginDatabase) -> Vec<ConfigProblem> {
+    let mut problems = Vec::new();
+    for table in tables {
+        let Err(e) = deserialize_table(&table.key, table.value.clone(), plugins) else {
+            continue;
+        };
+        let problem = |position, e : toml::de::Error| ConfigProblem { file : Some(table.file.clone()), element : None, position, message : e.to_string() };
+        let entries = table.value.as_table().into_iter().flatten();
+        let found : Vec<_> = entries.filter_map(|(key, value)| {
+            let single = toml::Value::Table(std::iter::once((key.clone(), value.clone())).collect());
+            let e = deserialize_table(&table.key, single, plugins).err()?;
+            Some(problem(table.keys.position(key).or(table.position), e))
+        }).collect();
+        if found.is_empty() {
+            problems.push(problem(table.position, e));
+        }
+        problems.extend(found);
+    }
+    problems
+}
+
+fn deserialize_table(key : &str, value : toml::Value, plugins : &PluginDatabase) -> Result<(), toml::de::Error> {
+    let config = toml::Value::Table(std::iter::once((String::from(key), value)).collect());
+    custom_deserializers::SwaystatusConfigDeserializeSeed(plugins).deserialize(config).map(|_| ())
+}
+
+fn check_element(element : toml::Value, element_number : usize, source : include::ElementSource, plugins : &PluginDatabase) -> Vec<ConfigProblem> {
+    let problem = |position, message| ConfigProblem { file : Some(source.file.clone()), element : Some(element_number), position, message };
+    match custom_deserializers::SwaystatusPluginConfigSeed(plugins).deserialize(element) {
+        Ok(config) => {
+            let position = ["Config", "config"].into_iter().find_map(|k| source.keys.position(k)).or(source.position);
+            let mut problems : Vec<_> = config.get_instance().validate().into_iter().map(|message| problem(position, message)).collect();
+            //A helper process loads the plugin's library, so plugins without one always run in a thread.
+            if config.get_non_plugin_settings().isolation == Some(ElementIsolation::Process) && plugins.get_library_path(config.get_name()).is_none() {
+                let position = ["General", "general"].into_iter().find_map(|k| source.keys.get(k)).and_then(|g| g.position("Isolation")).or(source.position);
+                problems.push(problem(position, gettext!("Isolation: the plugin {} isn't loaded from a library, so it can't run in a process of its own", config.get_name())));
+            }
+            problems
+        },
+        Err(e) => vec![problem(source.position, e.to_string())]
+    }
+}
+
+/// Signals are SIGRTMIN plus the number, so they have to stay within the real-time signals.
+pub(super) fn check_signals(merged : &mut include::MergedConfig) {
+    let elements = match merged.table.get("Element") {
+        Some(toml::Value::Array(elements)) => elements.as_slice(),
+        _ => &[]
+    };
+    let max = crate::signalhandler::max_element_signal();
+    for (element_number, (element, source)) in elements.iter().zip(&merged.element_sources).enumerate() {
+        let general = ["General", "general"].into_iter().find_map(|g| element.get(g).map(|v| (g, v)));
+        let Some((key, signal)) = general.and_then(|(g, v)| v.get("Signal").and_then(toml::Value::as_integer).map(|s| (g, s))) else {
+            continue;
+        };
+        if (1..=i64::from(max)).contains(&signal) {
+            continue;
+        }
+        let position = source.keys.get(key).and_then(|g| g.position("Signal")).or(source.position);
+        let message = gettext!("Signal: {} is not a number from 1 to {}", signal, max);
+        merged.problems.push(ConfigProblem { file : Some(source.file.clone()), element : Some(element_number), position, message });
+    }
+}
+
+/// Element names have to be unique, as they are used to address the elements. They also can't be
+/// numbers, as elements without a name are addressed by their position, and can't contain spaces,
+/// as they are a single word in the commands of the control socket. Names that aren't strings are
+/// left to the deserializer.
+pub(super) fn check_names(merged : &mut include::MergedConfig) {
+    let elements = match merged.table.get("Element") {
+        Some(toml::Value::Array(elements)) => elements.as_slice(),
+        _ => &[]
+    };
+    let mut seen : Vec<&str> = Vec::new();
+    for (element_number, (element, source)) in elements.iter().zip(&merged.element_sources).enumerate() {
+        let general = element.get("General").or_else(|| element.get("general"));
+        let Some(name) = general.and_then(|g| g.get("Name")).and_then(toml::Value::as_str) else {
+            seen.push("");
+            continue;
+        };
+        let message = if name.is_empty() {
+            Some(gettext("Name: the name can't be empty"))
+        }
+        else if name.contains(char::is_whitespace) {
+            Some(gettext!("Name: \"{}\" contains whitespace, which the control socket can't tell from the end of the name", name))
+        }
+        else if name.chars().all(|c| c.is_ascii_digit()) {
+            Some(gettext!("Name: \"{}\" is a number. Numbers are used for elements without a name", name))
+        }
+        else {
+            seen.iter().position(|n| *n == name).map(|other| gettext!("Name: \"{}\" is already used by element number {}", name, other))
+        };
+        if let Some(message) = message {
+            let position = ["General", "general"].into_iter().find_map(|k| source.keys.get(k)).and_then(|g| g.position("Name")).or(source.position);
+            merged.problems.push(ConfigProblem { file : Some(source.file.clone()), element : Some(element_number), position, message });
+        }
+        seen.push(name);
+    }
+}
+
+/// Where the keys of a TOML file are, as the parser saw them.
+#[derive(Clone, Default, Debug)]
+pub(super) struct KeyPositions {
+    /// The keys of a table, with their line and column, both counted from 1, and what's inside.
+    keys : Vec<(String, (usize, usize), KeyPositions)>,
+    /// The entries of an array.
+    entries : Vec<KeyPositions>,
+}
+
+impl KeyPositions {
+    /// Reads the positions of all keys in the file. A file that can't be parsed has none.
+    pub fn of_toml(serialized : &str) -> KeyPositions {
+        let mut deserializer = toml::Deserializer::new(serialized);
+        KeyPositionsSeed(serialized).deserialize(&mut deserializer).unwrap_or_default()
+    }
+    /// The positions inside the value of the given key.
+    pub fn get(&self, key : &str) -> Option<&KeyPositions> {
+        self.keys.iter().find(|(k, _, _)| k == key).map(|(_, _, inner)| inner)
+    }
+    pub fn position(&self, key : &str) -> Option<(usize, usize)> {
+        self.keys.iter().find(|(k, _, _)| k == key).map(|(_, position, _)| *position)
+    }
+    pub fn entry(&self, index : usize) -> Option<&KeyPositions> {
+        self.entries.get(index)
+    }
+    /// The position of the first key. For an entry of an array of tables, that's where it starts.
+    pub fn start(&self) -> Option<(usize, usize)> {
+        self.keys.iter().map(|(_, position, _)| *position).min()
+    }
+}
+
+/// Turns a byte offset into line and column, both counted from 1.
+fn line_and_column(text : &str, offset : usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |n| n + 1);
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
+
+/// Deserializes KeyPositions from the text it was given, which is needed to turn the spans the
+/// parser reports into lines and columns.
+struct KeyPositionsSeed<'t>(&'t str);
+
+impl<'de> DeserializeSeed<'de> for KeyPositionsSeed<'_> {
+    type Value = KeyPositions;
+    fn deserialize<D>(self, deserializer : D) -> Result<KeyPositions, D::Error>
+    where D: Deserializer<'de> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for KeyPositionsSeed<'_> {
+    type Value = KeyPositions;
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a TOML value")
+    }
+    fn visit_bool<E>(self, _ : bool) -> Result<KeyPositions, E> {
+        Ok(KeyPositions::default())
+    }
+    fn visit_i64<E>(self, _ : i64) -> Result<KeyPositions, E> {
+        Ok(KeyPositions::default())
+    }
+    fn visit_u64<E>(self, _ : u64) -> Result<KeyPositions, E> {
+        Ok(KeyPositions::default())
+    }
+    fn visit_f64<E>(self, _ : f64) -> Result<KeyPositions, E> {
+        Ok(KeyPositions::default())
+    }
+    fn visit_str<E>(self, _ : &str) -> Result<KeyPositions, E> {
+        Ok(KeyPositions::default())
+    }
+    fn visit_seq<A>(self, mut seq : A) -> Result<KeyPositions, A::Error>
+    where A: SeqAccess<'de> {
+        let mut entries = Vec::new();
+        while let Some(entry) = seq.next_element_seed(KeyPositionsSeed(self.0))? {
+            entries.push(entry);
+        }
+        Ok(KeyPositions { keys : Vec::new(), entries })
+    }
+    fn visit_map<A>(self, mut map : A) -> Result<KeyPositions, A::Error>
+    where A: MapAccess<'de> {
+        let mut keys = Vec::new();
+        loop {
+            let key = match map.next_key::<toml::Spanned<String>>() {
+                Ok(Some(key)) => key,
+                Ok(None) => break,
+                //Dates are maps too, with a key the parser doesn't keep a position for.
+                Err(_) => return Ok(KeyPositions::default())
+            };
+            let inner = map.next_value_seed(KeyPositionsSeed(self.0))?;
+            let position = line_and_column(self.0, key.start());
+            keys.push((key.into_inner(), position, inner));
+        }
+        Ok(KeyPositions { keys, entries : Vec::new() })
+    }
+}
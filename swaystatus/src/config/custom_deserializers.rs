@@ -1,13 +1,32 @@
 use super::*;
 
-#[derive(Deserialize)]
+/// The top level keys of a config file. Merging the files uses it too, to tell them apart.
+#[derive(Deserialize, Clone, Copy)]
 #[serde(field_identifier)]
-enum SwaystatusConfigField { 
+pub(super) enum SwaystatusConfigField { 
+    #[serde(alias = "include")]
+    Include,
     #[serde(alias = "settings")]
     Settings, 
+    #[serde(alias = "theme")]
+    Theme,
+    #[serde(alias = "plugins")]
+    Plugins,
     #[serde(alias = "element", alias = "elements", alias = "Elements")]
     Element 
 }
+impl SwaystatusConfigField {
+    /// The name the key is merged under, whichever alias was used.
+    pub(super) fn name(self) -> &'static str {
+        match self {
+            SwaystatusConfigField::Include => "Include",
+            SwaystatusConfigField::Settings => "Settings",
+            SwaystatusConfigField::Theme => "Theme",
+            SwaystatusConfigField::Plugins => "Plugins",
+            SwaystatusConfigField::Element => "Element"
+        }
+    }
+}
 struct SwaystatusConfigVisitor<'a>(&'a PluginDatabase<'a>) ;
 impl<'de, 'a> Visitor<'de> for SwaystatusConfigVisitor<'a> {
     type Value = SwaystatusConfig<'a>;
@@ -17,15 +36,33 @@ impl<'de, 'a> Visitor<'de> for SwaystatusConfigVisitor<'a> {
     fn visit_map<V>(self, mut map: V) -> Result<SwaystatusConfig<'a>, V::Error>
     where V: MapAccess<'de>, {
         let mut sett = None;
+        let mut theme = None;
+        let mut plugins = None;
         let mut elem = None;
         while let Some(key) = map.next_key()? {
             match key {
+                //Includes are resolved while the files are merged.
+                SwaystatusConfigField::Include => {
+                    map.next_value::<de::IgnoredAny>()?;
+                }
                 SwaystatusConfigField::Settings => {
                     if sett.is_some() {
                         return Err(de::Error::duplicate_field("Settings"));
                     }
                     sett = Some(map.next_value()?);
                 }
+                SwaystatusConfigField::Theme => {
+                    if theme.is_some() {
+                        return Err(de::Error::duplicate_field("Theme"));
+                    }
+                    theme = Some(map.next_value()?);
+                }
+                SwaystatusConfigField::Plugins => {
+                    if plugins.is_some() {
+                        return Err(de::Error::duplicate_field("Plugins"));
+                    }
+                    plugins = Some(map.next_value()?);
+                }
                 SwaystatusConfigField::Element => {
                     if elem.is_some() {
                         return Err(de::Error::duplicate_field("Elements"));
@@ -36,6 +73,8 @@ impl<'de, 'a> Visitor<'de> for SwaystatusConfigVisitor<'a> {
         }
         Ok(SwaystatusConfig {
             settings : sett,
+            theme,
+            plugins,
             elements : elem
         })
     }
@@ -45,7 +84,7 @@ impl<'de, 'a> DeserializeSeed<'de> for SwaystatusConfigDeserializeSeed<'a> {
     type Value = SwaystatusConfig<'a>;
     fn deserialize<D>(self, deserializer : D) -> Result<Self::Value, D::Error>
     where D: Deserializer<'de> {
-        const FIELDS: &[&str] = &["settings", "elements"];
+        const FIELDS: &[&str] = &["include", "settings", "theme", "plugins", "elements"];
         deserializer.deserialize_struct("SwaystatusConfig", FIELDS, SwaystatusConfigVisitor(self.0))
     }
 }
@@ -160,7 +199,7 @@ impl<'de, 'a> Visitor<'de> for PluginConfigVisitor<'a> {
         })
     }
 }
-struct SwaystatusPluginConfigSeed<'a>(&'a PluginDatabase<'a>);
+pub struct SwaystatusPluginConfigSeed<'a>(pub &'a PluginDatabase<'a>);
 impl<'de, 'a> DeserializeSeed<'de> for SwaystatusPluginConfigSeed<'a> {
     type Value = SwaystatusPluginConfig<'a>;
     fn deserialize<D>(self, deserializer: D) -> Result<SwaystatusPluginConfig<'a>, D::Error>
@@ -0,0 +1,80 @@
+//! Expansion of environment variables in the config. `${VAR}` is replaced by the content of the
+//! variable VAR, `${VAR:-default}` by the default if VAR is unset or empty. The default can contain
+//! further variables. `$${` is written as a literal `${`, for instance in commands for a shell.
+
+use gettextrs::*;
+
+/// Expands the variables in all strings inside the value. Keys are left alone. Fails with a
+/// description of the first problem found.
+pub(super) fn expand_variables(value : &mut toml::Value, lookup : &impl Fn(&str) -> Option<String>) -> Result<(), String> {
+    match value {
+        toml::Value::String(s) => {
+            *s = expand_string(s, lookup)?;
+            Ok(())
+        },
+        toml::Value::Array(values) => values.iter_mut().try_for_each(|v| expand_variables(v, lookup)),
+        toml::Value::Table(table) => table.iter_mut().try_for_each(|(_, v)| expand_variables(v, lookup)),
+        _ => Ok(())
+    }
+}
+
+/// Looks variables up in the environment of swaystatus.
+pub(super) fn from_environment(name : &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+pub(super) fn expand_string(text : &str, lookup : &impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("$${") {
+            result.push_str("${");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let end = find_closing_brace(after).ok_or_else(|| gettext!("unterminated `${` in \"{}\"", text))?;
+            result.push_str(&expand_reference(&after[..end], lookup)?);
+            rest = &after[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// The position of the `}` that ends a reference, skipping those of references in its default.
+fn find_closing_brace(text : &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut chars = text.char_indices().peekable();
+    while let Some((position, c)) = chars.next() {
+        match c {
+            '$' if chars.peek().map(|(_, c)| *c) == Some('{') => {
+                chars.next();
+                depth += 1;
+            },
+            '}' if depth == 0 => return Some(position),
+            '}' => depth -= 1,
+            _ => ()
+        }
+    }
+    None
+}
+
+/// Expands the inside of a `${...}`.
+fn expand_reference(reference : &str, lookup : &impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    let (name, default) = match reference.split_once(":-") {
+        Some((name, default)) => (name, Some(default)),
+        None => (reference, None)
+    };
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(gettext!("`${{}}` is not a valid variable reference", reference));
+    }
+    match (lookup(name).filter(|value| !value.is_empty() || default.is_none()), default) {
+        (Some(value), _) => Ok(value),
+        (None, Some(default)) => expand_string(default, lookup),
+        (None, None) => Err(gettext!("the environment variable {} is not set. Use `${{}:-}` if it's optional", name, name))
+    }
+}
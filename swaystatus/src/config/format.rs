@@ -0,0 +1,107 @@
+//! The file formats a config can be written in. Files in other formats than TOML are turned into
+//! TOML values right after parsing, so everything after that, from includes to the plugins'
+//! deserializers, works the same for all of them.
+
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ConfigFormat {
+    #[default]
+    Toml,
+    Json,
+    Yaml
+}
+
+/**
+ * The main config file, and the format it's written in. Included files and drop-ins always use
+ * the format their extension stands for.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigFile {
+    pub path : PathBuf,
+    pub format : ConfigFormat
+}
+
+/// Why a file couldn't be parsed.
+pub(super) struct ParseError {
+    pub message : String,
+    /// Line and column, both counted from 1.
+    pub position : Option<(usize, usize)>
+}
+
+impl ConfigFormat {
+    /// The format a file extension stands for: `.toml`, `.json`, `.yaml` or `.yml`.
+    pub fn from_extension(path : &Path) -> Option<ConfigFormat> {
+        match path.extension()?.to_str()? {
+            "toml" => Some(ConfigFormat::Toml),
+            "json" => Some(ConfigFormat::Json),
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            _ => None
+        }
+    }
+
+    /// The format given by its name on the command line.
+    pub fn from_name(name : &str) -> Option<ConfigFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "toml" => Some(ConfigFormat::Toml),
+            "json" => Some(ConfigFormat::Json),
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            _ => None
+        }
+    }
+
+    /// Files without a known extension are read as TOML.
+    pub(super) fn of_file(path : &Path) -> ConfigFormat {
+        ConfigFormat::from_extension(path).unwrap_or_default()
+    }
+
+    pub(super) fn parse(self, serialized : &str) -> Result<toml::value::Table, ParseError> {
+        match self {
+            ConfigFormat::Toml => toml::from_str(serialized).map_err(|e| ParseError {
+                position : e.line_col().map(|(l, c)| (l + 1, c + 1)),
+                message : e.to_string()
+            }),
+            ConfigFormat::Json => serde_json::from_str(serialized).map_err(|e| ParseError {
+                position : Some((e.line(), e.column())).filter(|(l, _)| *l > 0),
+                message : e.to_string()
+            }).and_then(into_table),
+            ConfigFormat::Yaml => serde_yaml::from_str(serialized).map_err(|e| ParseError {
+                position : e.location().map(|l| (l.line(), l.column())),
+                message : e.to_string()
+            }).and_then(into_table)
+        }
+    }
+
+    pub(super) fn serialize<T : Serialize>(self, value : &T) -> Result<String, String> {
+        match self {
+            ConfigFormat::Toml => toml::to_string(value).map_err(|e| e.to_string()),
+            ConfigFormat::Json => serde_json::to_string_pretty(value).map(|s| s + "\n").map_err(|e| e.to_string()),
+            ConfigFormat::Yaml => serde_yaml::to_string(value).map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// TOML has no null. Nulls in tables, as in the sample config, mean the same as a missing key.
+fn into_table(mut value : serde_json::Value) -> Result<toml::value::Table, ParseError> {
+    remove_nulls(&mut value);
+    serde_json::from_value(value).map_err(|e| ParseError { message : e.to_string(), position : None })
+}
+
+fn remove_nulls(value : &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            map.retain(|_, v| !v.is_null());
+            map.values_mut().for_each(remove_nulls);
+        },
+        serde_json::Value::Array(values) => values.iter_mut().for_each(remove_nulls),
+        _ => ()
+    }
+}
+
+impl ConfigFile {
+    /// A config file in the format its extension stands for.
+    pub fn new(path : PathBuf) -> ConfigFile {
+        ConfigFile { format : ConfigFormat::of_file(&path), path }
+    }
+}
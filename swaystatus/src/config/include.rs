@@ -0,0 +1,255 @@
+//! Includes and drop-ins. A config file can pull in other files with `Include = [...]`, and the
+//! `.toml` files in the folder named like the config file with `.d` appended (`config.d` for
+//! `config`) are added after it.
+//!
+//! The files are merged after parsing, in this order: Included files come before the file that
+//! includes them, so it can override them. The drop-ins come last, sorted by file name. Tables
+//! like `[Settings]` or `[Theme]` are merged key by key, and later files win. Elements are
+//! appended, unless they have `InsertAt = n`, which puts them at position n of the elements merged
+//! so far. Each file can be written in any of the supported formats, the merge doesn't care.
+//! Elements whose `OnlyOnHosts` or `When` conditions don't hold on this machine are left
+//! out right away, so they don't count for `InsertAt`, and their plugins don't need to exist.
+
+use std::path::{Path, PathBuf};
+use super::*;
+use super::check::{KeyPositions, check_names, check_signals};
+use super::custom_deserializers::SwaystatusConfigField;
+use gettextrs::*;
+
+/// A config file merged with all files it pulls in.
+pub(super) struct MergedConfig {
+    pub table : toml::value::Table,
+    /// Where each entry of the merged element list comes from.
+    pub element_sources : Vec<ElementSource>,
+    /// Every file that was read, in the order they were merged.
+    pub files : Vec<PathBuf>,
+    /// The top level tables each file contains, before they were merged.
+    pub tables : Vec<TableSource>,
+    /// What's wrong with the files. Files that can't be parsed and elements with problems in
+    /// their variables or `InsertAt` are left out of the table. The tables are checked later.
+    pub problems : Vec<ConfigProblem>,
+}
+
+pub(super) struct ElementSource {
+    pub file : PathBuf,
+    /// Line and column where the element starts, both counted from 1.
+    pub position : Option<(usize, usize)>,
+    /// Where the keys inside the element are.
+    pub keys : KeyPositions,
+}
+
+/// A top level table like `[Settings]`, as one of the files has it. Kept to check each file on its
+/// own, so problems can be located.
+pub(super) struct TableSource {
+    pub file : PathBuf,
+    /// The key as it's written in the file.
+    pub key : String,
+    pub value : toml::Value,
+    /// Line and column of the key, both counted from 1.
+    pub position : Option<(usize, usize)>,
+    /// Where the keys inside the table are.
+    pub keys : KeyPositions,
+}
+
+/// The folder with the drop-ins of the given config file.
+pub fn drop_in_folder(path : &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".d");
+    path.with_file_name(name)
+}
+
+/// Reads the config file and everything it pulls in. Fails only if the file itself can't be
+/// read, all other problems end up in the result.
+pub(super) fn read_merged(file : &ConfigFile) -> Result<MergedConfig, SwaystatusConfigErrors> {
+    let serialized = std::fs::read_to_string(&file.path).map_err(|_| SwaystatusConfigErrors::FileNotFound)?;
+    Ok(merge(file, &serialized))
+}
+
+/// Merges the given content of the config file with everything it pulls in.
+pub(super) fn merge(file : &ConfigFile, serialized : &str) -> MergedConfig {
+    let path = file.path.as_path();
+    let mut merger = Merger {
+        merged : MergedConfig { table : toml::value::Table::new(), element_sources : Vec::new(), files : Vec::new(), tables : Vec::new(), problems : Vec::new() },
+        reading : Vec::new(),
+    };
+    merger.merge_file(path, file.format, serialized);
+    let mut drop_ins : Vec<PathBuf> = std::fs::read_dir(drop_in_folder(path)).into_iter().flatten()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| ConfigFormat::from_extension(p).is_some() && p.is_file())
+        .collect();
+    drop_ins.sort();
+    for drop_in in drop_ins {
+        merger.read_file(&drop_in);
+    }
+    check_names(&mut merger.merged);
+    check_signals(&mut merger.merged);
+    merger.merged
+}
+
+/// A part of a file, checked and waiting to be merged.
+enum Part {
+    /// A top level table, under its canonical name.
+    Table(&'static str, toml::Value),
+    /// An element, with the positions of its keys.
+    Element(toml::Value, KeyPositions),
+}
+
+struct Merger {
+    merged : MergedConfig,
+    /// The files whose includes are being read right now, to find include cycles.
+    reading : Vec<PathBuf>,
+}
+
+impl Merger {
+    fn read_file(&mut self, path : &Path) {
+        let serialized = match std::fs::read_to_string(path) {
+            Ok(x) => x,
+            Err(e) => {
+                self.problem(path, None, gettext!("The file could not be read: {}", e));
+                return;
+            }
+        };
+        self.merge_file(path, ConfigFormat::of_file(path), &serialized);
+    }
+
+    fn merge_file(&mut self, path : &Path, format : ConfigFormat, serialized : &str) {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if self.reading.contains(&canonical) {
+            self.problem(path, None, gettext("The file includes itself."));
+            return;
+        }
+        self.merged.files.push(path.to_path_buf());
+        let table = match format.parse(serialized) {
+            Ok(x) => x,
+            Err(e) => {
+                self.problem(path, e.position, e.message);
+                return;
+            }
+        };
+        //Positions are only known in TOML files.
+        let positions = if format == ConfigFormat::Toml { KeyPositions::of_toml(serialized) } else { KeyPositions::default() };
+        let folder = path.parent().unwrap_or(Path::new(""));
+        let mut includes = Vec::new();
+        let mut own = Vec::new();
+        for (key, mut value) in table {
+            let position = positions.position(&key);
+            let field = match SwaystatusConfigField::deserialize(toml::Value::String(key.clone())) {
+                Ok(x) => x,
+                Err(e) => {
+                    self.problem(path, position, e.to_string());
+                    continue;
+                }
+            };
+            //Elements are expanded one by one, so problems point at the right one.
+            if !matches!(field, SwaystatusConfigField::Element) {
+                if let Err(e) = expand::expand_variables(&mut value, &expand::from_environment) {
+                    self.problem(path, position, format!("{}: {}", key, e));
+                    continue;
+                }
+            }
+            let keys = positions.get(&key).cloned().unwrap_or_default();
+            match (field, value) {
+                (SwaystatusConfigField::Include, value) => match Vec::<PathBuf>::deserialize(value) {
+                    Ok(files) => includes.extend(files.into_iter().map(|f| folder.join(f))),
+                    Err(e) => self.problem(path, position, format!("{}: {}", key, e))
+                },
+                (SwaystatusConfigField::Element, toml::Value::Array(elements)) => {
+                    for (index, mut element) in elements.into_iter().enumerate() {
+                        if !applies_here(&element) {
+                            continue;
+                        }
+                        let element_keys = keys.entry(index).cloned().unwrap_or_default();
+                        match expand::expand_variables(&mut element, &expand::from_environment) {
+                            Ok(()) => own.push(Part::Element(element, element_keys)),
+                            Err(e) => self.problem(path, element_keys.start(), e)
+                        }
+                    }
+                },
+                (SwaystatusConfigField::Element, _) => self.problem(path, position, gettext!("{}: expected an array of tables", key)),
+                (field, value) => {
+                    let value = if matches!(field, SwaystatusConfigField::Plugins) { make_libraries_absolute(value, folder) } else { value };
+                    self.merged.tables.push(TableSource { file : path.to_path_buf(), key, value : value.clone(), position, keys });
+                    own.push(Part::Table(field.name(), value));
+                }
+            }
+        }
+        self.reading.push(canonical);
+        for include in includes {
+            self.read_file(&include);
+        }
+        self.reading.pop();
+        for part in own {
+            match part {
+                Part::Table(name, value) => match self.merged.table.get_mut(name) {
+                    Some(existing) => merge_values(existing, value),
+                    None => { self.merged.table.insert(String::from(name), value); }
+                },
+                Part::Element(element, keys) => self.insert_element(path, element, keys)
+            }
+        }
+    }
+
+    fn insert_element(&mut self, path : &Path, mut element : toml::Value, keys : KeyPositions) {
+        let position = keys.start();
+        let elements = match self.merged.table.entry("Element").or_insert_with(|| toml::Value::Array(Vec::new())) {
+            toml::Value::Array(elements) => elements,
+            _ => return
+        };
+        let insert_at = match element.as_table_mut().and_then(|t| t.remove("InsertAt")) {
+            None => elements.len(),
+            Some(toml::Value::Integer(i)) if i >= 0 && i as usize <= elements.len() => i as usize,
+            Some(_) => {
+                let message = gettext!("InsertAt: expected a number from 0 to {}, the number of elements before this one", elements.len());
+                self.problem(path, keys.position("InsertAt").or(position), message);
+                return;
+            }
+        };
+        elements.insert(insert_at, element);
+        self.merged.element_sources.insert(insert_at, ElementSource { file : path.to_path_buf(), position, keys });
+    }
+
+    fn problem(&mut self, path : &Path, position : Option<(usize, usize)>, message : String) {
+        self.merged.problems.push(ConfigProblem { file : Some(path.to_path_buf()), element : None, position, message });
+    }
+}
+
+/// If the conditions in the element's [Element.General] section hold on this machine. Checked
+/// before the element is expanded, so variables that only exist on other machines don't matter.
+/// Elements whose conditions can't be read are kept, so the problem gets reported.
+fn applies_here(element : &toml::Value) -> bool {
+    let Some(toml::Value::Table(general)) = element.get("General").or_else(|| element.get("general")) else {
+        return true;
+    };
+    let mut conditions = toml::Value::Table(general.iter()
+        .filter(|(key, _)| matches!(key.as_str(), "OnlyOnHosts" | "When"))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect());
+    expand::expand_variables(&mut conditions, &expand::from_environment).is_err()
+        || SwaystatusElementNonPluginOptions::deserialize(conditions).map_or(true, |g| g.applies_here())
+}
+
+/// Merges tables key by key. Everything else is replaced.
+fn merge_values(existing : &mut toml::Value, new : toml::Value) {
+    match (existing, new) {
+        (toml::Value::Table(existing), toml::Value::Table(new)) => {
+            for (key, value) in new {
+                match existing.get_mut(&key) {
+                    Some(e) => merge_values(e, value),
+                    None => { existing.insert(key, value); }
+                }
+            }
+        },
+        (existing, new) => *existing = new
+    }
+}
+
+/// Library paths in the [Plugins] section are relative to the file they are written in.
+fn make_libraries_absolute(mut plugins : toml::Value, folder : &Path) -> toml::Value {
+    for (_, source) in plugins.as_table_mut().into_iter().flat_map(|t| t.iter_mut()) {
+        if let Some(toml::Value::String(library)) = source.get_mut("Library") {
+            let absolute = folder.join(&*library).to_string_lossy().into_owned();
+            *library = absolute;
+        }
+    }
+    plugins
+}
@@ -1,13 +1,26 @@
 use std::fmt;
+use std::collections::{BTreeMap, HashSet};
 use serde::{Serialize, Deserialize, Deserializer};
 use serde::de::{self, Visitor, DeserializeSeed, MapAccess, SeqAccess, Error};
 use super::plugin_database::PluginDatabase;
 use super::plugin;
+use super::theme::ThemeConfig;
 
 mod custom_deserializers;
+mod check;
+mod include;
+mod expand;
+mod format;
+mod schema;
+
+pub use check::ConfigProblem;
+pub use include::drop_in_folder;
+pub use format::{ConfigFormat, ConfigFile};
 
 #[cfg(test)]
 mod tests;
+#[cfg(test)]
+pub mod test_helper;
 
 /**
  * Struct that holds configuration options specific to the main program.
@@ -16,7 +29,27 @@ mod tests;
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(deny_unknown_fields, default)]
 pub struct SwaystatusMainConfig {
-    pub separator : String
+    pub separator : String,
+    pub output : OutputFormat,
+    pub separator_block_width : u32,
+    /// Delay before a crashed element is restarted. Doubles with every further crash.
+    pub restart_delay_ms : u64,
+    /// How often a crashed element is restarted before it's given up. 0 disables restarts.
+    pub restart_max_retries : u32,
+    /// How long each element gets to finish on quit or reload, before it's considered hung.
+    pub shutdown_timeout_ms : u64,
+    /// Reload once the config file changed. Off by default, reloads are only done on SIGHUP then.
+    pub reload_on_change : bool
+}
+
+/**
+ * The format in which the status line is written to stdout.
+ * `Plain` writes one line of text per update, `I3bar` speaks the JSON protocol of i3bar/swaybar.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Plain,
+    I3bar
 }
 /**
  * Helper struct for global configuration. Holds a list of element configurations.
@@ -29,11 +62,41 @@ pub struct SwaystatusConfig<'p> {
     ///Settings for the main part of the program.
     #[serde(rename = "Settings")]
     pub settings : Option<SwaystatusMainConfig>,
+    ///Colors and icons shared by all elements.
+    #[serde(rename = "Theme")]
+    pub theme : Option<ThemeConfig>,
+    ///Where plugins come from, for plugins that need more than a lookup in the plugin folders.
+    #[serde(rename = "Plugins", skip_serializing_if = "Option::is_none")]
+    pub plugins : Option<BTreeMap<String, PluginSourceConfig>>,
     ///Settings for each part of the output sting.
     #[serde(rename = "Element")]
     pub elements : Option<Vec<SwaystatusPluginConfig<'p>>>,
 }
 
+/**
+ * An entry in the [Plugins] section. Makes the plugin available under the name of the entry,
+ * either from a given library file, or as another name for a plugin found in the plugin folders.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields, rename_all="PascalCase")]
+pub struct PluginSourceConfig {
+    /// The library to load the plugin from. Relative paths start at the config file's folder.
+    pub library : Option<std::path::PathBuf>,
+    /// The name the plugin itself reports. Defaults to the name of the entry.
+    pub plugin : Option<String>
+}
+
+/**
+ * What the config file needs from the plugin folders, read before any plugins are loaded.
+ */
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PluginRequirements {
+    /// The plugin names the elements use.
+    pub referenced : HashSet<String>,
+    /// The [Plugins] section, with library paths made absolute.
+    pub sources : BTreeMap<String, PluginSourceConfig>
+}
+
 /**
  * Helper struct with custom deserializer. Holds config for a single element.
  * This is its own struct to make serialization/deserialization easier to maintain.
@@ -55,23 +118,159 @@ pub struct SwaystatusPluginConfig<'p> {
 #[derive(Serialize, Deserialize)]
 #[serde(deny_unknown_fields, default, rename_all="PascalCase")]
 pub struct SwaystatusElementNonPluginOptions {
+    /// Identifies the element in log messages, click events and commands of the control socket.
+    /// Has to be unique, without whitespace, and can't be a number, as elements without a name are
+    /// identified by their position.
+    pub name : Option<String>,
     pub before_text : String,
-    pub after_text : String
+    pub after_text : String,
+    /// Text color, used unless the plugin sets one.
+    pub foreground : Option<String>,
+    /// Background color, used unless the plugin sets one.
+    pub background : Option<String>,
+    /// Border color, used unless the plugin sets one.
+    pub border : Option<String>,
+    /// If a separator should follow this element. Defaults to true.
+    pub separator : Option<bool>,
+    /// Overrides the separator_block_width from the settings for this element.
+    pub separator_block_width : Option<u32>,
+    pub min_width : Option<ElementMinWidth>,
+    pub align : Option<ElementAlignment>,
+    /// If set, the signal SIGRTMIN plus this number refreshes the element.
+    pub signal : Option<u32>,
+    /// If set, the element is reported as unresponsive if it doesn't send anything for this long.
+    pub heartbeat_timeout_ms : Option<u64>,
+    /// Overrides the shutdown_timeout_ms from the settings for this element.
+    pub shutdown_timeout_ms : Option<u64>,
+    /// Where the element runs. Defaults to a thread of the main process.
+    pub isolation : Option<ElementIsolation>,
+    /// If set, the element is only shown on machines with one of these host names.
+    pub only_on_hosts : Option<Vec<String>>,
+    /// If set, the element is only shown if the condition holds.
+    pub when : Option<ElementCondition>
 }
 
 impl Default for SwaystatusElementNonPluginOptions {
     fn default() -> Self {
         SwaystatusElementNonPluginOptions{
+            name : None,
             before_text : String::new(),
-            after_text : String::new() 
+            after_text : String::new(),
+            foreground : None,
+            background : None,
+            border : None,
+            separator : None,
+            separator_block_width : None,
+            min_width : None,
+            align : None,
+            signal : None,
+            heartbeat_timeout_ms : None,
+            shutdown_timeout_ms : None,
+            isolation : None,
+            only_on_hosts : None,
+            when : None
         }
     }
 }
 
+/**
+ * Minimum width of an element. Either a number of pixels, or a text that's as wide as the element
+ * should be at least.
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum ElementMinWidth {
+    Pixels(u32),
+    Text(String)
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ElementAlignment {
+    Left,
+    Center,
+    Right
+}
+
+/**
+ * `Thread` runs the element in the main process. `Process` runs it in a helper process of its
+ * own, so a crash of the plugin can't take the whole program down.
+ */
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ElementIsolation {
+    #[default]
+    Thread,
+    Process
+}
+
+/**
+ * A condition for showing an element, checked whenever the config is read. Every part that is set
+ * has to hold.
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields, rename_all="PascalCase")]
+pub struct ElementCondition {
+    /// The host name of the machine.
+    pub hostname : Option<String>,
+    /// An environment variable that has to be set, to any value.
+    pub env_set : Option<String>,
+    /// A file or folder that has to exist.
+    pub file_exists : Option<std::path::PathBuf>
+}
+
+impl ElementCondition {
+    fn holds(&self) -> bool {
+        self.hostname.as_ref().is_none_or(|h| hostname().as_ref() == Some(h))
+            && self.env_set.as_ref().is_none_or(|v| std::env::var_os(v).is_some())
+            && self.file_exists.as_ref().is_none_or(|f| f.exists())
+    }
+}
+
+/// The host name of this machine, if it can be found out.
+fn hostname() -> Option<String> {
+    let mut buffer = [0u8; 256];
+    if unsafe { libc::gethostname(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len()) } != 0 {
+        return None;
+    }
+    let length = buffer.iter().position(|b| *b == 0)?;
+    String::from_utf8(buffer[..length].to_vec()).ok()
+}
+
+impl From<&ElementMinWidth> for plugin::MinWidth {
+    fn from(m : &ElementMinWidth) -> Self {
+        match m {
+            ElementMinWidth::Pixels(p) => plugin::MinWidth::Pixels(*p),
+            ElementMinWidth::Text(t) => plugin::MinWidth::Text(t.clone())
+        }
+    }
+}
+
+impl From<ElementAlignment> for plugin::Alignment {
+    fn from(a : ElementAlignment) -> Self {
+        match a {
+            ElementAlignment::Left => plugin::Alignment::Left,
+            ElementAlignment::Center => plugin::Alignment::Center,
+            ElementAlignment::Right => plugin::Alignment::Right
+        }
+    }
+}
+
+impl SwaystatusElementNonPluginOptions {
+    /// If a separator should be drawn after this element.
+    pub fn wants_separator(&self) -> bool {
+        self.separator.unwrap_or(true)
+    }
+    /// If the element belongs on this machine, according to `OnlyOnHosts` and `When`.
+    pub fn applies_here(&self) -> bool {
+        self.only_on_hosts.as_ref().is_none_or(|hosts| hostname().is_some_and(|h| hosts.contains(&h)))
+            && self.when.as_ref().is_none_or(ElementCondition::holds)
+    }
+}
+
 impl<'p> SwaystatusConfig<'p> {
-    fn serialize(&self) -> Result<String, toml::ser::Error> {
-        toml::to_string(self)
+    fn serialize(&self, format : ConfigFormat) -> Result<String, String> {
+        format.serialize(self)
     }
+    #[cfg(test)]
     fn deserialize(serialized : &str, plugins : &'p PluginDatabase) -> Result<SwaystatusConfig<'p>, toml::de::Error> {
         let seed = custom_deserializers::SwaystatusConfigDeserializeSeed(plugins);
         let mut deserializer = toml::Deserializer::new(serialized);
@@ -81,6 +280,8 @@ impl<'p> SwaystatusConfig<'p> {
     fn create_default(plugins : &'p PluginDatabase) -> SwaystatusConfig<'p> {
         SwaystatusConfig {
             settings : Some(SwaystatusMainConfig::default()),
+            theme : Some(ThemeConfig::default()),
+            plugins : None,
             elements : {
                 let v : Vec<SwaystatusPluginConfig> = 
                     plugins.get_name_and_plugin_iterator().map(|(name, object)| {
@@ -95,24 +296,97 @@ impl<'p> SwaystatusConfig<'p> {
         }
     }
 
-    pub fn print_sample_config(plugins : &PluginDatabase) {
-        let output = SwaystatusConfig::create_default(plugins).serialize().unwrap();
+    pub fn print_sample_config(plugins : &PluginDatabase, format : ConfigFormat) {
+        let output = SwaystatusConfig::create_default(plugins).serialize(format).unwrap();
         print!("{}", output);
     }
-    pub fn read_config<'d>(path : &'d std::path::Path, plugins : &'p PluginDatabase) -> Result<SwaystatusConfig<'p>,SwaystatusConfigErrors> {
-        let config_file = match std::fs::read_to_string(path) {
-            Ok(x) => x,
-            Err (_) => return Err(SwaystatusConfigErrors::FileNotFound)
-        };
-        let result = match SwaystatusConfig::deserialize(&config_file, plugins) {
-            Ok(x) => x,
-            Err(e) => return Err(SwaystatusConfigErrors::ParsingError{message: e.to_string()})
-        };
+    /// Reads the config file, together with the files it includes and its drop-ins.
+    pub fn read_config(file : &ConfigFile, plugins : &'p PluginDatabase) -> Result<SwaystatusConfig<'p>,SwaystatusConfigErrors> {
+        SwaystatusConfig::from_merged(&file.path, include::read_merged(file)?, plugins)
+    }
 
+    fn from_merged(path : &std::path::Path, merged : include::MergedConfig, plugins : &'p PluginDatabase) -> Result<SwaystatusConfig<'p>,SwaystatusConfigErrors> {
+        let table_problems = check::check_tables(&merged.tables, plugins);
+        if let Some(problem) = merged.problems.into_iter().chain(table_problems).next() {
+            let message = match problem.position {
+                Some((line, column)) => format!("{} at line {} column {}", problem.message, line, column),
+                None => problem.message
+            };
+            return Err(SwaystatusConfigErrors::ParsingError { file : problem.file.unwrap_or_else(|| path.to_path_buf()), message });
+        }
+        let mut table = merged.table;
+        let elements = table.remove("Element");
+        let mut result = custom_deserializers::SwaystatusConfigDeserializeSeed(plugins).deserialize(toml::Value::Table(table))
+            .map_err(|e| SwaystatusConfigErrors::ParsingError { file : path.to_path_buf(), message : e.to_string() })?;
+        //Deserialized one by one, to know which file an error is in.
+        if let Some(toml::Value::Array(elements)) = elements {
+            result.elements = Some(elements.into_iter().zip(merged.element_sources).enumerate().map(|(element_number, (element, source))| {
+                custom_deserializers::SwaystatusPluginConfigSeed(plugins).deserialize(element).map_err(|e| {
+                    let message = match source.position {
+                        Some((line, column)) => format!("element number {}: {} at line {} column {}", element_number, e, line, column),
+                        None => format!("element number {}: {}", element_number, e)
+                    };
+                    SwaystatusConfigErrors::ParsingError { file : source.file, message }
+                })
+            }).collect::<Result<_,_>>()?);
+        }
         Ok(result)
     }
 }
 
+/// The plugins the config file needs, read without any plugins loaded. Returns None if the file
+/// can't be read this way. The full parser will report the reason later.
+pub fn read_plugin_requirements(file : &ConfigFile) -> Option<PluginRequirements> {
+    plugin_requirements(&include::read_merged(file).ok()?)
+}
+
+/// The config file and all files it pulls in, so they can be watched for changes. The folder
+/// with the drop-ins isn't part of the list.
+pub fn read_config_files(file : &ConfigFile) -> Vec<std::path::PathBuf> {
+    match include::read_merged(file) {
+        Ok(merged) => merged.files,
+        Err(_) => vec![file.path.clone()]
+    }
+}
+
+fn plugin_requirements(merged : &include::MergedConfig) -> Option<PluginRequirements> {
+    if !merged.problems.is_empty() {
+        return None;
+    }
+    let value = &merged.table;
+    let referenced = match value.get("Element") {
+        None => HashSet::new(),
+        Some(elements) => elements.as_array()?.iter()
+            .map(|element| element.get("Plugin").and_then(toml::Value::as_str).map(String::from))
+            .collect::<Option<_>>()?
+    };
+    //The merge already made library paths absolute.
+    let sources = match value.get("Plugins") {
+        None => BTreeMap::new(),
+        Some(plugins) => plugins.clone().try_into().ok()?
+    };
+    Some(PluginRequirements { referenced, sources })
+}
+
+impl PluginRequirements {
+    /// The names to look for in the plugin folders. Plugins with their own library aren't searched,
+    /// and for other names the plugin they stand for is searched.
+    pub fn plugins_to_search(&self) -> HashSet<String> {
+        self.referenced.iter().filter_map(|name| match self.sources.get(name) {
+            None => Some(name.clone()),
+            Some(PluginSourceConfig { library : Some(_), .. }) => None,
+            Some(PluginSourceConfig { library : None, plugin }) => Some(plugin.clone().unwrap_or_else(|| name.clone()))
+        }).collect()
+    }
+    /// The entries of the [Plugins] section that elements actually use.
+    pub fn used_sources(&self) -> BTreeMap<String, PluginSourceConfig> {
+        self.sources.iter()
+            .filter(|(name, _)| self.referenced.contains(*name))
+            .map(|(name, source)| (name.clone(), source.clone()))
+            .collect()
+    }
+}
+
 impl<'p> SwaystatusPluginConfig<'p> {
     pub fn get_instance(&'p self) -> &(dyn plugin::SwayStatusModuleInstance + 'p) {
         &*self.config
@@ -124,18 +398,32 @@ impl<'p> SwaystatusPluginConfig<'p> {
         &self.general
 
     }
+    /// The element's name from its general options, if it has one.
+    pub fn get_element_name(&self) -> Option<&str> {
+        self.general.name.as_deref()
+    }
 }
 
 pub enum SwaystatusConfigErrors
 {
     FileNotFound,
     ParsingError {
+        /// The file the error is in. Can be an included file or a drop-in.
+        file : std::path::PathBuf,
         message : String
     }
 }
 
 impl Default for SwaystatusMainConfig {
     fn default() -> Self {
-        SwaystatusMainConfig { separator : String::from(", ")}
+        SwaystatusMainConfig { 
+            separator : String::from(", "),
+            output : OutputFormat::Plain,
+            separator_block_width : 9,
+            restart_delay_ms : 1000,
+            restart_max_retries : 5,
+            shutdown_timeout_ms : 2000,
+            reload_on_change : false
+        }
     }
 }
@@ -0,0 +1,145 @@
+//! A JSON Schema of the whole config file, for `--print-config-schema`. The sections of the main
+//! program are described here, the `[Element.Config]` sections by the plugins themselves. Editors
+//! use the schema for completion and validation, for TOML as well as for JSON and YAML.
+
+use gettextrs::*;
+use serde_json::{json, Value};
+use super::*;
+
+impl<'p> SwaystatusConfig<'p> {
+    pub fn print_config_schema(plugins : &PluginDatabase) {
+        println!("{}", serde_json::to_string_pretty(&config_schema(plugins)).unwrap());
+    }
+}
+
+pub(super) fn config_schema(plugins : &PluginDatabase) -> Value {
+    let mut plugins : Vec<_> = plugins.get_name_and_plugin_iterator().collect();
+    plugins.sort_by_key(|(name, _)| *name);
+    let names : Vec<&String> = plugins.iter().map(|(name, _)| *name).collect();
+    //Each plugin's schema only applies to the elements that use the plugin.
+    let plugin_configs : Vec<Value> = plugins.iter().filter_map(|(name, plugin)| {
+        let schema : Value = match serde_json::from_str(&plugin.get_config_schema()?) {
+            Ok(schema) => schema,
+            Err(e) => {
+                eprintln!("{}", gettext!("Plugin {} returned an invalid config schema: {}", name, e));
+                return None;
+            }
+        };
+        Some(json!({
+            "if" : { "properties" : { "Plugin" : { "const" : name } }, "required" : ["Plugin"] },
+            "then" : { "properties" : { "Config" : schema } }
+        }))
+    }).collect();
+    json!({
+        "$schema" : "http://json-schema.org/draft-07/schema#",
+        "title" : "swaystatus configuration",
+        "type" : "object",
+        "properties" : {
+            "Include" : { "type" : "array", "items" : { "type" : "string" }, "description" : "Files to merge before this one, relative to it." },
+            "Settings" : settings_schema(),
+            "Theme" : theme_schema(),
+            "Plugins" : {
+                "type" : "object",
+                "description" : "Makes plugins available under other names, or from a given library.",
+                "additionalProperties" : {
+                    "type" : "object",
+                    "properties" : {
+                        "Library" : { "type" : "string" },
+                        "Plugin" : { "type" : "string" }
+                    },
+                    "additionalProperties" : false
+                }
+            },
+            "Element" : {
+                "type" : "array",
+                "items" : {
+                    "type" : "object",
+                    "properties" : {
+                        "Plugin" : { "type" : "string", "examples" : names, "description" : "A plugin name, or an entry of the Plugins section." },
+                        "Config" : { "type" : "object" },
+                        "General" : general_schema(),
+                        "InsertAt" : { "type" : "integer", "minimum" : 0, "description" : "Position among the elements merged before this one." }
+                    },
+                    "required" : ["Plugin", "Config"],
+                    "allOf" : plugin_configs
+                }
+            }
+        }
+    })
+}
+
+fn settings_schema() -> Value {
+    json!({
+        "type" : "object",
+        "properties" : {
+            "separator" : { "type" : "string" },
+            "output" : { "enum" : ["Plain", "I3bar"] },
+            "separator_block_width" : { "type" : "integer", "minimum" : 0 },
+            "restart_delay_ms" : { "type" : "integer", "minimum" : 0 },
+            "restart_max_retries" : { "type" : "integer", "minimum" : 0 },
+            "shutdown_timeout_ms" : { "type" : "integer", "minimum" : 0 },
+            "reload_on_change" : { "type" : "boolean" }
+        },
+        "additionalProperties" : false
+    })
+}
+
+fn theme_schema() -> Value {
+    let color = json!({ "type" : "string", "description" : "A color as \"#RRGGBB\"." });
+    json!({
+        "type" : "object",
+        "properties" : {
+            "Palette" : { "enum" : ["Default", "Solarized", "Gruvbox"] },
+            "IconSet" : { "enum" : ["Ascii", "NerdFont", "Emoji"] },
+            "Colors" : {
+                "type" : "object",
+                "properties" : {
+                    "Idle" : color,
+                    "Info" : color,
+                    "Good" : color,
+                    "Warning" : color,
+                    "Critical" : color,
+                    "Separator" : color,
+                    "SeparatorBackground" : color
+                },
+                "additionalProperties" : false
+            },
+            "Icons" : { "type" : "object", "additionalProperties" : { "type" : "string" } }
+        },
+        "additionalProperties" : false
+    })
+}
+
+fn general_schema() -> Value {
+    let color = json!({ "type" : "string", "description" : "A color as \"#RRGGBB\"." });
+    json!({
+        "type" : "object",
+        "properties" : {
+            "Name" : { "type" : "string", "pattern" : "^\\S*\\D\\S*$", "description" : "Identifies the element. Unique, without whitespace, and not a number." },
+            "BeforeText" : { "type" : "string" },
+            "AfterText" : { "type" : "string" },
+            "Foreground" : color,
+            "Background" : color,
+            "Border" : color,
+            "Separator" : { "type" : "boolean" },
+            "SeparatorBlockWidth" : { "type" : "integer", "minimum" : 0 },
+            "MinWidth" : { "type" : ["integer", "string"], "description" : "Pixels, or a text as wide as the element should be at least." },
+            "Align" : { "enum" : ["Left", "Center", "Right"] },
+            "Signal" : { "type" : "integer", "minimum" : 1, "maximum" : crate::signalhandler::max_element_signal(), "description" : "SIGRTMIN plus this number refreshes the element." },
+            "HeartbeatTimeoutMs" : { "type" : "integer", "minimum" : 0 },
+            "ShutdownTimeoutMs" : { "type" : "integer", "minimum" : 0 },
+            "Isolation" : { "enum" : ["Thread", "Process"] },
+            "OnlyOnHosts" : { "type" : "array", "items" : { "type" : "string" } },
+            "When" : {
+                "type" : "object",
+                "properties" : {
+                    "Hostname" : { "type" : "string" },
+                    "EnvSet" : { "type" : "string" },
+                    "FileExists" : { "type" : "string" }
+                },
+                "additionalProperties" : false
+            }
+        },
+        "additionalProperties" : false
+    })
+}
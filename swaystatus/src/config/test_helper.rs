@@ -0,0 +1,7 @@
+use crate::plugin_database::PluginDatabase;
+use super::{SwaystatusPluginConfig, SwaystatusConfig};
+
+/// Deserializes the given config and returns its elements. Meant to be used with the test plugin.
+pub fn get_elements_from_config<'p>(config : &str, plugins : &'p PluginDatabase) -> Vec<SwaystatusPluginConfig<'p>> {
+    SwaystatusConfig::deserialize(config, plugins).unwrap().elements.unwrap_or_default()
+}
@@ -96,12 +96,39 @@ fn custom_deserialize_optional_field_elements()
     "[Settings]\nseparator = \"Kisses!\"\n"
     );
     let deserialized = SwaystatusConfig::deserialize(&test_config, &p).unwrap();
+    assert!(deserialized.elements.is_none());
     let serialized = toml::to_string(&deserialized).unwrap();
     //println!("{}", serialized);
+    //The settings that aren't in the file are written with their defaults.
+    assert!(serialized.starts_with(&test_config), "{}", serialized);
+}
+
+#[test]
+fn settings_survive_a_round_trip()
+{
+    let p = get_plugin_database_with_test_plugin();
+    let test_config = String::from(
+    "[Settings]\nseparator = \"Kisses!\"\noutput = \"I3bar\"\nseparator_block_width = 5\nrestart_delay_ms = 500\nrestart_max_retries = 3\nshutdown_timeout_ms = 100\nreload_on_change = true\n"
+    );
+    let deserialized = SwaystatusConfig::deserialize(&test_config, &p).unwrap();
+    let serialized = toml::to_string(&deserialized).unwrap();
     assert_eq!(test_config, serialized);
 }
 
 
+#[test]
+fn custom_deserialize_theme()
+{
+    let p = get_plugin_database_with_test_plugin();
+    let test_config = String::from(
+    "[Theme]\nPalette = \"Gruvbox\"\nIconSet = \"NerdFont\"\n\n[Theme.Colors]\nCritical = \"#FF00FF\"\n\n[Theme.Icons]\nvolume_muted = \"M\"\n"
+    );
+    let deserialized = SwaystatusConfig::deserialize(&test_config, &p).unwrap();
+    let serialized = toml::to_string(&deserialized).unwrap();
+    //println!("{}", serialized);
+    assert_eq!(test_config, serialized);
+}
+
 #[test]
 fn custom_deserialize_multiple_plugins()
 {
@@ -120,10 +147,257 @@ fn custom_deserialize_multiple_plugins()
 fn self_consistency(){
     let p = get_plugin_database_with_test_plugin();
     let def = SwaystatusConfig::create_default(&p);
-    let serialized = def.serialize().unwrap();
+    let serialized = def.serialize(ConfigFormat::Toml).unwrap();
     let deserialized = SwaystatusConfig::deserialize(&serialized, &p).unwrap();
     let serialized2 = toml::to_string(&deserialized).unwrap();
     //println!("{}", serialized2);
     assert_eq!(serialized, serialized2);
 }
 
+#[test]
+fn sample_config_reads_back_in_all_formats() {
+    let p = get_plugin_database_with_test_plugin();
+    let def = SwaystatusConfig::create_default(&p);
+    for format in [ConfigFormat::Toml, ConfigFormat::Json, ConfigFormat::Yaml] {
+        let serialized = def.serialize(format).unwrap();
+        let file = ConfigFile { path : std::path::PathBuf::from("/nonexistent/config"), format };
+        let deserialized = SwaystatusConfig::from_merged(&file.path, include::merge(&file, &serialized), &p).ok().unwrap();
+        assert_eq!(serialized, deserialized.serialize(format).unwrap(), "{:?}", format);
+    }
+}
+
+
+/// Merges the given config as if it was a file in the given folder.
+fn merged_in(folder : &str, config : &str) -> include::MergedConfig {
+    include::merge(&ConfigFile::new(std::path::Path::new(folder).join("config")), config)
+}
+
+#[test]
+fn referenced_plugins_are_found_without_plugins() {
+    let config = "[Settings]\n[[Element]]\nPlugin = \"Clock\"\nformat = \"%R\"\n[[Element]]\nPlugin = \"Exec\"\n[[Element]]\nPlugin = \"Clock\"\n";
+    let requirements = plugin_requirements(&merged_in("/etc", config)).unwrap();
+    assert_eq!(requirements.referenced, ["Clock", "Exec"].into_iter().map(String::from).collect());
+    assert!(requirements.sources.is_empty());
+    assert!(plugin_requirements(&merged_in("/etc", "[Settings]\n")).unwrap().referenced.is_empty());
+}
+
+#[test]
+fn referenced_plugins_give_up_on_broken_configs() {
+    assert!(plugin_requirements(&merged_in("/etc", "[[Element]\n")).is_none());
+    assert!(plugin_requirements(&merged_in("/etc", "[[Element]]\nformat = \"%R\"\n")).is_none());
+    assert!(plugin_requirements(&merged_in("/etc", "[Plugins.A]\nLibary = \"a.so\"\n")).is_none());
+}
+
+#[test]
+fn plugin_sources_decide_what_is_searched() {
+    let config = "[Plugins.PatchedPulse]\nLibrary = \"plugins/libpatched.so\"\nPlugin = \"PulseVolume\"\n\
+        [Plugins.Time]\nPlugin = \"ClockPlugin\"\n\
+        [Plugins.Unused]\nPlugin = \"Other\"\n\
+        [[Element]]\nPlugin = \"PatchedPulse\"\n[[Element]]\nPlugin = \"PulseVolume\"\n[[Element]]\nPlugin = \"Time\"\n";
+    let requirements = plugin_requirements(&merged_in("/etc/swaystatus", config)).unwrap();
+    assert_eq!(requirements.sources["PatchedPulse"].library.as_deref(), Some(std::path::Path::new("/etc/swaystatus/plugins/libpatched.so")));
+    assert_eq!(requirements.plugins_to_search(), ["PulseVolume", "ClockPlugin"].into_iter().map(String::from).collect());
+    assert_eq!(requirements.used_sources().keys().collect::<Vec<_>>(), ["PatchedPulse", "Time"]);
+}
+
+#[test]
+fn plugin_sources_are_accepted_by_the_full_parser() {
+    let p = get_plugin_database_with_test_plugin();
+    let config = "[Plugins.Other]\nPlugin = \"TestPlugin\"\n";
+    let deserialized = SwaystatusConfig::deserialize(config, &p).unwrap();
+    assert_eq!(deserialized.plugins.unwrap()["Other"].plugin.as_deref(), Some("TestPlugin"));
+}
+
+#[test]
+fn check_reports_all_problems_with_positions() {
+    let p = get_plugin_database_with_test_plugin();
+    let config = "[Settings]\nseparator = 3\n\n\
+        [[Element]]\nPlugin = \"TestPlugin\"\n[Element.Config]\nlines = 1\nskull = \"x\"\n\n\
+        [[Element]]\nPlugin = \"TestPlugin\"\n[Element.Config]\nlines = 1\n\n\
+        [[Element]]\nPlugin = \"TestPlugin\"\n[Element.Config]\nlines = 0\nskull = \"x\"\n";
+    let problems = check::check(merged_in("/nonexistent", config), &p);
+    assert_eq!(problems.len(), 3, "{:?}", problems);
+    assert_eq!((problems[0].element, problems[0].position), (None, Some((2, 1))));
+    assert_eq!((problems[1].element, problems[1].position), (Some(1), Some((11, 1))));
+    assert!(problems[1].message.contains("skull"));
+    assert_eq!((problems[2].element, problems[2].position), (Some(2), Some((17, 10))));
+    assert_eq!(problems[2].message, "no lines");
+}
+
+#[test]
+fn check_points_at_the_keys_with_problems() {
+    let p = get_plugin_database_with_test_plugin();
+    let config = "Unknown = 1\nTheme = { Icons = 5 }\n[Settings]\nseparator = \" \"\nbogus = 1\n";
+    let problems = check::check(merged_in("/nonexistent", config), &p);
+    let positions : Vec<_> = problems.iter().filter_map(|p| p.position).collect();
+    assert_eq!(positions, [(1, 1), (2, 11), (5, 1)], "{:?}", problems);
+    assert!(problems[2].message.contains("bogus"), "{}", problems[1].message);
+}
+
+#[test]
+fn check_reports_syntax_errors_and_unknown_plugins() {
+    let p = get_plugin_database_with_test_plugin();
+    let problems = check::check(merged_in("/nonexistent", "[[Element]\n"), &p);
+    assert_eq!(problems.len(), 1);
+    assert_eq!(problems[0].position.map(|p| p.0), Some(1));
+    let problems = check::check(merged_in("/nonexistent", "[[Element]]\nPlugin = \"Unknown\"\n[Element.Config]\n"), &p);
+    assert_eq!(problems.len(), 1);
+    assert_eq!(problems[0].element, Some(0));
+    assert!(check::check(merged_in("/nonexistent", "[[Element]]\nPlugin = \"TestPlugin\"\n[Element.Config]\nlines = 1\nskull = \"x\"\n"), &p).is_empty());
+}
+
+#[test]
+fn isolation_needs_a_library() {
+    let p = get_plugin_database_with_test_plugin();
+    let element = "[[Element]]\nPlugin = \"TestPlugin\"\n[Element.Config]\nlines = 1\nskull = \"x\"\n[Element.General]\nIsolation = \"Process\"\n";
+    let problems = check::check(merged_in("/nonexistent", element), &p);
+    assert_eq!(problems.len(), 1);
+    assert_eq!(problems[0].position, Some((7, 1)));
+    assert!(problems[0].message.contains("TestPlugin"), "{}", problems[0].message);
+}
+
+#[test]
+fn element_names_are_unique_and_not_numbers() {
+    let p = get_plugin_database_with_test_plugin();
+    let element = |name : &str| format!("[[Element]]\nPlugin = \"TestPlugin\"\n[Element.Config]\nlines = 1\nskull = \"x\"\n[Element.General]\nName = \"{}\"\n", name);
+    let unnamed = "[[Element]]\nPlugin = \"TestPlugin\"\n[Element.Config]\nlines = 1\nskull = \"x\"\n";
+    assert!(check::check(merged_in("/nonexistent", &[element("clock"), String::from(unnamed), element("date")].concat()), &p).is_empty());
+    let problems = check::check(merged_in("/nonexistent", &[element("clock"), String::from(unnamed), element("clock"), element("2")].concat()), &p);
+    assert_eq!(problems.iter().map(|p| p.element).collect::<Vec<_>>(), [Some(2), Some(3)]);
+    assert!(problems[0].message.contains("element number 0"), "{}", problems[0].message);
+    assert!(problems[0].position.is_some());
+    assert_eq!(check::check(merged_in("/nonexistent", &element("two words")), &p).len(), 1);
+}
+
+#[test]
+fn element_signals_are_real_time_signals() {
+    let p = get_plugin_database_with_test_plugin();
+    let element = |signal : i64| format!("[[Element]]\nPlugin = \"TestPlugin\"\n[Element.Config]\nlines = 1\nskull = \"x\"\n[Element.General]\nSignal = {}\n", signal);
+    let max = crate::signalhandler::max_element_signal() as i64;
+    assert!(check::check(merged_in("/nonexistent", &[element(1), element(max)].concat()), &p).is_empty());
+    let problems = check::check(merged_in("/nonexistent", &[element(0), element(max + 1)].concat()), &p);
+    assert_eq!(problems.iter().map(|p| (p.element, p.position)).collect::<Vec<_>>(), [(Some(0), Some((7, 1))), (Some(1), Some((14, 1)))]);
+}
+
+fn make_config_folder(test_name : &str, files : &[(&str, &str)]) -> std::path::PathBuf {
+    let folder = std::env::temp_dir().join(format!("swaystatus-{}-{}", test_name, std::process::id()));
+    std::fs::create_dir_all(folder.join("config.d")).unwrap();
+    for (name, content) in files {
+        std::fs::create_dir_all(folder.join(name).parent().unwrap()).unwrap();
+        std::fs::write(folder.join(name), content).unwrap();
+    }
+    folder
+}
+
+#[test]
+fn includes_and_drop_ins_are_merged_in_order() {
+    let p = get_plugin_database_with_test_plugin();
+    let folder = make_config_folder("include-order", &[
+        ("base.toml", "[Settings]\nseparator = \" | \"\nrestart_delay_ms = 5\n[[Element]]\nPlugin = \"TestPlugin\"\n[Element.Config]\nlines = 1\nskull = \"base\"\n"),
+        ("config", "Include = [\"base.toml\"]\n[Settings]\nseparator = \" / \"\n[[Element]]\nPlugin = \"TestPlugin\"\n[Element.Config]\nlines = 1\nskull = \"main\"\n"),
+        ("config.d/10-first.toml", "[[Element]]\nPlugin = \"TestPlugin\"\nInsertAt = 0\n[Element.Config]\nlines = 1\nskull = \"first\"\n"),
+        ("config.d/notes.txt", "not a config"),
+    ]);
+    let config = folder.join("config");
+    let file = ConfigFile::new(config.clone());
+    let deserialized = SwaystatusConfig::read_config(&file, &p).ok().unwrap();
+    let settings = deserialized.settings.unwrap();
+    assert_eq!((settings.separator.as_str(), settings.restart_delay_ms), (" / ", 5));
+    let skulls : Vec<_> = deserialized.elements.unwrap().iter()
+        .map(|e| serde_json::to_value(e).unwrap()["Config"]["skull"].as_str().unwrap().to_owned())
+        .collect();
+    assert_eq!(skulls, ["first", "base", "main"]);
+    assert_eq!(read_config_files(&file), [config.clone(), folder.join("base.toml"), folder.join("config.d/10-first.toml")]);
+    std::fs::remove_dir_all(folder).unwrap();
+}
+
+#[test]
+fn problems_name_the_file_they_are_in() {
+    let p = get_plugin_database_with_test_plugin();
+    let folder = make_config_folder("include-problems", &[
+        ("config", "Include = [\"elements.toml\", \"loop.toml\"]\n"),
+        ("elements.toml", "[[Element]]\nPlugin = \"TestPlugin\"\n[Element.Config]\nlines = 1\n"),
+        ("loop.toml", "Include = [\"config\"]\n"),
+    ]);
+    let config = ConfigFile::new(folder.join("config"));
+    match SwaystatusConfig::read_config(&config, &p) {
+        Err(SwaystatusConfigErrors::ParsingError { file, .. }) => assert_eq!(file, folder.join("config")),
+        _ => panic!("the include cycle should be an error")
+    }
+    let problems = SwaystatusConfig::check_config(&config, &p).ok().unwrap();
+    assert_eq!(problems.len(), 2, "{:?}", problems);
+    assert_eq!(problems[0].file.as_deref(), Some(&*folder.join("config")));
+    assert_eq!((problems[1].file.as_deref(), problems[1].element, problems[1].position), (Some(&*folder.join("elements.toml")), Some(0), Some((2, 1))));
+    std::fs::remove_dir_all(folder).unwrap();
+}
+
+#[test]
+fn variables_are_expanded() {
+    let lookup = |name : &str| match name {
+        "HOME" => Some(String::from("/home/me")),
+        "EMPTY" => Some(String::new()),
+        _ => None
+    };
+    assert_eq!(expand::expand_string("${HOME}/bin, $5", &lookup).unwrap(), "/home/me/bin, $5");
+    assert_eq!(expand::expand_string("${EMPTY}|${EMPTY:-x}|${UNSET:-${HOME}}", &lookup).unwrap(), "|x|/home/me");
+    assert_eq!(expand::expand_string("echo $${i}", &lookup).unwrap(), "echo ${i}");
+    assert!(expand::expand_string("${UNSET}", &lookup).is_err());
+    assert!(expand::expand_string("${HOME", &lookup).is_err());
+}
+
+#[test]
+fn elements_for_other_machines_are_left_out() {
+    let p = get_plugin_database_with_test_plugin();
+    let host = hostname().unwrap();
+    let config = format!("[[Element]]\nPlugin = \"Elsewhere\"\n[Element.General]\nOnlyOnHosts = [\"not-{host}\"]\nBeforeText = \"${{SWAYSTATUS_TEST_UNSET}}\"\n\
+        [[Element]]\nPlugin = \"Elsewhere\"\n[Element.General.When]\nFileExists = \"/nonexistent\"\n\
+        [[Element]]\nPlugin = \"TestPlugin\"\n[Element.Config]\nlines = 1\nskull = \"x\"\n[Element.General]\nOnlyOnHosts = [\"{host}\"]\n[Element.General.When]\nHostname = \"{host}\"\nEnvSet = \"PATH\"\n");
+    let merged = merged_in("/nonexistent", &config);
+    assert_eq!(plugin_requirements(&merged).unwrap().referenced, HashSet::from([String::from("TestPlugin")]));
+    assert!(check::check(merged, &p).is_empty());
+}
+
+#[test]
+fn formats_can_be_mixed() {
+    let p = get_plugin_database_with_test_plugin();
+    let folder = make_config_folder("formats", &[
+        ("config.yaml", "Include: [settings.json]\nElement:\n  - Plugin: TestPlugin\n    Config: { lines: 1, skull: yaml }\n"),
+        ("settings.json", "{ \"Settings\": { \"separator\": \" | \" }, \"Theme\": null }"),
+        ("config.yaml.d/10-more.toml", "[[Element]]\nPlugin = \"TestPlugin\"\n[Element.Config]\nlines = 1\nskull = \"toml\"\n"),
+        ("config.yaml.d/20-broken.json", "{ \"Element\": [ }"),
+    ]);
+    let config = ConfigFile::new(folder.join("config.yaml"));
+    assert_eq!(config.format, ConfigFormat::Yaml);
+    let problems = SwaystatusConfig::check_config(&config, &p).ok().unwrap();
+    assert_eq!(problems.len(), 1, "{:?}", problems);
+    assert_eq!((problems[0].file.as_deref(), problems[0].position), (Some(&*folder.join("config.yaml.d/20-broken.json")), Some((1, 16))));
+    std::fs::remove_file(folder.join("config.yaml.d/20-broken.json")).unwrap();
+    let deserialized = SwaystatusConfig::read_config(&config, &p).ok().unwrap();
+    assert_eq!(deserialized.settings.unwrap().separator, " | ");
+    assert_eq!(deserialized.elements.unwrap().len(), 2);
+    std::fs::remove_dir_all(folder).unwrap();
+}
+
+/// The names of the keys in a serialized value, to compare them with the schema.
+fn keys_of(value : &impl Serialize) -> Vec<String> {
+    serde_json::to_value(value).unwrap().as_object().unwrap().keys().cloned().collect()
+}
+
+fn keys_in_schema(schema : &serde_json::Value) -> Vec<String> {
+    schema["properties"].as_object().unwrap().keys().cloned().collect()
+}
+
+#[test]
+fn schema_describes_every_key() {
+    let p = get_plugin_database_with_test_plugin();
+    let schema = schema::config_schema(&p);
+    let properties = &schema["properties"];
+    assert_eq!(keys_in_schema(&properties["Settings"]), keys_of(&SwaystatusMainConfig::default()));
+    assert_eq!(keys_in_schema(&properties["Theme"]), keys_of(&ThemeConfig::default()));
+    assert_eq!(keys_in_schema(&properties["Theme"]["properties"]["Colors"]), keys_of(&crate::theme::Palette::default()));
+    let element = &properties["Element"]["items"];
+    assert_eq!(keys_in_schema(&element["properties"]["General"]), keys_of(&SwaystatusElementNonPluginOptions::default()));
+    assert_eq!(keys_in_schema(&element["properties"]["General"]["properties"]["When"]), keys_of(&ElementCondition::default()));
+    assert_eq!(element["allOf"][0]["if"]["properties"]["Plugin"]["const"], "TestPlugin");
+    assert_eq!(element["allOf"][0]["then"]["properties"]["Config"]["required"], serde_json::json!(["lines", "skull"]));
+}
@@ -0,0 +1,195 @@
+//! Watches the config file with inotify, so the bar can reload once it's changed.
+//! The folders containing the files are watched, not the files themselves, because most editors
+//! save by writing a new file and renaming it over the old one. The folder with the drop-ins is
+//! watched as a whole, for config files (`.toml`, `.json`, `.yaml` or `.yml`) being added, changed or
+//! removed.
+
+use std::ffi::{CString, OsStr, OsString};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests;
+
+/// How long to wait for further changes after a file changed. Saving a file often causes
+/// several events, and the config should only be read once all of them are done.
+const SETTLE_TIME : Duration = Duration::from_millis(200);
+
+pub struct ConfigWatcher {
+    inotify : OwnedFd,
+    stop : OwnedFd,
+    watches : Vec<Watch>,
+}
+
+/// A watched folder.
+struct Watch {
+    descriptor : libc::c_int,
+    /// The names of the watched files in the folder.
+    names : Vec<OsString>,
+    /// If all config files in the folder are watched.
+    config_files : bool,
+}
+
+/// Wakes up a `ConfigWatcher` that's waiting for changes, and makes it return. Can be used from
+/// any thread.
+pub struct ConfigWatcherStop(OwnedFd);
+
+impl ConfigWatcherStop {
+    pub fn stop(&self) {
+        let value : u64 = 1;
+        unsafe { libc::write(self.0.as_raw_fd(), &value as *const u64 as *const libc::c_void, std::mem::size_of::<u64>()) };
+    }
+}
+
+impl ConfigWatcher {
+    /// Starts watching the given files and folders, see `watch`.
+    pub fn new(files : &[PathBuf], folders : &[PathBuf]) -> std::io::Result<ConfigWatcher> {
+        let stop = new_fd(unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) })?;
+        let mut watcher = ConfigWatcher { inotify : new_inotify()?, stop, watches : Vec::new() };
+        watcher.watch(files, folders)?;
+        Ok(watcher)
+    }
+
+    /// Replaces what's watched, for instance because the list of included files changed.
+    /// Symbolic links are watched as well as the files they point to. Folders don't need to
+    /// exist, their creation counts as a change.
+    pub fn watch(&mut self, files : &[PathBuf], folders : &[PathBuf]) -> std::io::Result<()> {
+        self.inotify = new_inotify()?;
+        self.watches.clear();
+        for file in files.iter().chain(folders) {
+            self.add(file)?;
+            if let Ok(target) = file.canonicalize() {
+                if &target != file {
+                    self.add(&target)?;
+                }
+            }
+        }
+        for folder in folders.iter().filter(|f| f.is_dir()) {
+            let descriptor = self.add_watch(folder)?;
+            self.watch_for(descriptor).config_files = true;
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, file : &Path) -> std::io::Result<()> {
+        let (Some(folder), Some(name)) = (file.parent(), file.file_name()) else {
+            return Err(std::io::Error::from(std::io::ErrorKind::InvalidInput));
+        };
+        let folder = if folder.as_os_str().is_empty() { Path::new(".") } else { folder };
+        let descriptor = self.add_watch(folder)?;
+        self.watch_for(descriptor).names.push(name.to_os_string());
+        Ok(())
+    }
+
+    fn add_watch(&self, folder : &Path) -> std::io::Result<libc::c_int> {
+        let folder = CString::new(folder.as_os_str().as_bytes()).map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidInput))?;
+        let descriptor = unsafe { libc::inotify_add_watch(self.inotify.as_raw_fd(), folder.as_ptr(), libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_MOVED_FROM | libc::IN_CREATE | libc::IN_DELETE) };
+        if descriptor < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(descriptor)
+    }
+
+    /// inotify returns the same descriptor if a folder is watched twice.
+    fn watch_for(&mut self, descriptor : libc::c_int) -> &mut Watch {
+        match self.watches.iter().position(|w| w.descriptor == descriptor) {
+            Some(index) => &mut self.watches[index],
+            None => {
+                self.watches.push(Watch { descriptor, names : Vec::new(), config_files : false });
+                self.watches.last_mut().unwrap()
+            }
+        }
+    }
+
+    pub fn stopper(&self) -> std::io::Result<ConfigWatcherStop> {
+        Ok(ConfigWatcherStop(self.stop.try_clone()?))
+    }
+
+    /// Blocks until one of the files has changed and no further changes followed for a moment.
+    /// Returns false if stopped, or if watching failed.
+    pub fn wait_for_change(&self) -> bool {
+        loop {
+            match self.wait(None) {
+                Wait::Stopped => return false,
+                Wait::Changed => break,
+                Wait::Other | Wait::Timeout => {}
+            }
+        }
+        loop {
+            match self.wait(Some(SETTLE_TIME)) {
+                Wait::Stopped => return false,
+                Wait::Timeout => return true,
+                Wait::Changed | Wait::Other => {}
+            }
+        }
+    }
+
+    fn wait(&self, timeout : Option<Duration>) -> Wait {
+        let mut fds = [
+            libc::pollfd { fd : self.inotify.as_raw_fd(), events : libc::POLLIN, revents : 0 },
+            libc::pollfd { fd : self.stop.as_raw_fd(), events : libc::POLLIN, revents : 0 },
+        ];
+        let timeout = timeout.map_or(-1, |t| t.as_millis() as libc::c_int);
+        let result = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) };
+        if result < 0 {
+            return if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted { Wait::Other } else { Wait::Stopped };
+        }
+        if fds[1].revents != 0 {
+            return Wait::Stopped;
+        }
+        if fds[0].revents == 0 {
+            return Wait::Timeout;
+        }
+        if self.read_events() { Wait::Changed } else { Wait::Other }
+    }
+
+    /// Reads all pending events. Returns true if one of them concerns a watched file.
+    fn read_events(&self) -> bool {
+        let mut buffer = [0u8; 4096];
+        let mut changed = false;
+        loop {
+            let length = unsafe { libc::read(self.inotify.as_raw_fd(), buffer.as_mut_ptr() as *mut libc::c_void, buffer.len()) };
+            if length <= 0 {
+                return changed;
+            }
+            let mut offset = 0;
+            while offset + std::mem::size_of::<libc::inotify_event>() <= length as usize {
+                let event = unsafe { std::ptr::read_unaligned(buffer.as_ptr().add(offset) as *const libc::inotify_event) };
+                let name_start = offset + std::mem::size_of::<libc::inotify_event>();
+                let name = &buffer[name_start..name_start + event.len as usize];
+                let name = OsStr::from_bytes(name.split(|b| *b == 0).next().unwrap_or_default());
+                changed |= self.watches.iter().any(|w| w.descriptor == event.wd && w.concerns(name));
+                offset = name_start + event.len as usize;
+            }
+        }
+    }
+}
+
+impl Watch {
+    fn concerns(&self, name : &OsStr) -> bool {
+        self.names.iter().any(|n| n == name) || self.config_files && crate::config::ConfigFormat::from_extension(Path::new(name)).is_some()
+    }
+}
+
+enum Wait {
+    Changed,
+    /// Something happened that doesn't concern the watched files.
+    Other,
+    Timeout,
+    Stopped,
+}
+
+fn new_inotify() -> std::io::Result<OwnedFd> {
+    new_fd(unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) })
+}
+
+fn new_fd(fd : libc::c_int) -> std::io::Result<OwnedFd> {
+    if fd < 0 {
+        Err(std::io::Error::last_os_error())
+    }
+    else {
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+}
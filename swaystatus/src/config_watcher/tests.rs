@@ -0,0 +1,62 @@
+use super::*;
+
+fn make_folder(test_name : &str) -> PathBuf {
+    let folder = std::env::temp_dir().join(format!("swaystatus-{}-{}", test_name, std::process::id()));
+    std::fs::create_dir_all(&folder).unwrap();
+    folder
+}
+
+#[test]
+fn changes_to_the_file_are_noticed() {
+    let folder = make_folder("watch-change");
+    let config = folder.join("config");
+    std::fs::write(&config, "a").unwrap();
+    let watcher = ConfigWatcher::new(std::slice::from_ref(&config), &[]).unwrap();
+    let writer = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        std::fs::write(folder.join("unrelated"), "b").unwrap();
+        //Saved the way most editors do it.
+        std::fs::write(folder.join("config.new"), "c").unwrap();
+        std::fs::rename(folder.join("config.new"), &config).unwrap();
+        folder
+    });
+    assert!(watcher.wait_for_change());
+    std::fs::remove_dir_all(writer.join().unwrap()).unwrap();
+}
+
+#[test]
+fn stopping_ends_the_wait() {
+    let folder = make_folder("watch-stop");
+    let config = folder.join("config");
+    std::fs::write(&config, "a").unwrap();
+    let watcher = ConfigWatcher::new(&[config], &[]).unwrap();
+    let stopper = watcher.stopper().unwrap();
+    let stopping = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        stopper.stop();
+    });
+    assert!(!watcher.wait_for_change());
+    stopping.join().unwrap();
+    std::fs::remove_dir_all(folder).unwrap();
+}
+
+#[test]
+fn new_drop_ins_are_noticed() {
+    let folder = make_folder("watch-drop-in");
+    let config = folder.join("config");
+    let drop_ins = folder.join("config.d");
+    std::fs::write(&config, "a").unwrap();
+    //The folder doesn't exist yet, creating it is a change as well.
+    let mut watcher = ConfigWatcher::new(std::slice::from_ref(&config), std::slice::from_ref(&drop_ins)).unwrap();
+    std::fs::create_dir(&drop_ins).unwrap();
+    assert!(watcher.wait_for_change());
+    watcher.watch(std::slice::from_ref(&config), std::slice::from_ref(&drop_ins)).unwrap();
+    let writer = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        std::fs::write(drop_ins.join("editor.swp"), "b").unwrap();
+        std::fs::write(drop_ins.join("alsa.yaml"), "c").unwrap();
+    });
+    assert!(watcher.wait_for_change());
+    writer.join().unwrap();
+    std::fs::remove_dir_all(folder).unwrap();
+}
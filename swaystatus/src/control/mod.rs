@@ -0,0 +1,250 @@
+//! The control socket, and its client `swaystatus ctl`.
+//! Every running swaystatus listens on a Unix domain socket in `$XDG_RUNTIME_DIR/swaystatus/`,
+//! named after its process id. A client connects, writes one command as a line, and reads the
+//! reply until the connection is closed. The first line of the reply is either `ok`, followed by
+//! the command's output, one line each, or `error` followed by a description of the problem.
+//!
+//! The commands are `refresh [element]`, `reload`, `quit`, `dump` and `message <element> <text>`.
+//! Elements are addressed like in the i3bar output: by their name, or by their position on the
+//! bar if they don't have one.
+
+use std::io::{BufRead, BufReader, Read};
+use std::os::unix::fs::DirBuilderExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+use gettextrs::*;
+use crate::communication::{CurrentMainSender, InternalMessage, Message, send_without_sigpipe};
+
+#[cfg(test)]
+mod tests;
+
+/// How long either side waits for the other one.
+const TIMEOUT : Duration = Duration::from_secs(2);
+/// Commands are short. Anything longer is cut off, so a client can't make us read forever.
+const MAX_COMMAND_LENGTH : u64 = 4096;
+
+/// A command sent to the control socket.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Refreshes the given element, or all of them.
+    Refresh(Option<String>),
+    Reload,
+    Quit,
+    /// Replies with the current text of each element.
+    Dump,
+    /// Passes a text to an element, see `MsgMainToModule::send_custom_message`.
+    Message { element : String, text : String },
+}
+
+/// The commands that need the elements. The core loop answers them.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Request {
+    Refresh(String),
+    Dump,
+    Message { element : String, text : String },
+}
+
+/// The output lines of a command, or what went wrong.
+pub type Reply = Result<Vec<String>, String>;
+
+pub fn parse_command(line : &str) -> Result<Command, String> {
+    let mut words = line.trim().splitn(2, char::is_whitespace);
+    let command = words.next().unwrap_or_default();
+    let argument = words.next().map(str::trim_start).filter(|a| !a.is_empty());
+    match (command, argument) {
+        ("refresh", None) => Ok(Command::Refresh(None)),
+        ("refresh", Some(element)) if !element.contains(char::is_whitespace) => Ok(Command::Refresh(Some(String::from(element)))),
+        ("reload", None) => Ok(Command::Reload),
+        ("quit", None) => Ok(Command::Quit),
+        ("dump", None) => Ok(Command::Dump),
+        ("message", Some(argument)) => match argument.split_once(char::is_whitespace) {
+            Some((element, text)) if !text.trim().is_empty() => Ok(Command::Message { element : String::from(element), text : String::from(text.trim()) }),
+            _ => Err(gettext("The message command needs an element and a text."))
+        },
+        ("refresh" | "reload" | "quit" | "dump", Some(_)) => Err(gettext!("Too many arguments for the {} command.", command)),
+        _ => Err(gettext!("Unknown command \"{}\". Known commands are refresh, reload, quit, dump and message.", command))
+    }
+}
+
+/// Turns a reply into the text sent over the socket.
+pub fn format_reply(reply : &Reply) -> String {
+    match reply {
+        Ok(lines) => std::iter::once("ok").chain(lines.iter().map(String::as_str)).map(|l| format!("{}\n", l)).collect(),
+        Err(e) => format!("error {}\n", e.replace('\n', " "))
+    }
+}
+
+/// The counterpart of `format_reply`. Returns None if the text isn't a reply.
+pub fn parse_reply(text : &str) -> Option<Reply> {
+    let mut lines = text.lines();
+    match lines.next()? {
+        "ok" => Some(Ok(lines.map(String::from).collect())),
+        status => status.strip_prefix("error ").map(|e| Err(String::from(e)))
+    }
+}
+
+/// The folder with the sockets of all running instances.
+fn socket_folder() -> Option<PathBuf> {
+    dirs::runtime_dir().map(|d| d.join("swaystatus"))
+}
+
+/// Owns the socket and the thread that listens on it. Like the click event reader, the thread is
+/// started once and keeps running across reloads, so a reload can be triggered through it. The
+/// socket file is removed once this is dropped.
+#[derive(Default)]
+pub struct ControlSocket {
+    target : CurrentMainSender,
+    path : Option<PathBuf>,
+}
+
+impl ControlSocket {
+    /// Creates the socket and starts the listener thread, unless that's already done.
+    pub fn ensure_running(&mut self) {
+        if self.path.is_some() {
+            return;
+        }
+        let Some(folder) = socket_folder() else {
+            eprintln!("{}", gettext("XDG_RUNTIME_DIR is not set, so there is no control socket. swaystatus ctl won't work."));
+            return;
+        };
+        let path = folder.join(format!("{}.sock", std::process::id()));
+        let target = self.target.clone();
+        let result = bind(&folder, &path).and_then(|listener| std::thread::Builder::new().name(String::from("control socket")).spawn(move || {
+            serve(listener, &target);
+        }));
+        match result {
+            Ok(_) => self.path = Some(path),
+            Err(e) => eprintln!("{}", gettext!("Failed to create the control socket {}. swaystatus ctl won't work. The error was: {}", path.display(), e))
+        }
+    }
+    /// Gives access to the sender the listener thread uses, so the core loop can connect to it.
+    pub fn target(&self) -> &CurrentMainSender {
+        &self.target
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+fn bind(folder : &Path, path : &Path) -> std::io::Result<UnixListener> {
+    std::fs::DirBuilder::new().recursive(true).mode(0o700).create(folder)?;
+    //Left behind by a process with the same id, or by ourselves before re-executing.
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+        _ => ()
+    }
+    UnixListener::bind(path)
+}
+
+fn serve(listener : UnixListener, target : &CurrentMainSender) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => handle_connection(&stream, target),
+            Err(e) => eprintln!("{}", gettext!("Failed to accept a connection on the control socket: {}", e))
+        }
+    }
+}
+
+/// Connections are handled one after the other. The timeouts make sure a client that doesn't
+/// send its command can't block the others for long.
+fn handle_connection(stream : &UnixStream, target : &CurrentMainSender) {
+    let _ = stream.set_read_timeout(Some(TIMEOUT));
+    let mut line = String::new();
+    let reply = match BufReader::new(stream.take(MAX_COMMAND_LENGTH)).read_line(&mut line) {
+        Ok(_) => parse_command(&line).and_then(|command| execute(command, target)),
+        Err(e) => Err(gettext!("Failed to read the command: {}", e))
+    };
+    //Writing fails only if the client is gone, and then nobody cares about the reply.
+    let _ = send_without_sigpipe(stream, format_reply(&reply).as_bytes());
+}
+
+fn execute(command : Command, target : &CurrentMainSender) -> Reply {
+    let request = match command {
+        Command::Refresh(None) => return send_internal(target, InternalMessage::Refresh),
+        Command::Reload => return send_internal(target, InternalMessage::Reload),
+        Command::Quit => return send_internal(target, InternalMessage::Quit),
+        Command::Refresh(Some(element)) => Request::Refresh(element),
+        Command::Dump => Request::Dump,
+        Command::Message { element, text } => Request::Message { element, text },
+    };
+    let (reply, answer) = mpsc::channel();
+    if !target.send(Message::Control { request, reply }) {
+        return Err(not_ready());
+    }
+    answer.recv_timeout(TIMEOUT).unwrap_or_else(|_| Err(gettext("swaystatus didn't answer in time.")))
+}
+
+fn send_internal(target : &CurrentMainSender, message : InternalMessage) -> Reply {
+    if target.send(Message::Internal(message)) { Ok(Vec::new()) } else { Err(not_ready()) }
+}
+
+/// Between the core loops of a restart, nobody is connected for a moment.
+fn not_ready() -> String {
+    gettext("swaystatus is restarting. Please try again.")
+}
+
+/// Sends the command to the instance listening on the given socket, or to all running instances,
+/// and prints their replies. Returns the exit code: 0 if all of them succeeded.
+pub fn run_client(socket : Option<&Path>, command : &str) -> i32 {
+    if let Err(e) = parse_command(command) {
+        eprintln!("{}", e);
+        return 2;
+    }
+    let sockets = match socket {
+        Some(socket) => vec![socket.to_path_buf()],
+        None => running_instances()
+    };
+    let mut reached = 0;
+    let mut exit_code = 0;
+    for path in sockets {
+        match send_command(&path, command) {
+            Ok(Ok(lines)) => lines.iter().for_each(|l| println!("{}", l)),
+            Ok(Err(e)) => {
+                eprintln!("{}", e);
+                exit_code = 1;
+            },
+            //The process that created it is gone.
+            Err(e) if socket.is_none() && e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                let _ = std::fs::remove_file(&path);
+                continue;
+            },
+            Err(e) => {
+                eprintln!("{}", gettext!("Failed to send the command to {}: {}", path.display(), e));
+                exit_code = 1;
+            }
+        }
+        reached += 1;
+    }
+    if reached == 0 {
+        eprintln!("{}", gettext("No running swaystatus found."));
+        return 1;
+    }
+    exit_code
+}
+
+/// The sockets in the socket folder, sorted, so the output of several instances has a stable order.
+fn running_instances() -> Vec<PathBuf> {
+    let mut sockets : Vec<PathBuf> = socket_folder().and_then(|f| std::fs::read_dir(f).ok()).into_iter().flatten()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e == "sock"))
+        .collect();
+    sockets.sort();
+    sockets
+}
+
+fn send_command(path : &Path, command : &str) -> std::io::Result<Reply> {
+    let mut stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(TIMEOUT + TIMEOUT))?;
+    send_without_sigpipe(&stream, format!("{}\n", command.trim()).as_bytes())?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+    let mut text = String::new();
+    stream.read_to_string(&mut text)?;
+    parse_reply(&text).ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, gettext("The reply could not be understood.")))
+}
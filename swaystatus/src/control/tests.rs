@@ -0,0 +1,58 @@
+use super::*;
+
+#[test]
+fn commands_are_parsed() {
+    assert_eq!(parse_command("refresh\n"), Ok(Command::Refresh(None)));
+    assert_eq!(parse_command(" refresh  volume "), Ok(Command::Refresh(Some(String::from("volume")))));
+    assert_eq!(parse_command("reload"), Ok(Command::Reload));
+    assert_eq!(parse_command("quit"), Ok(Command::Quit));
+    assert_eq!(parse_command("dump"), Ok(Command::Dump));
+    assert_eq!(parse_command("message volume  set 50%"), Ok(Command::Message { element : String::from("volume"), text : String::from("set 50%") }));
+    assert!(parse_command("message volume").is_err());
+    assert!(parse_command("refresh a b").is_err());
+    assert!(parse_command("quit now").is_err());
+    assert!(parse_command("restart").is_err());
+    assert!(parse_command("").is_err());
+}
+
+#[test]
+fn replies_survive_the_socket() {
+    let replies : [Reply; 3] = [Ok(Vec::new()), Ok(vec![String::from("clock\t12:00"), String::from("1\t")]), Err(String::from("no"))];
+    for reply in replies {
+        assert_eq!(parse_reply(&format_reply(&reply)), Some(reply));
+    }
+    assert_eq!(parse_reply(&format_reply(&Err(String::from("two\nlines")))), Some(Err(String::from("two lines"))));
+    assert_eq!(parse_reply("hello\n"), None);
+    assert_eq!(parse_reply(""), None);
+}
+
+#[test]
+fn commands_reach_the_core_loop() {
+    let folder = std::env::temp_dir().join(format!("swaystatus-control-{}", std::process::id()));
+    let path = folder.join("test.sock");
+    let listener = bind(&folder, &path).unwrap();
+    let target = CurrentMainSender::default();
+    let (sender, receiver) = mpsc::channel();
+    target.connect(Some(sender));
+    //The listener thread never ends, it's left behind when the test is over.
+    std::thread::spawn(move || serve(listener, &target));
+    let core_loop = std::thread::spawn(move || {
+        let mut received = Vec::new();
+        for _ in 0..2 {
+            match receiver.recv().unwrap() {
+                Message::Control { request, reply } => {
+                    reply.send(Ok(vec![String::from("clock\t12:00")])).unwrap();
+                    received.push(format!("{:?}", request));
+                },
+                Message::Internal(InternalMessage::Reload) => received.push(String::from("reload")),
+                _ => received.push(String::from("other"))
+            }
+        }
+        received
+    });
+    assert_eq!(send_command(&path, "dump").unwrap(), Ok(vec![String::from("clock\t12:00")]));
+    assert_eq!(send_command(&path, "reload").unwrap(), Ok(Vec::new()));
+    assert!(send_command(&path, "jump").unwrap().is_err());
+    assert_eq!(core_loop.join().unwrap(), ["Dump", "reload"]);
+    std::fs::remove_dir_all(folder).unwrap();
+}
@@ -0,0 +1,366 @@
+//! The built-in "Exec" plugin. Runs an external command, and shows what it prints.
+//! This allows to write elements in any language, without the need to build a plugin library
+//! with the exact same compiler version as the main program. The command's stdout is read line by
+//! line, every line replaces the element's text. Refresh and click events can be written to the
+//! command's stdin, or delivered as signals. If the command exits, it is started again after a
+//! delay, so a command that prints a single line and exits is simply run periodically.
+
+use std::io::{BufRead, BufReader};
+use std::os::unix::io::OwnedFd;
+use std::os::unix::net::UnixStream;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex, mpsc};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use serde::{Serialize, Deserialize};
+use gettextrs::*;
+use crate::plugin;
+use crate::communication::send_without_sigpipe;
+
+#[cfg(test)]
+mod tests;
+
+pub struct ExecPlugin;
+
+impl ExecPlugin {
+    pub const FEATURES : plugin::PluginFeatures = plugin::PluginFeatures::CLICK_EVENTS.union(plugin::PluginFeatures::STRUCTURED_UPDATES);
+}
+
+impl plugin::SwayStatusModule for ExecPlugin {
+    fn get_name(&self) -> &str {
+        "Exec"
+    }
+    fn deserialize_config<'de>(&self, deserializer : &mut (dyn erased_serde::Deserializer + 'de)) -> Result<Box<dyn plugin::SwayStatusModuleInstance>, erased_serde::Error> {
+        let result : ExecConfig = erased_serde::deserialize(deserializer)?;
+        Ok(Box::new(result))
+    }
+    fn get_default_config(&self) -> Box<dyn plugin::SwayStatusModuleInstance> {
+        Box::new(ExecConfig::default())
+    }
+    fn print_help(&self) {
+        println!("{}", gettext(
+r##"Built-in plugin that runs an external command.
+
+Every line the command writes to stdout becomes the new text of the element. If the command exits, it is started again after a delay. A command that prints one line and exits is therefore run periodically.
+
+[Element.Config]
+Command = <list of the program and its arguments, for instance ["date", "+%R"]>
+Format = <"Lines" if every line of output is the text to show, or "Json" if every line is a JSON object with the fields of an i3bar block, for instance {"full_text":"Hi","color":"#FF0000"}. The "state" field accepts "Idle", "Info", "Good", "Warning" or "Critical".>
+Input = <what is written to the command's stdin on refresh, click, or a message sent with swaystatus ctl: "None", "Lines" (the word "refresh", "click" followed by the X11 button number and the click position relative to the element, or "message" followed by the message), or "Json" (one JSON object per event)>
+RefreshSignal = <optional, one of "HUP", "INT", "TERM", "KILL", "USR1", "USR2". If set, a refresh sends this signal to the command instead of writing to stdin.>
+ClickSignal = <optional, like RefreshSignal, but for clicks.>
+QuitSignal = <the signal sent to the command and all processes it started when swaystatus quits. Defaults to "TERM".>
+RestartDelayMs = <how long to wait before starting the command again after it exited. Defaults to 1000.>"##));
+    }
+    fn get_config_schema(&self) -> Option<String> {
+        let signal = serde_json::json!({ "enum" : ["HUP", "INT", "TERM", "KILL", "USR1", "USR2"] });
+        Some(serde_json::json!({
+            "type" : "object",
+            "properties" : {
+                "Command" : { "type" : "array", "items" : { "type" : "string" }, "minItems" : 1, "description" : "The program and its arguments." },
+                "Format" : { "enum" : ["Lines", "Json"], "description" : "If each line of output is the text to show, or an i3bar block as JSON." },
+                "Input" : { "enum" : ["None", "Lines", "Json"], "description" : "What is written to the command's stdin on refresh, click, or a message." },
+                "RefreshSignal" : signal,
+                "ClickSignal" : signal,
+                "QuitSignal" : signal,
+                "RestartDelayMs" : { "type" : "integer", "minimum" : 0 }
+            },
+            "required" : ["Command"],
+            "additionalProperties" : false
+        }).to_string())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecOutputFormat {
+    Lines,
+    Json
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecInputFormat {
+    None,
+    Lines,
+    Json
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ExecSignal {
+    Hup,
+    Int,
+    Term,
+    Kill,
+    Usr1,
+    Usr2
+}
+
+impl ExecSignal {
+    fn number(self) -> libc::c_int {
+        match self {
+            ExecSignal::Hup => libc::SIGHUP,
+            ExecSignal::Int => libc::SIGINT,
+            ExecSignal::Term => libc::SIGTERM,
+            ExecSignal::Kill => libc::SIGKILL,
+            ExecSignal::Usr1 => libc::SIGUSR1,
+            ExecSignal::Usr2 => libc::SIGUSR2,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "PascalCase")]
+pub struct ExecConfig {
+    command : Vec<String>,
+    #[serde(default = "ExecConfig::default_format")]
+    format : ExecOutputFormat,
+    #[serde(default = "ExecConfig::default_input")]
+    input : ExecInputFormat,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    refresh_signal : Option<ExecSignal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    click_signal : Option<ExecSignal>,
+    #[serde(default = "ExecConfig::default_quit_signal")]
+    quit_signal : ExecSignal,
+    #[serde(default = "ExecConfig::default_restart_delay_ms")]
+    restart_delay_ms : u64,
+}
+
+impl ExecConfig {
+    fn default_format() -> ExecOutputFormat { ExecOutputFormat::Lines }
+    fn default_input() -> ExecInputFormat { ExecInputFormat::None }
+    fn default_quit_signal() -> ExecSignal { ExecSignal::Term }
+    fn default_restart_delay_ms() -> u64 { 1000 }
+}
+
+impl Default for ExecConfig {
+    fn default() -> Self {
+        ExecConfig {
+            command : vec![String::from("date"), String::from("+%R")],
+            format : ExecConfig::default_format(),
+            input : ExecConfig::default_input(),
+            refresh_signal : None,
+            click_signal : None,
+            quit_signal : ExecConfig::default_quit_signal(),
+            restart_delay_ms : ExecConfig::default_restart_delay_ms(),
+        }
+    }
+}
+
+impl plugin::SwayStatusModuleInstance for ExecConfig {
+    fn make_runnable<'p>(&'p self, to_main : Box<dyn plugin::MsgModuleToMain + 'p>) -> (Box<dyn plugin::SwayStatusModuleRunnable + 'p>, Box<dyn plugin::MsgMainToModule + 'p>) {
+        let shared = Arc::new(Shared { quit : AtomicBool::new(false), child : Mutex::new(None) });
+        let (wake_sender, wake_receiver) = mpsc::channel();
+        (
+            Box::new(ExecRunnable { config : self, to_main, shared : shared.clone(), wake : wake_receiver }),
+            Box::new(ExecSender { config : self, shared, wake : wake_sender })
+        )
+    }
+}
+
+/// The command's process, as far as the sender needs to know it.
+struct RunningChild {
+    pid : libc::pid_t,
+    /// A socket, not a pipe. See `send_without_sigpipe()` for the reason.
+    stdin : Option<UnixStream>,
+}
+
+struct Shared {
+    quit : AtomicBool,
+    child : Mutex<Option<RunningChild>>,
+}
+
+impl Shared {
+    fn lock_child(&self) -> std::sync::MutexGuard<'_, Option<RunningChild>> {
+        self.child.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Sent to the runnable while it waits to restart the command.
+enum Wake {
+    Quit,
+    Refresh
+}
+
+/// Events for the command's stdin.
+#[derive(Serialize)]
+enum ExecInput<'a> {
+    Refresh,
+    Click(&'a plugin::ClickEvent),
+    Message(&'a str),
+}
+
+fn format_input(format : ExecInputFormat, input : &ExecInput) -> Option<String> {
+    match format {
+        ExecInputFormat::None => None,
+        ExecInputFormat::Lines => Some(match input {
+            ExecInput::Refresh => String::from("refresh\n"),
+            ExecInput::Click(e) => format!("click {} {} {}\n", u32::from(e.button), e.relative_x, e.relative_y),
+            ExecInput::Message(m) => format!("message {}\n", m.replace('\n', " "))
+        }),
+        ExecInputFormat::Json => serde_json::to_string(input).ok().map(|s| s + "\n")
+    }
+}
+
+fn parse_output_line(format : ExecOutputFormat, line : &str) -> Result<plugin::StatusBlock, serde_json::Error> {
+    match format {
+        ExecOutputFormat::Lines => Ok(plugin::StatusBlock::from(String::from(line))),
+        ExecOutputFormat::Json => serde_json::from_str(line)
+    }
+}
+
+struct ExecRunnable<'p> {
+    config : &'p ExecConfig,
+    to_main : Box<dyn plugin::MsgModuleToMain + 'p>,
+    shared : Arc<Shared>,
+    wake : mpsc::Receiver<Wake>,
+}
+
+impl<'p> ExecRunnable<'p> {
+    /// Runs the command once. Returns false if main isn't listening any more.
+    fn run_command_once(&self) -> bool {
+        let (program, arguments) = match self.config.command.split_first() {
+            Some(x) => x,
+            None => {
+                return self.to_main.send_update(Err(plugin::PluginError::ShowInsteadOfText(gettext("No command configured")))).is_ok();
+            }
+        };
+        let (ours, theirs) = match UnixStream::pair() {
+            Ok(x) => x,
+            Err(e) => return self.report_error(&e)
+        };
+        let child = Command::new(program)
+            .args(arguments)
+            .stdin(Stdio::from(OwnedFd::from(theirs)))
+            .stdout(Stdio::piped())
+            //A process group of its own, so quitting also ends what the command started.
+            .process_group(0)
+            .spawn();
+        let mut child = match child {
+            Ok(c) => c,
+            Err(e) => return self.report_error(&e)
+        };
+        *self.shared.lock_child() = Some(RunningChild { pid : child.id() as libc::pid_t, stdin : Some(ours) });
+        if self.shared.quit.load(Ordering::SeqCst) {
+            ExecSender::stop_child(&self.shared, self.config.quit_signal);
+        }
+
+        let mut main_listening = true;
+        if let Some(stdout) = child.stdout.take() {
+            for line in BufReader::new(stdout).lines() {
+                let line = match line {
+                    Ok(l) => l,
+                    Err(_) => break
+                };
+                let result = match parse_output_line(self.config.format, &line) {
+                    Ok(block) => self.to_main.send_block_update(Ok(block)),
+                    Err(e) => self.to_main.send_update(Err(plugin::PluginError::PrintToStdErr(gettext!("The command printed invalid JSON: {}", e))))
+                };
+                if result.is_err() {
+                    main_listening = false;
+                    ExecSender::stop_child(&self.shared, self.config.quit_signal);
+                    break;
+                }
+            }
+        }
+        //Forget the pid before reaping the process, so no signal can hit a recycled pid.
+        *self.shared.lock_child() = None;
+        match child.wait() {
+            Ok(status) if !status.success() && !self.shared.quit.load(Ordering::SeqCst) => {
+                main_listening &= self.to_main.send_update(Err(plugin::PluginError::PrintToStdErr(gettext!("The command {} exited: {}", program, status)))).is_ok();
+            },
+            Ok(_) => {},
+            Err(e) => {
+                main_listening &= self.report_error(&e);
+            }
+        }
+        main_listening
+    }
+
+    fn report_error(&self, e : &std::io::Error) -> bool {
+        self.to_main.send_update(Err(plugin::PluginError::PrintToStdErr(gettext!("Failed to run the command {}: {}", self.config.command.join(" "), e)))).is_ok()
+            && self.to_main.send_update(Err(plugin::PluginError::ShowInsteadOfText(gettext("<command failed>")))).is_ok()
+    }
+}
+
+impl<'p> plugin::SwayStatusModuleRunnable for ExecRunnable<'p> {
+    fn run(&self) {
+        while !self.shared.quit.load(Ordering::SeqCst) {
+            if !self.run_command_once() {
+                return;
+            }
+            if self.shared.quit.load(Ordering::SeqCst) {
+                return;
+            }
+            match self.wake.recv_timeout(Duration::from_millis(self.config.restart_delay_ms)) {
+                Ok(Wake::Quit) | Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                Ok(Wake::Refresh) | Err(mpsc::RecvTimeoutError::Timeout) => {}
+            }
+        }
+    }
+}
+
+struct ExecSender<'p> {
+    config : &'p ExecConfig,
+    shared : Arc<Shared>,
+    wake : mpsc::Sender<Wake>,
+}
+
+impl<'p> ExecSender<'p> {
+    /// Closes the command's stdin, and sends the quit signal to its process group. A shell script
+    /// would otherwise leave the programs it started behind, and those keep its stdout open.
+    fn stop_child(shared : &Shared, signal : ExecSignal) {
+        if let Some(child) = shared.lock_child().as_mut() {
+            child.stdin = None;
+            //The group has the id of the command, which isn't reaped yet.
+            unsafe { libc::kill(-child.pid, signal.number()); }
+        }
+    }
+
+    /// Delivers an event to the running command. Returns false if no command is running.
+    fn deliver(&self, signal : Option<ExecSignal>, input : &ExecInput) -> bool {
+        let mut child = self.shared.lock_child();
+        let child = match child.as_mut() {
+            Some(c) => c,
+            None => return false
+        };
+        match (signal, format_input(self.config.input, input), &child.stdin) {
+            (Some(signal), _, _) => unsafe { libc::kill(child.pid, signal.number()); },
+            (None, Some(text), Some(stdin)) => {
+                //If the command doesn't read its input any more, that's its problem.
+                let _ = send_without_sigpipe(stdin, text.as_bytes());
+            },
+            _ => {}
+        }
+        true
+    }
+}
+
+impl<'p> plugin::MsgMainToModule for ExecSender<'p> {
+    fn send_quit(&self) -> Result<(),plugin::PluginCommunicationError> {
+        self.shared.quit.store(true, Ordering::SeqCst);
+        ExecSender::stop_child(&self.shared, self.config.quit_signal);
+        //If the runnable is gone already, it has seen the quit flag once the command ended.
+        let _ = self.wake.send(Wake::Quit);
+        Ok(())
+    }
+    fn send_refresh(&self) -> Result<(),plugin::PluginCommunicationError> {
+        if self.deliver(self.config.refresh_signal, &ExecInput::Refresh) {
+            Ok(())
+        }
+        else {
+            //Not running at the moment. Refreshing means to start it right now.
+            self.wake.send(Wake::Refresh).map_err(|_| plugin::PluginCommunicationError)
+        }
+    }
+    fn send_click_event(&self, event : &plugin::ClickEvent) -> Result<(),plugin::PluginCommunicationError> {
+        self.deliver(self.config.click_signal, &ExecInput::Click(event));
+        Ok(())
+    }
+    fn send_custom_message(&self, message : &str) -> Result<(),plugin::PluginCommunicationError> {
+        self.deliver(None, &ExecInput::Message(message));
+        Ok(())
+    }
+}
+
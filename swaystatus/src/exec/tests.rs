@@ -0,0 +1,112 @@
+use super::*;
+use plugin::SwayStatusModuleInstance;
+
+struct CollectingSender {
+    blocks : Arc<Mutex<Vec<Result<plugin::StatusBlock, plugin::PluginError>>>>,
+}
+
+impl plugin::MsgModuleToMain for CollectingSender {
+    fn send_block_update(&self, block : Result<plugin::StatusBlock, plugin::PluginError>) -> Result<(),plugin::PluginCommunicationError> {
+        self.blocks.lock().unwrap().push(block);
+        Ok(())
+    }
+}
+
+fn config_from_toml(text : &str) -> ExecConfig {
+    toml::from_str(text).unwrap()
+}
+
+#[test]
+fn config_defaults() {
+    let config = config_from_toml("Command = [\"true\"]");
+    assert_eq!(config.format, ExecOutputFormat::Lines);
+    assert_eq!(config.input, ExecInputFormat::None);
+    assert_eq!(config.refresh_signal, None);
+    assert_eq!(config.quit_signal, ExecSignal::Term);
+    assert_eq!(config.restart_delay_ms, 1000);
+}
+
+#[test]
+fn config_with_signals() {
+    let config = config_from_toml("Command = [\"true\"]\nFormat = \"Json\"\nRefreshSignal = \"USR1\"\nQuitSignal = \"INT\"");
+    assert_eq!(config.format, ExecOutputFormat::Json);
+    assert_eq!(config.refresh_signal, Some(ExecSignal::Usr1));
+    assert_eq!(config.quit_signal, ExecSignal::Int);
+    assert!(toml::from_str::<ExecConfig>("Command = [\"true\"]\nRefreshSignal = \"STOP\"").is_err());
+}
+
+#[test]
+fn input_is_formatted() {
+    let event = plugin::ClickEvent { button : plugin::MouseButton::Right, modifiers : Vec::new(), x : 1, y : 2, relative_x : 3, relative_y : 4, width : 5, height : 6 };
+    assert_eq!(format_input(ExecInputFormat::None, &ExecInput::Refresh), None);
+    assert_eq!(format_input(ExecInputFormat::Lines, &ExecInput::Refresh).unwrap(), "refresh\n");
+    assert_eq!(format_input(ExecInputFormat::Lines, &ExecInput::Click(&event)).unwrap(), "click 3 3 4\n");
+    assert_eq!(format_input(ExecInputFormat::Json, &ExecInput::Refresh).unwrap(), "\"Refresh\"\n");
+    let json = format_input(ExecInputFormat::Json, &ExecInput::Click(&event)).unwrap();
+    let parsed : serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed["Click"]["relative_x"], 3);
+    assert_eq!(format_input(ExecInputFormat::Lines, &ExecInput::Message("mute\nnow")).unwrap(), "message mute now\n");
+    assert_eq!(format_input(ExecInputFormat::Json, &ExecInput::Message("mute")).unwrap(), "{\"Message\":\"mute\"}\n");
+}
+
+#[test]
+fn output_is_parsed() {
+    assert_eq!(parse_output_line(ExecOutputFormat::Lines, "{ hello }").unwrap().full_text, "{ hello }");
+    let block = parse_output_line(ExecOutputFormat::Json, r#"{"full_text":"hi","state":"Warning"}"#).unwrap();
+    assert_eq!(block.full_text, "hi");
+    assert_eq!(block.state, plugin::BlockState::Warning);
+    assert!(parse_output_line(ExecOutputFormat::Json, "hi").is_err());
+}
+
+#[test]
+fn i3bar_blocks_are_parsed() {
+    let line = r##"{"name":"vol","instance":"0","full_text":"<b>50%</b>","short_text":"50","color":"#ff0000","urgent":true,"markup":"pango","min_width":100,"align":"center","separator":false,"separator_block_width":9}"##;
+    let block = parse_output_line(ExecOutputFormat::Json, line).unwrap();
+    assert_eq!(block, plugin::StatusBlock {
+        full_text : String::from("<b>50%</b>"),
+        short_text : Some(String::from("50")),
+        color : Some(String::from("#ff0000")),
+        urgent : true,
+        markup : true,
+        min_width : Some(plugin::MinWidth::Pixels(100)),
+        align : Some(plugin::Alignment::Center),
+        ..Default::default()
+    });
+    let block = parse_output_line(ExecOutputFormat::Json, r#"{"full_text":"a","markup":"none","min_width":"100%","align":"right"}"#).unwrap();
+    assert!(!block.markup);
+    assert_eq!(block.min_width, Some(plugin::MinWidth::Text(String::from("100%"))));
+    assert_eq!(block.align, Some(plugin::Alignment::Right));
+}
+
+/// Runs the command until it printed the given number of lines, then quits it. Panics if either
+/// takes too long, instead of hanging the test.
+fn run_until_lines(command : &str, lines : usize) -> Vec<String> {
+    let config : &'static ExecConfig = Box::leak(Box::new(config_from_toml(&format!("Command = [\"sh\", \"-c\", \"{}\"]\nRestartDelayMs = 60000", command))));
+    let blocks = Arc::new(Mutex::new(Vec::new()));
+    let (runnable, sender) = config.make_runnable(Box::new(CollectingSender { blocks : blocks.clone() }));
+    let (done_sender, done) = mpsc::channel();
+    std::thread::spawn(move || {
+        runnable.run();
+        let _ = done_sender.send(());
+    });
+    let deadline = std::time::Instant::now() + Duration::from_secs(10);
+    while blocks.lock().unwrap().len() < lines {
+        assert!(std::time::Instant::now() < deadline, "the command didn't print enough lines in time");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    sender.send_quit().unwrap();
+    done.recv_timeout(Duration::from_secs(10)).expect("the element didn't quit in time");
+    let texts = blocks.lock().unwrap().iter().map(|b| b.as_ref().unwrap().full_text.clone()).collect();
+    texts
+}
+
+#[test]
+fn every_line_becomes_an_update() {
+    assert_eq!(run_until_lines("echo a; echo b", 2), vec!["a", "b"]);
+}
+
+#[test]
+fn quitting_stops_what_the_command_started() {
+    //Without the process group, sleep would keep stdout open for a minute after sh is gone.
+    assert_eq!(run_until_lines("echo started; sleep 60; echo done", 1), vec!["started"]);
+}
@@ -0,0 +1,104 @@
+//! Reads click events that i3bar/swaybar write to our stdin.
+//! The protocol is the counterpart of the one in the output module: An infinite JSON array, with
+//! one click event object per line (see swaybar-protocol(7)).
+
+use std::io::BufRead;
+use serde::Deserialize;
+use gettextrs::*;
+use crate::communication::{CurrentMainSender, Message};
+use crate::plugin;
+
+#[cfg(test)]
+mod tests;
+
+/// A click event as sent by the status bar. Sway and i3 don't agree on all fields, so everything
+/// we don't strictly need is optional.
+#[derive(Deserialize)]
+struct I3barClickEvent {
+    name : Option<String>,
+    instance : Option<String>,
+    button : u32,
+    #[serde(default)]
+    modifiers : Vec<String>,
+    #[serde(default)]
+    x : i32,
+    #[serde(default)]
+    y : i32,
+    #[serde(default)]
+    relative_x : i32,
+    #[serde(default)]
+    relative_y : i32,
+    #[serde(default)]
+    width : i32,
+    #[serde(default)]
+    height : i32,
+}
+
+/// Owns the thread that reads stdin. The thread is only started once the output format requires
+/// it, and then keeps running until stdin is closed, even across reloads. That's because a
+/// thread that's blocked reading stdin can't be told to stop.
+#[derive(Default)]
+pub struct ClickEventReader {
+    target : CurrentMainSender,
+    started : bool,
+}
+
+impl ClickEventReader {
+    /// Starts the reader thread, unless it's already running.
+    pub fn ensure_running(&mut self) {
+        if self.started {
+            return;
+        }
+        let target = self.target.clone();
+        let spawn_result = std::thread::Builder::new().name(String::from("click events")).spawn(move || {
+            read_click_events(std::io::stdin().lock(), &target);
+        });
+        match spawn_result {
+            Ok(_) => self.started = true,
+            Err(e) => eprintln!("{}", gettext!("Failed to start the thread that reads click events. Clicks will be ignored. The error was: {}", e))
+        }
+    }
+    /// Gives access to the sender the reader thread uses, so the core loop can connect to it.
+    pub fn target(&self) -> &CurrentMainSender {
+        &self.target
+    }
+}
+
+fn read_click_events<R : BufRead>(input : R, target : &CurrentMainSender) {
+    for line in input.lines() {
+        match line {
+            Ok(line) => {
+                if let Some(message) = parse_click_event_line(&line) {
+                    target.send(message);
+                }
+            },
+            Err(e) => {
+                eprintln!("{}", gettext!("Failed to read click events from stdin: {}", e));
+                return;
+            }
+        }
+    }
+}
+
+/// Parses one line of the infinite array. Returns None for lines that don't hold an event, like
+/// the opening bracket, and for events that can't be routed to an element.
+fn parse_click_event_line(line : &str) -> Option<Message> {
+    let trimmed = line.trim().trim_start_matches(['[', ',']).trim_start();
+    if trimmed.is_empty() {
+        return None;
+    }
+    match serde_json::from_str::<I3barClickEvent>(trimmed) {
+        Ok(I3barClickEvent { name : Some(name), instance : Some(instance), button, modifiers, x, y, relative_x, relative_y, width, height }) => {
+            Some(Message::Click {
+                name,
+                instance,
+                event : plugin::ClickEvent { button : button.into(), modifiers, x, y, relative_x, relative_y, width, height }
+            })
+        },
+        Ok(_) => None,
+        Err(e) => {
+            eprintln!("{}", gettext!("Received a click event that could not be parsed. The error was: {}", e));
+            None
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use super::*;
+
+#[test]
+fn opening_bracket_is_no_event() {
+    assert!(parse_click_event_line("[").is_none());
+    assert!(parse_click_event_line("").is_none());
+}
+
+#[test]
+fn parse_first_and_following_events() {
+    let first = "[{\"name\":\"ClockPlugin\",\"instance\":\"0\",\"button\":1,\"modifiers\":[\"Shift\"],\"x\":10,\"y\":2,\"relative_x\":3,\"relative_y\":2,\"width\":40,\"height\":20}";
+    let following = ",{\"name\":\"PulseVolume\",\"instance\":\"1\",\"button\":5,\"x\":10,\"y\":2}";
+    match parse_click_event_line(first) {
+        Some(Message::Click { name, instance, event }) => {
+            assert_eq!(name, "ClockPlugin");
+            assert_eq!(instance, "0");
+            assert_eq!(event.button, plugin::MouseButton::Left);
+            assert_eq!(event.modifiers, vec![String::from("Shift")]);
+            assert_eq!(event.relative_x, 3);
+            assert_eq!(event.width, 40);
+        },
+        _ => panic!("Expected a click event")
+    }
+    match parse_click_event_line(following) {
+        Some(Message::Click { name, instance, event }) => {
+            assert_eq!(name, "PulseVolume");
+            assert_eq!(instance, "1");
+            assert_eq!(event.button, plugin::MouseButton::ScrollDown);
+            assert!(event.modifiers.is_empty());
+        },
+        _ => panic!("Expected a click event")
+    }
+}
+
+#[test]
+fn event_without_instance_is_ignored() {
+    assert!(parse_click_event_line("{\"name\":\"ClockPlugin\",\"button\":1}").is_none());
+}
@@ -0,0 +1,145 @@
+//! The plugin host side of process isolation. This runs in the helper process, with the socket to
+//! the main process as stdin and stdout. Before the plugin is loaded, the socket is moved away
+//! from them, so that a plugin that prints something or reads from stdin can't disturb the
+//! messages.
+
+use std::io::{BufRead, Write};
+use std::os::fd::{AsFd, AsRawFd};
+use std::os::unix::net::UnixStream;
+use std::sync::Mutex;
+use crossbeam_utils::thread;
+use gettextrs::*;
+use crate::plugin;
+use crate::plugin_database::{Libraries, PluginDatabase};
+use super::{MainToHost, HostToMain};
+
+/// Sends the element's updates to the main process.
+struct SenderToMainProcess {
+    stream : Mutex<UnixStream>,
+}
+
+impl plugin::MsgModuleToMain for SenderToMainProcess {
+    fn send_block_update(&self, block : Result<plugin::StatusBlock, plugin::PluginError>) -> Result<(),plugin::PluginCommunicationError> {
+        let mut line = serde_json::to_vec(&HostToMain::Update(block)).map_err(|_| plugin::PluginCommunicationError)?;
+        line.push(b'\n');
+        let mut stream = self.stream.lock().unwrap_or_else(|e| e.into_inner());
+        stream.write_all(&line).map_err(|_| plugin::PluginCommunicationError)
+    }
+}
+
+/// Runs one element of the plugin in the given library. Returns the exit code of the process.
+pub fn run_plugin_host(library : &std::path::Path) -> i32 {
+    ignore_signals_for_main();
+    let stream = match take_socket_from_standard_streams() {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("{}", gettext!("The plugin host failed to set up the connection to the main process: {}", e));
+            return 1;
+        }
+    };
+    let libraries = match Libraries::load_single(library) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("{}", gettext!("The plugin host failed to load library {}: {}", library.display(), e));
+            return 1;
+        }
+    };
+    let plugins = PluginDatabase::new(&libraries);
+
+    let mut lines = std::io::BufReader::new(&stream).lines();
+    let (plugin_name, config) = match lines.next().map(|l| l.map(|l| serde_json::from_str::<MainToHost>(&l))) {
+        Some(Ok(Ok(MainToHost::Config { plugin, config }))) => (plugin, config),
+        _ => {
+            eprintln!("{}", gettext("The plugin host did not receive a valid configuration from the main process."));
+            return 1;
+        }
+    };
+    let plugin = match plugins.get_plugin(&plugin_name) {
+        Some(p) => p,
+        None => {
+            eprintln!("{}", gettext!("The plugin host could not find plugin {} in library {}.", plugin_name, library.display()));
+            return 1;
+        }
+    };
+    let instance = match plugin.deserialize_config(&mut <dyn erased_serde::Deserializer>::erase(config)) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("{}", gettext!("The plugin host failed to read the configuration of plugin {}: {}", plugin_name, e));
+            return 1;
+        }
+    };
+
+    let to_main = match stream.try_clone() {
+        Ok(stream) => SenderToMainProcess { stream : Mutex::new(stream) },
+        Err(e) => {
+            eprintln!("{}", gettext!("The plugin host failed to set up the connection to the main process: {}", e));
+            return 1;
+        }
+    };
+    let (runnable, from_main) = instance.make_runnable(Box::new(to_main));
+
+    //The runnable gets a thread, because the plugin's sender can't be passed between threads.
+    //The process ends as soon as the runnable is done, as the reader can't be interrupted while
+    //it's waiting for input.
+    let result = thread::scope(|s| {
+        s.spawn(move |_| {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| runnable.run()));
+            std::process::exit(if result.is_ok() { 0 } else { 101 });
+        });
+        for line in lines {
+            let message = match line {
+                Ok(l) => serde_json::from_str::<MainToHost>(&l),
+                Err(_) => break
+            };
+            let result = match message {
+                Ok(MainToHost::Quit) => break,
+                Ok(MainToHost::Refresh) => from_main.send_refresh(),
+                Ok(MainToHost::Click(event)) => from_main.send_click_event(&event),
+                Ok(MainToHost::CustomMessage(message)) => from_main.send_custom_message(&message),
+                Ok(MainToHost::Config { .. }) => Ok(()),
+                Err(e) => {
+                    eprintln!("{}", gettext!("The plugin host received an invalid message: {}", e));
+                    Ok(())
+                }
+            };
+            if result.is_err() {
+                break;
+            }
+        }
+        //Either main asked us to quit, or it is gone. Both mean we're done.
+        let _ = from_main.send_quit();
+    });
+    //Only reached if the runnable's thread couldn't exit the process itself.
+    match result {
+        Ok(()) => 0,
+        Err(_) => 101
+    }
+}
+
+/// Signals like `pkill -USR1 swaystatus` are meant for the main process, but they also reach the
+/// plugin hosts, as they have the same name. Their default action would kill the host, so they get
+/// handlers that do nothing. Unlike ignoring them, this doesn't carry over to programs a plugin
+/// starts. The main process tells the host when to quit.
+fn ignore_signals_for_main() {
+    use signal_hook::consts::signal::*;
+    for signal in [SIGUSR1, SIGHUP, SIGINT, SIGTERM] {
+        //Safe, as the handler does nothing at all.
+        if let Err(e) = unsafe { signal_hook::low_level::register(signal, || {}) } {
+            eprintln!("{}", gettext!("The plugin host failed to register a signal handler: {}", e));
+        }
+    }
+}
+
+/// Returns the socket to the main process, which is stdin and stdout when the host starts. After
+/// this, stdin reads from /dev/null, and stdout goes to stderr.
+fn take_socket_from_standard_streams() -> std::io::Result<UnixStream> {
+    let socket = UnixStream::from(std::io::stdin().as_fd().try_clone_to_owned()?);
+    let null = std::fs::File::open("/dev/null")?;
+    //Safe, as both only replace file descriptors that nothing else refers to any more.
+    unsafe {
+        if libc::dup2(null.as_raw_fd(), libc::STDIN_FILENO) < 0 || libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(socket)
+}
@@ -0,0 +1,161 @@
+//! Runs elements in helper processes.
+//! If an element is configured with `Isolation = "Process"`, the main process doesn't call the
+//! plugin directly. Instead it starts `swaystatus --plugin-host <library>`, which loads only that
+//! plugin and runs the element. Both sides exchange JSON messages, one per line, over a socket
+//! that is the helper's stdin and stdout. If the helper dies, for instance because the plugin
+//! segfaulted, the main process reports it as a crash of the element, exactly as if a thread had
+//! panicked.
+
+use std::io::{BufRead, BufReader};
+use std::os::unix::io::OwnedFd;
+use std::os::unix::net::UnixStream;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use serde::{Serialize, Deserialize};
+use gettextrs::*;
+use crate::plugin;
+use crate::communication::{Message, SenderToMain, send_without_sigpipe};
+use crate::config::SwaystatusPluginConfig;
+use plugin::MsgModuleToMain;
+
+mod host;
+pub use host::run_plugin_host;
+
+#[cfg(test)]
+mod tests;
+
+/// Messages from the main process to the plugin host.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum MainToHost {
+    /// Always the first message. Says which plugin of the library to use, and its config.
+    Config { plugin : String, config : serde_json::Value },
+    Quit,
+    Refresh,
+    Click(plugin::ClickEvent),
+    CustomMessage(String),
+}
+
+/// Messages from the plugin host to the main process.
+#[derive(Serialize, Deserialize, Debug)]
+enum HostToMain {
+    Update(Result<plugin::StatusBlock, plugin::PluginError>),
+}
+
+/// Writes one message as a line.
+fn send_message<T : Serialize>(stream : &UnixStream, message : &T) -> std::io::Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    send_without_sigpipe(stream, &line)
+}
+
+/// Starts the plugin host for the given element, and returns the runnable and the sender the core
+/// loop uses for it. The plugin name is the one the library reports, which differs from the
+/// element's if the config gives the plugin another name.
+pub fn make_process_runnable<'p>(element : &'p SwaystatusPluginConfig, plugin_name : &str, library : &std::path::Path, to_main : SenderToMain) -> (Box<dyn plugin::SwayStatusModuleRunnable + 'p>, Box<dyn plugin::MsgMainToModule + 'p>) {
+    match spawn_plugin_host(element, plugin_name, library) {
+        Ok((child, stream)) => {
+            let sender = stream.try_clone().ok().map(Mutex::new);
+            let label = crate::element_label(element, to_main.element_number);
+            (Box::new(ProcessRunnable { child : Mutex::new(child), stream, to_main, label }), Box::new(ProcessSender { stream : sender }))
+        },
+        Err(e) => (Box::new(FailedProcessRunnable { error : e.to_string(), to_main }), Box::new(ProcessSender { stream : None }))
+    }
+}
+
+fn spawn_plugin_host(element : &SwaystatusPluginConfig, plugin_name : &str, library : &std::path::Path) -> std::io::Result<(Child, UnixStream)> {
+    let config = serde_json::to_value(element.get_instance())?;
+    let (ours, theirs) = UnixStream::pair()?;
+    let child = Command::new(std::env::current_exe()?)
+        .arg("--plugin-host")
+        .arg(library)
+        .stdin(Stdio::from(OwnedFd::from(theirs.try_clone()?)))
+        .stdout(Stdio::from(OwnedFd::from(theirs)))
+        //A process group of its own, so Ctrl-C in the terminal only reaches the main process,
+        //which then tells the host to quit.
+        .process_group(0)
+        .spawn()?;
+    send_message(&ours, &MainToHost::Config { plugin : String::from(plugin_name), config })?;
+    Ok((child, ours))
+}
+
+struct ProcessRunnable {
+    child : Mutex<Child>,
+    stream : UnixStream,
+    to_main : SenderToMain,
+    /// How log messages refer to the element.
+    label : String,
+}
+
+impl plugin::SwayStatusModuleRunnable for ProcessRunnable {
+    fn run(&self) {
+        let element_number = self.to_main.element_number;
+        for line in BufReader::new(&self.stream).lines() {
+            let line = match line {
+                Ok(l) => l,
+                Err(_) => break
+            };
+            match serde_json::from_str::<HostToMain>(&line) {
+                Ok(HostToMain::Update(block)) => {
+                    if self.to_main.send_block_update(block).is_err() {
+                        break;
+                    }
+                },
+                Err(e) => eprintln!("{}", gettext!("Received an invalid message from the plugin host process of element {}: {}", self.label, e))
+            }
+        }
+        let status = self.child.lock().unwrap_or_else(|e| e.into_inner()).wait();
+        match status {
+            Ok(s) if s.success() => {},
+            Ok(s) => {
+                eprintln!("{}", gettext!("The plugin host process of element {} ended unexpectedly: {}", self.label, s));
+                //Ignoring the error is fine. If main isn't listening, it's not interested in crashes.
+                let _ = self.to_main.sender.send(Message::ThreadCrash { element_number });
+            },
+            Err(e) => {
+                eprintln!("{}", gettext!("Failed to wait for the plugin host process of element {}: {}", self.label, e));
+                let _ = self.to_main.sender.send(Message::ThreadCrash { element_number });
+            }
+        }
+    }
+}
+
+/// Used if the plugin host could not even be started.
+struct FailedProcessRunnable {
+    error : String,
+    to_main : SenderToMain,
+}
+
+impl plugin::SwayStatusModuleRunnable for FailedProcessRunnable {
+    fn run(&self) {
+        let _ = self.to_main.send_update(Err(plugin::PluginError::PrintToStdErr(gettext!("Failed to start the plugin host process: {}", self.error))));
+        let _ = self.to_main.send_update(Err(plugin::PluginError::ShowInsteadOfText(gettext("<plugin host failed>"))));
+    }
+}
+
+struct ProcessSender {
+    stream : Option<Mutex<UnixStream>>,
+}
+
+impl ProcessSender {
+    fn send(&self, message : &MainToHost) -> Result<(), plugin::PluginCommunicationError> {
+        let stream = self.stream.as_ref().ok_or(plugin::PluginCommunicationError)?;
+        let stream = stream.lock().unwrap_or_else(|e| e.into_inner());
+        send_message(&stream, message).map_err(|_| plugin::PluginCommunicationError)
+    }
+}
+
+impl plugin::MsgMainToModule for ProcessSender {
+    fn send_quit(&self) -> Result<(),plugin::PluginCommunicationError> {
+        self.send(&MainToHost::Quit)
+    }
+    fn send_refresh(&self) -> Result<(),plugin::PluginCommunicationError> {
+        self.send(&MainToHost::Refresh)
+    }
+    fn send_click_event(&self, event : &plugin::ClickEvent) -> Result<(),plugin::PluginCommunicationError> {
+        self.send(&MainToHost::Click(event.clone()))
+    }
+    fn send_custom_message(&self, message : &str) -> Result<(),plugin::PluginCommunicationError> {
+        self.send(&MainToHost::CustomMessage(String::from(message)))
+    }
+}
@@ -0,0 +1,26 @@
+use super::*;
+
+#[test]
+fn messages_survive_a_roundtrip() {
+    let (a, b) = UnixStream::pair().unwrap();
+    let event = plugin::ClickEvent { button : plugin::MouseButton::Other(12), modifiers : vec![String::from("Mod4")], x : 1, y : 2, relative_x : 3, relative_y : 4, width : 5, height : 6 };
+    let messages = vec![
+        MainToHost::Config { plugin : String::from("TestPlugin"), config : serde_json::json!({ "lines" : 2, "skull" : "bones" }) },
+        MainToHost::Refresh,
+        MainToHost::Click(event),
+        MainToHost::Quit,
+    ];
+    for m in &messages {
+        send_message(&a, m).unwrap();
+    }
+    drop(a);
+    let received : Vec<MainToHost> = BufReader::new(&b).lines().map(|l| serde_json::from_str(&l.unwrap()).unwrap()).collect();
+    assert_eq!(received, messages);
+}
+
+#[test]
+fn sending_to_a_closed_socket_is_an_error() {
+    let (a, b) = UnixStream::pair().unwrap();
+    drop(b);
+    assert!(send_message(&a, &MainToHost::Quit).is_err());
+}
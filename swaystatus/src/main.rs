@@ -4,11 +4,22 @@ mod plugin_database;
 mod communication;
 mod signalhandler;
 mod commandline;
+mod output;
+mod input;
+mod theme;
+mod restart;
+mod watchdog;
+mod isolation;
+mod exec;
+mod config_watcher;
+mod reload;
+mod control;
 
 extern crate gettextrs;
 use gettextrs::*;
 use crossbeam_utils::thread;
 use std::sync::mpsc;
+use std::time::Instant;
 
 use commandline::CommandlineAction;
 #[cfg(test)]
@@ -24,17 +35,33 @@ fn main() {
     }
     let commandline_parameters = commandline::parse_commandline();
     match commandline_parameters.action {
-        CommandlineAction::PrintSampleConfig => { 
-            print_sample_config(&commandline_parameters.plugin_folder);
+        CommandlineAction::PrintSampleConfig { format } => {
+            print_sample_config(&commandline_parameters.plugin_folders, format);
+        }
+        CommandlineAction::PrintConfigSchema => {
+            print_config_schema(&commandline_parameters.plugin_folders);
         }
         CommandlineAction::ListPlugins => {
-            list_plugins(&commandline_parameters.plugin_folder);
+            list_plugins(&commandline_parameters.plugin_folders);
         }
         CommandlineAction::PluginHelp(list) => {
-            print_plugin_help(&commandline_parameters.plugin_folder, list);
+            print_plugin_help(&commandline_parameters.plugin_folders, list);
+        }
+        CommandlineAction::CheckConfig { config_file } => {
+            std::process::exit(check_config(&commandline_parameters.plugin_folders, &config_file));
+        }
+        CommandlineAction::PluginHost { library } => {
+            std::process::exit(isolation::run_plugin_host(&library));
+        }
+        CommandlineAction::Control { socket, command } => {
+            std::process::exit(control::run_client(socket.as_deref(), &command));
         }
         CommandlineAction::Run { config_file } => {
-            while !core_loop(&commandline_parameters.plugin_folder, &config_file) {}
+            let mut printer = output::StatusPrinter::from_environment();
+            let mut click_reader = input::ClickEventReader::default();
+            let mut control_socket = control::ControlSocket::default();
+            control_socket.ensure_running();
+            while !core_loop(&commandline_parameters.plugin_folders, &config_file, &mut printer, &mut click_reader, &control_socket) {}
         }
     }
 
@@ -42,20 +69,16 @@ fn main() {
 
 /// Actually the main() function. Factored out so we can restart without actually restaring.
 /// Because some people might expect that SIGHUP triggers a reload, and it's trivial to implement.
-fn core_loop(plugin_path : &std::path::Path, config_path : &std::path::Path) -> bool {
+/// Most reloads don't need the restart though: As long as the loaded plugins are enough for the
+/// new config, only the elements that changed are stopped and started.
+fn core_loop(plugin_folders : &[std::path::PathBuf], config_file : &config::ConfigFile, printer : &mut output::StatusPrinter, click_reader : &mut input::ClickEventReader, control_socket : &control::ControlSocket) -> bool {
     //Read plugins first (needed for config deserialization, given the config files has
     //plugin config as well...
-    let libraries = match plugin_database::Libraries::load_from_folder(plugin_path) {
-        Ok(x) => x,
-        Err(e) => {
-            print_plugin_load_error(e,plugin_path);
-            return true;
-        }
-    };
-    let plugins = plugin_database::PluginDatabase::new(&libraries); 
+    let (libraries, sources) = load_libraries_for_config(plugin_folders, config_file);
+    let plugins = plugin_database::PluginDatabase::with_sources(&libraries, &sources);
 
-    let (elements, main_config) = match config::SwaystatusConfig::read_config(config_path, &plugins) {
-        Ok(x) => (x.elements.unwrap_or_default(), x.settings.unwrap_or_default()),
+    let (elements, mut main_config, mut theme) = match config::SwaystatusConfig::read_config(config_file, &plugins) {
+        Ok(x) => (x.elements.unwrap_or_default(), x.settings.unwrap_or_default(), theme::Theme::from(x.theme.unwrap_or_default())),
         Err(e) => { print_config_error(e); return true;}
     };
 
@@ -66,47 +89,218 @@ fn core_loop(plugin_path : &std::path::Path, config_path : &std::path::Path) ->
 
     let (sender_from_plugins, receiver_from_plugins) = mpsc::channel();
 
-    let (runnables, senders_to_plugins) : (Vec<_>, Vec<_>) = elements.iter().enumerate().map(|(i,x)| {
-        let s = communication::SenderToMain { 
-            sender : sender_from_plugins.clone(),
-            element_number : i,
-        };
-        x.get_instance().make_runnable(Box::new(s))
+    if main_config.output == config::OutputFormat::I3bar {
+        click_reader.ensure_running();
+    }
+    click_reader.target().connect(Some(sender_from_plugins.clone()));
+    control_socket.target().connect(Some(sender_from_plugins.clone()));
+
+    //The threads borrow the config of their element, and a reload can keep threads running. The
+    //store has the config of every element that is running, indexed by the number it sends its
+    //messages with. This number stays the same if a reload moves the element to another position
+    //on the bar.
+    let element_store = reload::ElementStore::default();
+    //The numbers of the elements on the bar, in the order they are shown.
+    let mut shown_elements : Vec<usize> = elements.into_iter().map(|e| element_store.add(e).0).collect();
+    //The elements on the bar. Like the blocks and the senders, indexed by position.
+    let mut elements : Vec<_> = shown_elements.iter().map(|n| element_store.get(*n)).collect();
+
+    let (runnables, mut senders_to_plugins) : (Vec<_>, Vec<_>) = elements.iter().enumerate().map(|(i,x)| {
+        warn_if_isolation_impossible(x, i, &plugins);
+        make_runnable_for_element(x, i, &sender_from_plugins, &plugins)
     }).unzip();
 
-    //mutable array into which we store our updated texts.
-    let mut texts = Vec::with_capacity(elements.len());
-    texts.resize(elements.len(),String::new());
-    assert_eq!(texts.len(), runnables.len());
-    assert_eq!(texts.len(), senders_to_plugins.len());
+    //mutable array into which we store our updated blocks.
+    let mut blocks = Vec::with_capacity(elements.len());
+    blocks.resize(elements.len(),plugin::StatusBlock::default());
+    assert_eq!(blocks.len(), runnables.len());
+    assert_eq!(blocks.len(), senders_to_plugins.len());
     assert_eq!(elements.len(), runnables.len());
 
     let mut should_restart = false;
 
+    //Needed to restart crashed elements, and to start the new elements of a reload. Dropped once
+    //we quit, as no new threads are wanted then.
+    let mut sender_for_new_threads = Some(sender_from_plugins.clone());
+    let mut restarts = restart::RestartSchedule::new(&main_config, elements.len());
+    let mut watchdog = watchdog::Watchdog::new(&main_config, &elements, Instant::now());
+
     // Main everything is ready for the big main loop. Let's spawn the threads!
     if let Err(_e) = thread::scope(|s| {
-        signalhandler::handle_signals(s, sender_from_plugins);
-        for runnable in runnables {
-            s.spawn(move |_| {
-                runnable.run();
-            });
+        let signals = signalhandler::handle_signals(s, sender_from_plugins.clone());
+        let config_watcher = if main_config.reload_on_change {
+            watch_config(s, config_file, sender_from_plugins.clone())
+        }
+        else {
+            None
+        };
+        for (element_number, runnable) in runnables.into_iter().enumerate() {
+            spawn_element_thread(s, runnable, element_number, &sender_from_plugins, &mut watchdog);
         }
+        drop(sender_from_plugins);
 
-        while let Ok(msg) = receiver_from_plugins.recv() {
+        loop {
+            let next_deadline = [restarts.next_deadline(), watchdog.next_deadline()].into_iter().flatten().min();
+            let msg = match next_deadline {
+                Some(deadline) => match receiver_from_plugins.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(msg) => Some(msg),
+                    Err(mpsc::RecvTimeoutError::Timeout) => None,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break
+                },
+                None => match receiver_from_plugins.recv() {
+                    Ok(msg) => Some(msg),
+                    Err(_) => break
+                }
+            };
             match msg {
-                communication::Message::Internal(i) => {
-                    if let communication::InternalMessage::Reload = i {
-                        should_restart = true;
+                None => {},
+                Some(communication::Message::Internal(communication::InternalMessage::Refresh)) => {
+                    forward_to_all_plugins(&senders_to_plugins, &elements, communication::InternalMessage::Refresh);
+                },
+                //Only quitting and restarting cut the long-lived threads off from this core loop.
+                Some(communication::Message::Internal(i @ (communication::InternalMessage::Quit | communication::InternalMessage::Reload))) => {
+                    let plan = match (&i, &sender_for_new_threads) {
+                        (communication::InternalMessage::Reload, Some(_)) => plan_reload(plugin_folders, config_file, &plugins, &sources, &main_config),
+                        _ => ReloadPlan::Restart
+                    };
+                    match (plan, &sender_for_new_threads) {
+                        (ReloadPlan::Incremental(new_config), Some(sender)) => {
+                            let now = Instant::now();
+                            let new_elements = new_config.elements.unwrap_or_default();
+                            //Elements that crashed for good are started again, like on a restart.
+                            let running : Vec<_> = elements.iter().zip(&shown_elements)
+                                .map(|(e, n)| watchdog.is_running(*n).then(|| reload::element_identity(e)).flatten())
+                                .collect();
+                            let kept = reload::match_elements(&running, &new_elements.iter().map(reload::element_identity).collect::<Vec<_>>());
+                            let removed : Vec<_> = shown_elements.iter().copied().enumerate().filter(|(position, _)| !kept.contains(&Some(*position))).collect();
+                            for (position, element_number) in &removed {
+                                if watchdog.is_running(*element_number) {
+                                    stop_removed_element(senders_to_plugins[*position].as_ref(), elements[*position], *position);
+                                }
+                                watchdog.stop_element(*element_number, now);
+                                restarts.cancel(*element_number);
+                            }
+                            main_config = new_config.settings.unwrap_or_default();
+                            theme = theme::Theme::from(new_config.theme.unwrap_or_default());
+                            restarts.apply_settings(&main_config);
+                            //The senders and blocks of kept elements move to their new position.
+                            //What's left belongs to the removed elements, which already got told
+                            //to quit.
+                            let mut old_senders : Vec<_> = senders_to_plugins.drain(..).map(Some).collect();
+                            let mut old_blocks : Vec<_> = blocks.drain(..).map(Some).collect();
+                            let old_shown = std::mem::take(&mut shown_elements);
+                            for (element, keep) in new_elements.into_iter().zip(kept) {
+                                match keep {
+                                    //The running element has the same config, the new one isn't needed.
+                                    Some(position) => {
+                                        watchdog.configure(old_shown[position], &main_config, &element);
+                                        shown_elements.push(old_shown[position]);
+                                        senders_to_plugins.extend(old_senders[position].take());
+                                        blocks.extend(old_blocks[position].take());
+                                    },
+                                    None => {
+                                        let (element_number, element) = element_store.add(element);
+                                        watchdog.add_element(element_number, &main_config, element, now);
+                                        restarts.add_element(element_number);
+                                        warn_if_isolation_impossible(element, shown_elements.len(), &plugins);
+                                        let (runnable, sender_to_plugin) = make_runnable_for_element(element, element_number, sender, &plugins);
+                                        spawn_element_thread(s, runnable, element_number, sender, &mut watchdog);
+                                        shown_elements.push(element_number);
+                                        senders_to_plugins.push(sender_to_plugin);
+                                        blocks.push(plugin::StatusBlock::default());
+                                    }
+                                }
+                            }
+                            elements = shown_elements.iter().map(|n| element_store.get(*n)).collect();
+                            //The senders of removed elements borrow from their config, so they
+                            //have to be gone before any config is dropped.
+                            drop(old_senders);
+                            drop(old_blocks);
+                            //Removed elements that are still running are dropped once they finish.
+                            for (_, element_number) in removed.into_iter().filter(|(_, n)| !watchdog.is_running(*n)) {
+                                drop_removed_element(&element_store, &watchdog, &shown_elements, &senders_to_plugins, element_number);
+                            }
+                            if main_config.output == config::OutputFormat::I3bar {
+                                click_reader.ensure_running();
+                            }
+                            printer.print(&blocks, &main_config, &theme, &elements);
+                        },
+                        (ReloadPlan::Keep, _) => {},
+                        _ => {
+                            should_restart = matches!(i, communication::InternalMessage::Reload);
+                            click_reader.target().connect(None);
+                            control_socket.target().connect(None);
+                            sender_for_new_threads = None;
+                            restarts.cancel_all();
+                            watchdog.begin_shutdown(Instant::now());
+                            signals.close();
+                            if let Some(watcher) = &config_watcher {
+                                watcher.stop();
+                            }
+                            forward_to_all_plugins(&senders_to_plugins,&elements, i);
+                        }
                     }
-                    forward_to_all_plugins(&senders_to_plugins,&elements, i);
                 },
-                communication::Message::External{text, element_number} => {
-                    handle_message_from_element(&mut texts, elements[element_number].get_name(), element_number, text);
-                    print_texts(&texts, &main_config, &elements);
+                Some(communication::Message::External{block, element_number}) => {
+                    let responsive_again = watchdog.seen(element_number, Instant::now());
+                    //Elements that a reload removed might still send something while finishing.
+                    if let Some(position) = position_on_bar(&shown_elements, element_number) {
+                        if responsive_again {
+                            eprintln!("{}", gettext!("Element {} (plugin: {}) is responsive again.", element_label(elements[position], position), elements[position].get_name()));
+                        }
+                        handle_message_from_element(&mut blocks, elements[position], position, block);
+                        printer.print(&blocks, &main_config, &theme, &elements);
+                    }
+                },
+                Some(communication::Message::ThreadCrash{element_number}) => {
+                    if let Some(position) = position_on_bar(&shown_elements, element_number) {
+                        handle_crash_from_element(&mut blocks, elements[position], position);
+                        if sender_for_new_threads.is_some() {
+                            schedule_restart_of_element(&mut restarts, elements[position], element_number, position);
+                        }
+                        printer.print(&blocks, &main_config, &theme, &elements);
+                    }
                 },
-                communication::Message::ThreadCrash{element_number} => {
-                    handle_crash_from_element(&mut texts, elements[element_number].get_name(), element_number);
-                    print_texts(&texts, &main_config, &elements);
+                Some(communication::Message::Finished{element_number}) => {
+                    watchdog.thread_finished(element_number);
+                    //The last thread of an element that a reload removed is gone. Its sender was
+                    //dropped by the reload already.
+                    if !watchdog.is_running(element_number) && position_on_bar(&shown_elements, element_number).is_none() {
+                        drop_removed_element(&element_store, &watchdog, &shown_elements, &senders_to_plugins, element_number);
+                    }
+                },
+                Some(communication::Message::Click{name, instance, event}) => {
+                    forward_click_to_element(&senders_to_plugins, &elements, &name, &instance, &event);
+                },
+                Some(communication::Message::Signal(signal)) => {
+                    refresh_elements_with_signal(&senders_to_plugins, &elements, signal);
+                },
+                Some(communication::Message::Control{request, reply}) => {
+                    //The control socket only waits for a while, so the answer might not be wanted any more.
+                    let _ = reply.send(answer_control_request(request, &senders_to_plugins, &elements, &blocks, &theme));
+                }
+            }
+            for event in watchdog.check(Instant::now()) {
+                handle_watchdog_event(&mut blocks, &shown_elements, &element_store, event);
+                printer.print(&blocks, &main_config, &theme, &elements);
+            }
+            if watchdog.is_shutdown_complete() {
+                break;
+            }
+            if watchdog.only_hung_elements_left() {
+                //The scope would wait for the hung threads forever. The only way out is to leave
+                //the process behind.
+                abandon_hung_elements(printer, should_restart);
+            }
+            if let Some(sender) = &sender_for_new_threads {
+                for element_number in restarts.take_due(Instant::now()) {
+                    let Some(position) = position_on_bar(&shown_elements, element_number) else {
+                        continue;
+                    };
+                    eprintln!("{}", gettext!("Restarting element {} (plugin: {}).", element_label(elements[position], position), elements[position].get_name()));
+                    let (runnable, sender_to_plugin) = make_runnable_for_element(elements[position], element_number, sender, &plugins);
+                    senders_to_plugins[position] = sender_to_plugin;
+                    spawn_element_thread(s, runnable, element_number, sender, &mut watchdog);
                 }
             }
         }
@@ -119,10 +313,72 @@ fn core_loop(plugin_path : &std::path::Path, config_path : &std::path::Path) ->
         //print a general error here.
         eprintln!("{}", gettext("At least one of the plugins panicked. For details please check the (hopefully existing) previous error messages."));
     }
+    click_reader.target().connect(None);
+    control_socket.target().connect(None);
 
     !should_restart
 }
 
+/// What to do about a reload.
+enum ReloadPlan<'p> {
+    /// The loaded plugins are enough for the new config, so only the elements that changed need
+    /// to be stopped and started.
+    Incremental(Box<config::SwaystatusConfig<'p>>),
+    /// Everything has to be restarted, for instance because the new config needs other plugins.
+    Restart,
+    /// The new config can't be used. The reason has been printed already.
+    Keep,
+}
+
+/// Checks the new config before anything is stopped, so a broken file doesn't take the bar down.
+fn plan_reload<'p>(plugin_folders : &[std::path::PathBuf], config_file : &config::ConfigFile, plugins : &'p plugin_database::PluginDatabase, sources : &std::collections::BTreeMap<String, config::PluginSourceConfig>, settings : &config::SwaystatusMainConfig) -> ReloadPlan<'p> {
+    let needs_other_plugins = config::read_plugin_requirements(config_file).is_some_and(|requirements| {
+        requirements.used_sources() != *sources || requirements.referenced.iter().any(|name| plugins.get_plugin(name).is_none())
+    });
+    let problems = if needs_other_plugins {
+        find_config_problems(plugin_folders, config_file)
+    }
+    else {
+        config::SwaystatusConfig::check_config(config_file, plugins).map_err(print_config_error).ok()
+    };
+    match problems {
+        None => {
+            eprintln!("{}", gettext("Could not reload the configuration. Keeping the current one."));
+            return ReloadPlan::Keep;
+        },
+        Some(problems) if !problems.is_empty() => {
+            for problem in &problems {
+                eprintln!("{}", format_config_problem(problem));
+            }
+            eprintln!("{}", gettext("The new configuration has problems. Keeping the current one."));
+            return ReloadPlan::Keep;
+        },
+        Some(_) => {}
+    }
+    if needs_other_plugins {
+        return ReloadPlan::Restart;
+    }
+    let config = match config::SwaystatusConfig::read_config(config_file, plugins) {
+        Ok(x) => x,
+        Err(e) => {
+            print_config_error(e);
+            eprintln!("{}", gettext("Could not reload the configuration. Keeping the current one."));
+            return ReloadPlan::Keep;
+        }
+    };
+    if config.elements.as_ref().is_none_or(|e| e.is_empty()) {
+        eprintln!("{}", gettext("No elements set up in configuration. Nothing to display."));
+        eprintln!("{}", gettext("Could not reload the configuration. Keeping the current one."));
+        return ReloadPlan::Keep;
+    }
+    //The config watcher is only started along with the core loop.
+    let reload_on_change = config.settings.as_ref().map_or(config::SwaystatusMainConfig::default().reload_on_change, |s| s.reload_on_change);
+    if reload_on_change != settings.reload_on_change {
+        return ReloadPlan::Restart;
+    }
+    ReloadPlan::Incremental(Box::new(config))
+}
+
 //-----------------------------------------------------------------------------
 //Helpers
 
@@ -131,104 +387,336 @@ fn print_config_error(e : config::SwaystatusConfigErrors) {
         config::SwaystatusConfigErrors::FileNotFound => {
             eprintln!("{}", gettext("The configuration file could not be read. Nothing to do."));
         },
-        config::SwaystatusConfigErrors::ParsingError {message} => {
-            eprintln!("{}", gettext!("The parser for the config file returned an error: {}", message));
+        config::SwaystatusConfigErrors::ParsingError {file, message} => {
+            eprintln!("{}", gettext!("The parser for the config file {} returned an error: {}", file.display(), message));
         }
     }
 }
 
-fn forward_to_all_plugins<'p>(senders : &[Box<dyn plugin::MsgMainToModule + 'p>], elements : &[config::SwaystatusPluginConfig], message : communication::InternalMessage) {
+fn forward_to_all_plugins<'p>(senders : &[Box<dyn plugin::MsgMainToModule + 'p>], elements : &[&config::SwaystatusPluginConfig], message : communication::InternalMessage) {
     match message {
         communication::InternalMessage::Quit | communication::InternalMessage::Reload => {
             for (i, sender) in senders.iter().enumerate() {
                 if sender.send_quit().is_err() {
-                    eprintln!("{}", gettext!("Tried to tell a plugin to quit, but that plugin seems to no longer listen to messages. Either that plugin has already terminated, or it's stuck. In the latter case a clean exit is impossible, you'll need to kill this process. The offending element is element number {} from plugin {}.", i, elements[i].get_name()));
+                    eprintln!("{}", gettext!("Tried to tell a plugin to quit, but that plugin seems to no longer listen to messages. Either that plugin has already terminated, or it's stuck. In the latter case it will be abandoned once its shutdown timeout has passed. The offending element is element {} from plugin {}.", element_label(elements[i], i), elements[i].get_name()));
                 }
             }
         },
         communication::InternalMessage::Refresh => {
             for (i,sender) in senders.iter().enumerate() {
                 if sender.send_refresh().is_err() {
-                    eprintln!("{}", gettext!("Tried to tell a plugin to refresh, but it doesn't listen any more. Either the plugin already terminated, or it is stuck. The offending element is element number {} from plugin {}.", i, elements[i].get_name()));
+                    eprintln!("{}", gettext!("Tried to tell a plugin to refresh, but it doesn't listen any more. Either the plugin already terminated, or it is stuck. The offending element is element {} from plugin {}.", element_label(elements[i], i), elements[i].get_name()));
                 }
             }
         }
     }
 }
 
-fn handle_message_from_element(texts : &mut Vec<String>, plugin : &str, element_number : usize, message : Result<String, plugin::PluginError>) {
+fn refresh_elements_with_signal<'p>(senders : &[Box<dyn plugin::MsgMainToModule + 'p>], elements : &[&config::SwaystatusPluginConfig], signal : u32) {
+    for (i, sender) in senders.iter().enumerate().filter(|(i, _)| elements[*i].get_non_plugin_settings().signal == Some(signal)) {
+        if sender.send_refresh().is_err() {
+            eprintln!("{}", gettext!("Tried to tell a plugin to refresh, but it doesn't listen any more. Either the plugin already terminated, or it is stuck. The offending element is element {} from plugin {}.", element_label(elements[i], i), elements[i].get_name()));
+        }
+    }
+}
+
+/// The instance we send to the bar is the element's name, or its position if it has none. The
+/// plugin name is checked as well, so clicks on separators, or clicks that arrive after a reload
+/// changed the elements, end up nowhere. Clicks on named elements still arrive if a reload only
+/// moved them.
+fn forward_click_to_element<'p>(senders : &[Box<dyn plugin::MsgMainToModule + 'p>], elements : &[&config::SwaystatusPluginConfig], name : &str, instance : &str, event : &plugin::ClickEvent) {
+    let Some(position) = elements.iter().enumerate().position(|(position, element)| {
+        element.get_name() == name && output::element_instance(element, position) == instance
+    }) else {
+        return;
+    };
+    if senders[position].send_click_event(event).is_err() {
+        eprintln!("{}", gettext!("Tried to forward a click to a plugin, but it doesn't listen any more. Either the plugin already terminated, or it is stuck. The offending element is element {} from plugin {}.", element_label(elements[position], position), name));
+    }
+}
+
+/// Answers the commands of the control socket that need the elements.
+fn answer_control_request<'p>(request : control::Request, senders : &[Box<dyn plugin::MsgMainToModule + 'p>], elements : &[&config::SwaystatusPluginConfig], blocks : &[plugin::StatusBlock], theme : &theme::Theme) -> control::Reply {
+    let find = |key : &str| elements.iter().enumerate()
+        .position(|(position, element)| output::element_instance(element, position) == key)
+        .ok_or_else(|| gettext!("There is no element {}.", key));
+    let not_listening = |position : usize| gettext!("Element {} (plugin: {}) doesn't listen any more.", element_label(elements[position], position), elements[position].get_name());
+    match request {
+        control::Request::Dump => Ok(elements.iter().zip(blocks).enumerate()
+            .map(|(position, (element, block))| format!("{}\t{}", output::element_instance(element, position), output::element_text(block, element, theme).replace('\n', " ")))
+            .collect()),
+        control::Request::Refresh(key) => {
+            let position = find(&key)?;
+            senders[position].send_refresh().map(|_| Vec::new()).map_err(|_| not_listening(position))
+        },
+        control::Request::Message { element, text } => {
+            let position = find(&element)?;
+            senders[position].send_custom_message(&text).map(|_| Vec::new()).map_err(|_| not_listening(position))
+        }
+    }
+}
+
+/// How log messages refer to an element: by its name, or by its position on the bar if it has none.
+fn element_label(element : &config::SwaystatusPluginConfig, position : usize) -> String {
+    match element.get_element_name() {
+        Some(name) => format!("\"{}\"", name),
+        None => gettext!("number {}", position)
+    }
+}
+
+fn handle_message_from_element(blocks : &mut [plugin::StatusBlock], element : &config::SwaystatusPluginConfig, position : usize, message : Result<plugin::StatusBlock, plugin::PluginError>) {
     match message {
-        Ok(t) => texts[element_number] = t,
+        Ok(b) => blocks[position] = b,
         Err(e) => match e {
-            plugin::PluginError::PrintToStdErr(t) => eprintln!("{}", gettext!("Element number {} (plugin: {}) sent an error message: {}",element_number, plugin,t)),
+            plugin::PluginError::PrintToStdErr(t) => eprintln!("{}", gettext!("Element {} (plugin: {}) sent an error message: {}", element_label(element, position), element.get_name(), t)),
             plugin::PluginError::ShowInsteadOfText(t) => {
-                eprintln!("{}", gettext!("Element number {} (plugin: {}) sent an error message: {}",element_number, plugin,t));
-                texts[element_number] = t;
+                eprintln!("{}", gettext!("Element {} (plugin: {}) sent an error message: {}", element_label(element, position), element.get_name(), t));
+                blocks[position] = plugin::StatusBlock::from(t);
             }
         }
     }
 }
 
-fn print_texts(texts : &[String], settings : &config::SwaystatusMainConfig, element_settings : &[config::SwaystatusPluginConfig]) {
-    //Once we do more than just printing, we might want a more advanced code here...
-    let separators = std::iter::once("").chain(std::iter::repeat(&settings.separator[..]));
-    let before_texts = element_settings.iter().map(|x| &x.get_non_plugin_settings().before_text);
-    let after_texts = element_settings.iter().map(|x| &x.get_non_plugin_settings().after_text);
-    let text_with_after = texts.iter().zip(after_texts);
-    let complete_text = before_texts.zip(text_with_after);
+/// A helper process loads the plugin's library, so plugins without one, like the built-in ones,
+/// always run in a thread.
+fn warn_if_isolation_impossible(element : &config::SwaystatusPluginConfig, position : usize, plugins : &plugin_database::PluginDatabase) {
+    if element.get_non_plugin_settings().isolation == Some(config::ElementIsolation::Process) && plugins.get_library_path(element.get_name()).is_none() {
+        eprintln!("{}", gettext!("Element {} (plugin: {}) is configured to run in a process of its own, but the plugin isn't loaded from a library. It runs in a thread of swaystatus instead.", element_label(element, position), element.get_name()));
+    }
+}
+
+fn make_runnable_for_element<'p>(element : &'p config::SwaystatusPluginConfig, element_number : usize, sender : &mpsc::Sender<communication::Message>, plugins : &plugin_database::PluginDatabase) -> (Box<dyn plugin::SwayStatusModuleRunnable + 'p>, Box<dyn plugin::MsgMainToModule + 'p>) {
+    let features = plugins.get_features(element.get_name());
+    let s = communication::SenderToMain { 
+        sender : sender.clone(),
+        element_number,
+        structured_updates : features.contains(plugin::PluginFeatures::STRUCTURED_UPDATES),
+    };
+    let library = plugins.get_library_path(element.get_name())
+        .filter(|_| element.get_non_plugin_settings().isolation == Some(config::ElementIsolation::Process));
+    let (runnable, sender) = match library {
+        Some(library) => {
+            let plugin_name = plugins.get_plugin(element.get_name()).map_or(element.get_name(), |p| p.get_name());
+            isolation::make_process_runnable(element, plugin_name, library, s)
+        },
+        None => element.get_instance().make_runnable(Box::new(s))
+    };
+    if features.contains(plugin::PluginFeatures::CLICK_EVENTS) {
+        (runnable, sender)
+    }
+    else {
+        (runnable, Box::new(communication::IgnoreClickEvents(sender)))
+    }
+}
+
+/// Drops the config of an element that a reload removed. Only allowed once the element isn't
+/// shown, its threads have finished, and its sender is gone.
+fn drop_removed_element<'p, 's>(element_store : &reload::ElementStore<config::SwaystatusPluginConfig<'p>>, watchdog : &watchdog::Watchdog, shown_elements : &[usize], senders_to_plugins : &[Box<dyn plugin::MsgMainToModule + 's>], element_number : usize) {
+    //The senders are kept by position on the bar, so an element that isn't shown has none left.
+    debug_assert_eq!(senders_to_plugins.len(), shown_elements.len());
+    debug_assert!(position_on_bar(shown_elements, element_number).is_none());
+    debug_assert!(!watchdog.is_running(element_number));
+    //The runnable went with the thread, the sender with the reload, and the references in the
+    //list of shown elements with the last rebuild of that list.
+    unsafe { element_store.remove(element_number) };
+}
 
-    let final_iterator = separators.zip(complete_text);
+fn spawn_element_thread<'s, 'p : 's>(scope : &thread::Scope<'s>, runnable : Box<dyn plugin::SwayStatusModuleRunnable + 'p>, element_number : usize, sender : &mpsc::Sender<communication::Message>, watchdog : &mut watchdog::Watchdog) {
+    let finished = communication::FinishedNotifier { sender : sender.clone(), element_number };
+    watchdog.thread_started(element_number, Instant::now());
+    scope.spawn(move |_| {
+        //Locals are dropped in reverse order, also when unwinding. The runnable has to go first,
+        //so its crash report arrives before the notification that the thread is done.
+        let _finished = finished;
+        let runnable = runnable;
+        runnable.run();
+    });
+}
 
-    for (separator, (before,(text,after))) in final_iterator {
-        print!("{}{}{}{}",separator,before,text,after);
+fn handle_watchdog_event(blocks : &mut [plugin::StatusBlock], shown_elements : &[usize], element_store : &reload::ElementStore<config::SwaystatusPluginConfig>, event : watchdog::WatchdogEvent) {
+    let element = |element_number| element_store.get(element_number);
+    match event {
+        watchdog::WatchdogEvent::Unresponsive(element_number) => {
+            let Some(position) = position_on_bar(shown_elements, element_number) else {
+                return;
+            };
+            eprintln!("{}", gettext!("Element {} (plugin: {}) didn't send anything within its heartbeat timeout. It might be stuck.", element_label(element(element_number), position), element(element_number).get_name()));
+            blocks[position] = plugin::StatusBlock { 
+                full_text : gettext("<plugin not responding>"),
+                state : plugin::BlockState::Critical,
+                ..Default::default()
+            };
+        },
+        watchdog::WatchdogEvent::Hung(element_number) => match position_on_bar(shown_elements, element_number) {
+            Some(position) => eprintln!("{}", gettext!("Element {} (plugin: {}) didn't finish within its shutdown timeout. It seems to be hung.", element_label(element(element_number), position), element(element_number).get_name())),
+            None => eprintln!("{}", gettext!("An element that was removed by a reload (plugin: {}) didn't finish within its shutdown timeout. It seems to be hung.", element(element_number).get_name()))
+        }
     }
-    println!(); //Previosly there was an explicit flush here, but printnl should do that for us.
 }
 
-fn handle_crash_from_element(texts : &mut Vec<String>, name : &str, element_number : usize) {
-    texts[element_number] = gettext("<plugin crashed>");
-    eprintln!("{}", gettext!("The plugin {} crashed while displaying element number {}. Please see the plugin's panic message above for details.",name, element_number));
+/// Where the element with the given number is shown on the bar. None if a reload removed it.
+fn position_on_bar(shown_elements : &[usize], element_number : usize) -> Option<usize> {
+    shown_elements.iter().position(|n| *n == element_number)
 }
 
-fn print_plugin_load_error(e : std::io::Error, plugin_path : &std::path::Path) {
-    eprintln!("{} {}", gettext!("Tried to load plugins from folder \"{}\", but failed. You might want to set a plugin directory on the command line. The actual error was:", plugin_path.display()), e);
+/// Tells an element that a reload removed or changed to quit.
+fn stop_removed_element(sender : &dyn plugin::MsgMainToModule, element : &config::SwaystatusPluginConfig, position : usize) {
+    if sender.send_quit().is_err() {
+        eprintln!("{}", gettext!("Tried to tell a plugin to quit, but that plugin seems to no longer listen to messages. Either that plugin has already terminated, or it's stuck. In the latter case it will be abandoned once its shutdown timeout has passed. The offending element is element {} from plugin {}.", element_label(element, position), element.get_name()));
+    }
 }
 
-fn print_sample_config(plugin_path : &std::path::Path) {
-    let libraries = match plugin_database::Libraries::load_from_folder(plugin_path) {
-        Ok(x) => x,
+/// Called if some elements don't finish. Threads can't be killed, so the only options are to
+/// exit the process, or to replace it with a fresh one for a reload. In the latter case the new
+/// process is told how far the output already got, so the status bar doesn't get a second header.
+fn abandon_hung_elements(printer : &output::StatusPrinter, reload : bool) -> ! {
+    if !reload {
+        eprintln!("{}", gettext("Exiting without waiting for the hung elements."));
+        std::process::exit(0);
+    }
+    eprintln!("{}", gettext("Restarting swaystatus without waiting for the hung elements."));
+    let error = match std::env::current_exe() {
+        Ok(executable) => {
+            let mut command = std::process::Command::new(executable);
+            command.args(std::env::args_os().skip(1));
+            if let Some(value) = printer.resume_value() {
+                command.env(output::StatusPrinter::RESUME_VARIABLE, value);
+            }
+            std::os::unix::process::CommandExt::exec(&mut command)
+        },
+        Err(e) => e
+    };
+    eprintln!("{}", gettext!("Failed to restart swaystatus: {}", error));
+    std::process::exit(1);
+}
+
+fn schedule_restart_of_element(restarts : &mut restart::RestartSchedule, element : &config::SwaystatusPluginConfig, element_number : usize, position : usize) {
+    match restarts.schedule(element_number, Instant::now()) {
+        Some(delay) => eprintln!("{}", gettext!("Element {} (plugin: {}) will be restarted in {} ms.", element_label(element, position), element.get_name(), delay.as_millis())),
+        None => eprintln!("{}", gettext!("Element {} (plugin: {}) crashed too often. It won't be restarted until swaystatus is reloaded.", element_label(element, position), element.get_name()))
+    }
+}
+
+fn handle_crash_from_element(blocks : &mut [plugin::StatusBlock], element : &config::SwaystatusPluginConfig, position : usize) {
+    blocks[position] = plugin::StatusBlock::from(gettext("<plugin crashed>"));
+    eprintln!("{}", gettext!("The plugin {} crashed while displaying element {}. Please see the plugin's panic message above for details.", element.get_name(), element_label(element, position)));
+}
+
+/// Loads the libraries of the plugins the config needs. Only the plugins the config mentions are
+/// loaded, and the libraries named in its [Plugins] section. Returns the [Plugins] section too, as
+/// it's needed to set up the plugin database.
+fn load_libraries_for_config(plugin_folders : &[std::path::PathBuf], config_file : &config::ConfigFile) -> (plugin_database::Libraries, std::collections::BTreeMap<String, config::PluginSourceConfig>) {
+    let (selection, sources) = match config::read_plugin_requirements(config_file) {
+        Some(requirements) => (plugin_database::PluginSelection::Named(requirements.plugins_to_search()), requirements.used_sources()),
+        None => (plugin_database::PluginSelection::All, Default::default())
+    };
+    let mut libraries = plugin_database::Libraries::load_from_folders(plugin_folders, &selection);
+    libraries.load_pinned(&sources);
+    (libraries, sources)
+}
+
+/// Loads the plugins the config file needs, and returns all problems in it. Returns None if the
+/// file couldn't be read at all, after printing why.
+fn find_config_problems(plugin_folders : &[std::path::PathBuf], config_file : &config::ConfigFile) -> Option<Vec<config::ConfigProblem>> {
+    let (libraries, sources) = load_libraries_for_config(plugin_folders, config_file);
+    let plugins = plugin_database::PluginDatabase::with_sources(&libraries, &sources);
+    match config::SwaystatusConfig::check_config(config_file, &plugins) {
+        Ok(x) => Some(x),
         Err(e) => {
-            print_plugin_load_error(e,plugin_path);
-            return;
+            print_config_error(e);
+            None
         }
+    }
+}
+
+fn format_config_problem(problem : &config::ConfigProblem) -> String {
+    let location = match (problem.element, problem.position) {
+        (Some(element), Some((line, column))) => gettext!("Element number {} (line {}, column {})", element, line, column),
+        (Some(element), None) => gettext!("Element number {}", element),
+        (None, Some((line, column))) => gettext!("Line {}, column {}", line, column),
+        (None, None) => gettext("Configuration")
     };
-    let plugins = plugin_database::PluginDatabase::new(&libraries); 
+    match &problem.file {
+        Some(file) => format!("{}: {}: {}", file.display(), location, problem.message),
+        None => format!("{}: {}", location, problem.message)
+    }
+}
 
-    config::SwaystatusConfig::print_sample_config(&plugins);
+/// Prints every problem in the config file. Returns the exit code: 0 if there are none.
+fn check_config(plugin_folders : &[std::path::PathBuf], config_file : &config::ConfigFile) -> i32 {
+    let Some(problems) = find_config_problems(plugin_folders, config_file) else {
+        return 1;
+    };
+    for problem in &problems {
+        println!("{}", format_config_problem(problem));
+    }
+    if problems.is_empty() {
+        println!("{}", gettext!("The configuration file {} is fine.", config_file.path.display()));
+        0
+    }
+    else {
+        println!("{}", ngettext!("Found {} problem in {}.", "Found {} problems in {}.", problems.len() as u32, problems.len(), config_file.path.display()));
+        1
+    }
 }
 
-fn list_plugins(plugin_path : &std::path::Path) {
-    let libraries = match plugin_database::Libraries::load_from_folder(plugin_path) {
+/// Starts a thread that triggers a reload whenever the config file changes. Whether the new
+/// config can be used is up to the core loop. Returns the means to stop the thread, or None if
+/// the file can't be watched.
+fn watch_config<'s>(scope : &thread::Scope<'s>, config_file : &'s config::ConfigFile, sender : mpsc::Sender<communication::Message>) -> Option<config_watcher::ConfigWatcherStop> {
+    let drop_ins = [config::drop_in_folder(&config_file.path)];
+    let watcher = config_watcher::ConfigWatcher::new(&config::read_config_files(config_file), &drop_ins);
+    let (mut watcher, stop) = match watcher.and_then(|w| w.stopper().map(|s| (w, s))) {
         Ok(x) => x,
         Err(e) => {
-            print_plugin_load_error(e,plugin_path);
-            return;
+            eprintln!("{}", gettext!("Failed to watch the configuration file for changes. Send SIGHUP to reload it. The error was: {}", e));
+            return None;
         }
     };
+    scope.spawn(move |_| {
+        while watcher.wait_for_change() {
+            //Includes might have been added or removed.
+            if let Err(e) = watcher.watch(&config::read_config_files(config_file), &drop_ins) {
+                eprintln!("{}", gettext!("Failed to watch the configuration file for changes. Send SIGHUP to reload it. The error was: {}", e));
+                break;
+            }
+            eprintln!("{}", gettext("The configuration file changed. Reloading."));
+            //Fails only if the core loop is already gone, then there's nothing left to do.
+            if sender.send(communication::Message::Internal(communication::InternalMessage::Reload)).is_err() {
+                break;
+            }
+        }
+    });
+    Some(stop)
+}
+
+fn print_sample_config(plugin_folders : &[std::path::PathBuf], format : config::ConfigFormat) {
+    let libraries = plugin_database::Libraries::load_from_folders(plugin_folders, &plugin_database::PluginSelection::All);
+    let plugins = plugin_database::PluginDatabase::new(&libraries); 
+
+    config::SwaystatusConfig::print_sample_config(&plugins, format);
+}
+
+fn print_config_schema(plugin_folders : &[std::path::PathBuf]) {
+    let libraries = plugin_database::Libraries::load_from_folders(plugin_folders, &plugin_database::PluginSelection::All);
+    let plugins = plugin_database::PluginDatabase::new(&libraries);
+    config::SwaystatusConfig::print_config_schema(&plugins);
+}
+
+fn list_plugins(plugin_folders : &[std::path::PathBuf]) {
+    let libraries = plugin_database::Libraries::load_from_folders(plugin_folders, &plugin_database::PluginSelection::All);
     let plugins = plugin_database::PluginDatabase::new(&libraries);
     for (name, _) in plugins.get_name_and_plugin_iterator() {
         println!("{}", name);
     }
 }
 
-fn print_plugin_help(plugin_path : &std::path::Path, list : commandline::PluginHelpOption) {
-    let libraries = match plugin_database::Libraries::load_from_folder(plugin_path) {
-        Ok(x) => x,
-        Err(e) => {
-            print_plugin_load_error(e,plugin_path);
-            return;
-        }
+fn print_plugin_help(plugin_folders : &[std::path::PathBuf], list : commandline::PluginHelpOption) {
+    let selection = match &list {
+        commandline::PluginHelpOption::All => plugin_database::PluginSelection::All,
+        commandline::PluginHelpOption::List(l) => plugin_database::PluginSelection::Named(l.iter().cloned().collect())
     };
+    let libraries = plugin_database::Libraries::load_from_folders(plugin_folders, &selection);
     let plugins = plugin_database::PluginDatabase::new(&libraries);
     match list {
         commandline::PluginHelpOption::All => {
@@ -0,0 +1,245 @@
+//! Everything that ends up on stdout goes through here.
+//! There are two output formats. The plain one just prints a line of text for every update, which
+//! is what swaybar/i3bar accept if nothing else is said. The other one is the i3bar JSON protocol
+//! (see swaybar-protocol(7)), which tells the bar where one element ends and the next one starts.
+
+use std::borrow::Cow;
+use std::io::Write;
+use serde::Serialize;
+use gettextrs::*;
+use crate::config::{SwaystatusMainConfig, SwaystatusPluginConfig, SwaystatusElementNonPluginOptions, OutputFormat};
+use crate::plugin::{StatusBlock, MinWidth, Alignment};
+use crate::theme::Theme;
+
+#[cfg(test)]
+mod tests;
+
+/// The header of the i3bar protocol. Only holds the fields we actually use.
+#[derive(Serialize)]
+struct I3barHeader {
+    version : u32,
+    click_events : bool,
+}
+
+/// One block of the i3bar protocol. Fields that are not set are left out, so the bar uses its
+/// defaults for them.
+#[derive(Serialize, Default)]
+struct I3barBlock<'a> {
+    name : &'a str,
+    instance : String,
+    full_text : String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    short_text : Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color : Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    background : Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    border : Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    urgent : bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    markup : Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_width : Option<MinWidth>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    align : Option<Alignment>,
+    separator : bool,
+    separator_block_width : u32,
+}
+
+/// Remembers what has already been written to stdout. The i3bar protocol requires a header and
+/// the opening bracket of an infinite array, and both must be written exactly once per process.
+/// Since a reload restarts the core loop, this needs to live outside of it.
+#[derive(Default)]
+pub struct StatusPrinter {
+    /// The format that has been used so far. Set on first print, and never changed afterwards.
+    format : Option<OutputFormat>,
+    /// If the header and the opening bracket have already been written.
+    header_written : bool,
+    /// Only complain once if a reload tries to change the output format.
+    format_change_reported : bool,
+}
+
+impl StatusPrinter {
+    /// Environment variable that tells a re-executed swaystatus which output format its
+    /// predecessor already started on stdout.
+    pub const RESUME_VARIABLE : &'static str = "SWAYSTATUS_RESUME_OUTPUT";
+
+    /// Creates a printer that continues where a previous process left off, if the environment
+    /// says so. Otherwise it's the same as `StatusPrinter::default()`.
+    pub fn from_environment() -> Self {
+        let format = match std::env::var(Self::RESUME_VARIABLE).as_deref() {
+            Ok("Plain") => Some(OutputFormat::Plain),
+            Ok("I3bar") => Some(OutputFormat::I3bar),
+            _ => None
+        };
+        std::env::remove_var(Self::RESUME_VARIABLE);
+        StatusPrinter {
+            format,
+            header_written : format == Some(OutputFormat::I3bar),
+            format_change_reported : false,
+        }
+    }
+
+    /// The value for `RESUME_VARIABLE` that lets a new process continue this one's output.
+    pub fn resume_value(&self) -> Option<&'static str> {
+        match self.format {
+            Some(OutputFormat::Plain) => Some("Plain"),
+            Some(OutputFormat::I3bar) if self.header_written => Some("I3bar"),
+            _ => None
+        }
+    }
+
+    pub fn print(&mut self, blocks : &[StatusBlock], settings : &SwaystatusMainConfig, theme : &Theme, element_settings : &[&SwaystatusPluginConfig]) {
+        let format = *self.format.get_or_insert(settings.output);
+        if format != settings.output && !self.format_change_reported {
+            eprintln!("{}", gettext("The output format cannot be changed by a reload, because the status bar would not understand it. Please restart swaystatus to switch formats."));
+            self.format_change_reported = true;
+        }
+        let stdout = std::io::stdout();
+        let mut lock = stdout.lock();
+        let result = match format {
+            OutputFormat::Plain => write_plain(&mut lock, blocks, settings, theme, element_settings),
+            OutputFormat::I3bar => {
+                let is_first_line = !self.header_written;
+                self.header_written = true;
+                write_i3bar(&mut lock, is_first_line, blocks, settings, theme, element_settings)
+            }
+        };
+        if let Err(e) = result {
+            eprintln!("{}", gettext!("Failed to write the status line to stdout: {}", e));
+        }
+    }
+}
+
+fn write_plain<W : Write>(out : &mut W, blocks : &[StatusBlock], settings : &SwaystatusMainConfig, theme : &Theme, element_settings : &[&SwaystatusPluginConfig]) -> std::io::Result<()> {
+    let mut separator = "";
+    for (block, element) in blocks.iter().zip(element_settings) {
+        write!(out, "{}{}", separator, element_text(block, element, theme))?;
+        separator = if element.get_non_plugin_settings().wants_separator() { &settings.separator } else { "" };
+    }
+    writeln!(out)?;
+    out.flush()
+}
+
+/// The text of an element as the plain output shows it, with the before and after texts.
+pub fn element_text(block : &StatusBlock, element : &SwaystatusPluginConfig, theme : &Theme) -> String {
+    let general = element.get_non_plugin_settings();
+    theme.expand_icons(&format!("{}{}{}", general.before_text, block.full_text, general.after_text)).into_owned()
+}
+
+/// Writes one line of the infinite i3bar array. If this is the first line, the header and the
+/// opening bracket are written before it, otherwise the line starts with the separating comma.
+fn write_i3bar<W : Write>(out : &mut W, is_first_line : bool, blocks : &[StatusBlock], settings : &SwaystatusMainConfig, theme : &Theme, element_settings : &[&SwaystatusPluginConfig]) -> std::io::Result<()> {
+    if is_first_line {
+        serde_json::to_writer(&mut *out, &I3barHeader { version : 1, click_events : true })?;
+        writeln!(out, "\n[")?;
+    }
+    else {
+        write!(out, ",")?;
+    }
+    serde_json::to_writer(&mut *out, &make_i3bar_blocks(blocks, settings, theme, element_settings))?;
+    writeln!(out)?;
+    out.flush()
+}
+
+/// The i3bar protocol has its own separators, drawn by the bar. Those are used if the separator
+/// text in the settings is empty. Otherwise the separator text is inserted as a block of its own
+/// between the elements, so the bar looks the same as with plain output. Such separator blocks use
+/// the separator colors of the theme.
+fn make_i3bar_blocks<'a>(blocks : &[StatusBlock], settings : &SwaystatusMainConfig, theme : &Theme, element_settings : &[&'a SwaystatusPluginConfig]) -> Vec<I3barBlock<'a>> {
+    let native_separators = settings.separator.is_empty();
+    let default_separator_block_width = if native_separators { settings.separator_block_width } else { 0 };
+    let mut result = Vec::with_capacity(2 * blocks.len());
+    for (element_number, (block, element)) in blocks.iter().zip(element_settings).enumerate() {
+        let general = element.get_non_plugin_settings();
+        let styled = apply_element_style(block, general, theme);
+        let separator_block_width = general.separator_block_width.unwrap_or(default_separator_block_width);
+        let instance = element_instance(element, element_number);
+        result.push(I3barBlock {
+            name : element.get_name(),
+            instance : instance.clone(),
+            full_text : styled.full_text,
+            short_text : styled.short_text,
+            color : styled.color,
+            background : styled.background,
+            border : styled.border,
+            urgent : styled.urgent,
+            markup : if styled.markup { Some("pango") } else { None },
+            min_width : styled.min_width,
+            align : styled.align,
+            separator : native_separators && general.wants_separator(),
+            separator_block_width,
+        });
+        let is_last = element_number + 1 == blocks.len();
+        if !native_separators && general.wants_separator() && !is_last {
+            result.push(I3barBlock {
+                name : "separator",
+                instance,
+                full_text : theme.expand_icons(&settings.separator).into_owned(),
+                color : theme.palette.separator.clone(),
+                background : theme.palette.separator_background.clone(),
+                separator : false,
+                separator_block_width : 0,
+                ..Default::default()
+            });
+        }
+    }
+    result
+}
+
+/// The instance of an element's blocks in the i3bar protocol, which clicks are routed back with.
+/// That's the element's name, or its position on the bar if it has none.
+pub fn element_instance(element : &SwaystatusPluginConfig, position : usize) -> String {
+    element.get_element_name().map_or_else(|| position.to_string(), String::from)
+}
+
+/// Escapes the characters that have a meaning in Pango markup.
+pub fn escape_markup(text : &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '\'', '"']) {
+        return Cow::Borrowed(text);
+    }
+    let mut result = String::with_capacity(text.len() + 16);
+    for c in text.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '\'' => result.push_str("&apos;"),
+            '"' => result.push_str("&quot;"),
+            c => result.push(c)
+        }
+    }
+    Cow::Owned(result)
+}
+
+/// Fills in everything the plugin left open with the values configured for the element, adds
+/// the element's before and after texts, and replaces icon placeholders. If the plugin sends
+/// Pango markup, the before and after texts and the icons are escaped, as they are plain text.
+/// If the plugin reports a state other than Idle, the theme's color for it beats the element's
+/// foreground color, but not a color set by the plugin. The theme's idle color is only used if
+/// nobody else set a color.
+fn apply_element_style(block : &StatusBlock, general : &SwaystatusElementNonPluginOptions, theme : &Theme) -> StatusBlock {
+    let decorate = |text : &str| if block.markup {
+        theme.expand_icons_in_markup(&format!("{}{}{}", escape_markup(&general.before_text), text, escape_markup(&general.after_text))).into_owned()
+    }
+    else {
+        theme.expand_icons(&format!("{}{}{}", general.before_text, text, general.after_text)).into_owned()
+    };
+    StatusBlock {
+        full_text : decorate(&block.full_text),
+        short_text : block.short_text.as_deref().map(decorate),
+        color : block.color.clone()
+            .or_else(|| theme.palette.state_color(block.state).map(String::from))
+            .or_else(|| general.foreground.clone())
+            .or_else(|| theme.palette.idle.clone()),
+        background : block.background.clone().or_else(|| general.background.clone()),
+        border : block.border.clone().or_else(|| general.border.clone()),
+        urgent : block.urgent,
+        markup : block.markup,
+        min_width : block.min_width.clone().or_else(|| general.min_width.as_ref().map(MinWidth::from)),
+        align : block.align.or_else(|| general.align.map(Alignment::from)),
+        state : block.state,
+    }
+}
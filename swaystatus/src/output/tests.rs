@@ -0,0 +1,154 @@
+use super::*;
+use crate::plugin_database::test_helper::*;
+use crate::config::test_helper::*;
+use crate::plugin::BlockState;
+
+const TWO_ELEMENTS : &str = "[[Element]]\nPlugin = \"TestPlugin\"\n\n[Element.Config]\nlines = 2\nskull = \"bones\"\n\n[Element.General]\nBeforeText = \"<\"\nAfterText = \">\"\n\n[[Element]]\nPlugin = \"TestPlugin\"\n\n[Element.Config]\nlines = 5\nskull = \"pirate\"\n";
+
+fn write_to_string(is_first_line : bool, blocks : &[StatusBlock], settings : &SwaystatusMainConfig, elements : &[&SwaystatusPluginConfig]) -> String {
+    let mut out = Vec::new();
+    write_i3bar(&mut out, is_first_line, blocks, settings, &Theme::default(), elements).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn plain_output_joins_texts() {
+    let p = get_plugin_database_with_test_plugin();
+    let elements = get_elements_from_config(TWO_ELEMENTS, &p);
+    let elements : Vec<_> = elements.iter().collect();
+    let texts = vec![StatusBlock::from(String::from("a")), StatusBlock::from(String::from("b"))];
+    let mut out = Vec::new();
+    write_plain(&mut out, &texts, &SwaystatusMainConfig::default(), &Theme::default(), &elements).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "<a>, b\n");
+}
+
+#[test]
+fn i3bar_header_only_on_first_line() {
+    let p = get_plugin_database_with_test_plugin();
+    let elements = get_elements_from_config(TWO_ELEMENTS, &p);
+    let elements : Vec<_> = elements.iter().collect();
+    let settings = SwaystatusMainConfig { separator : String::new(), ..Default::default() };
+    let texts = vec![StatusBlock::from(String::from("a")), StatusBlock::from(String::from("b"))];
+    let first = write_to_string(true, &texts, &settings, &elements);
+    let second = write_to_string(false, &texts, &settings, &elements);
+    let blocks = "[{\"name\":\"TestPlugin\",\"instance\":\"0\",\"full_text\":\"<a>\",\"separator\":true,\"separator_block_width\":9},{\"name\":\"TestPlugin\",\"instance\":\"1\",\"full_text\":\"b\",\"separator\":true,\"separator_block_width\":9}]\n";
+    assert_eq!(first, format!("{{\"version\":1,\"click_events\":true}}\n[\n{}", blocks));
+    assert_eq!(second, format!(",{}", blocks));
+}
+
+#[test]
+fn named_elements_use_their_name_as_instance() {
+    let p = get_plugin_database_with_test_plugin();
+    let config = TWO_ELEMENTS.replace("BeforeText", "Name = \"bones\"\nBeforeText");
+    let elements = get_elements_from_config(&config, &p);
+    let elements : Vec<_> = elements.iter().collect();
+    let texts = vec![StatusBlock::from(String::from("a")), StatusBlock::from(String::from("b"))];
+    let blocks = make_i3bar_blocks(&texts, &SwaystatusMainConfig::default(), &Theme::default(), &elements);
+    assert_eq!(blocks.iter().map(|b| b.instance.as_str()).collect::<Vec<_>>(), ["bones", "bones", "1"]);
+}
+
+#[test]
+fn i3bar_separator_text_becomes_block() {
+    let p = get_plugin_database_with_test_plugin();
+    let elements = get_elements_from_config(TWO_ELEMENTS, &p);
+    let elements : Vec<_> = elements.iter().collect();
+    let settings = SwaystatusMainConfig::default();
+    let texts = vec![StatusBlock::from(String::from("a")), StatusBlock::from(String::from("b"))];
+    let blocks = make_i3bar_blocks(&texts, &settings, &Theme::default(), &elements);
+    assert_eq!(blocks.len(), 3);
+    assert_eq!(blocks[1].name, "separator");
+    assert_eq!(blocks[1].full_text, ", ");
+    assert!(blocks.iter().all(|b| !b.separator && b.separator_block_width == 0));
+}
+
+#[test]
+fn i3bar_block_with_all_fields() {
+    let p = get_plugin_database_with_test_plugin();
+    let elements = get_elements_from_config(TWO_ELEMENTS, &p);
+    let elements : Vec<_> = elements.iter().collect();
+    let settings = SwaystatusMainConfig { separator : String::new(), ..Default::default() };
+    let blocks = vec![StatusBlock {
+        full_text : String::from("full"),
+        short_text : Some(String::from("s")),
+        color : Some(String::from("#ff0000")),
+        background : Some(String::from("#000000")),
+        border : Some(String::from("#00ff00")),
+        urgent : true,
+        markup : true,
+        min_width : Some(MinWidth::Text(String::from("100%"))),
+        align : Some(Alignment::Center),
+        state : BlockState::Critical,
+    }];
+    let serialized = serde_json::to_string(&make_i3bar_blocks(&blocks, &settings, &Theme::default(), &elements)).unwrap();
+    assert_eq!(serialized, "[{\"name\":\"TestPlugin\",\"instance\":\"0\",\"full_text\":\"&lt;full&gt;\",\"short_text\":\"&lt;s&gt;\",\"color\":\"#ff0000\",\"background\":\"#000000\",\"border\":\"#00ff00\",\"urgent\":true,\"markup\":\"pango\",\"min_width\":\"100%\",\"align\":\"center\",\"separator\":true,\"separator_block_width\":9}]");
+}
+
+#[test]
+fn element_style_is_used_unless_plugin_sets_it() {
+    let p = get_plugin_database_with_test_plugin();
+    let config = "[[Element]]\nPlugin = \"TestPlugin\"\n\n[Element.Config]\nlines = 2\nskull = \"bones\"\n\n[Element.General]\nForeground = \"#111111\"\nBackground = \"#222222\"\nSeparator = false\nSeparatorBlockWidth = 3\nMinWidth = 50\nAlign = \"Right\"\n\n[[Element]]\nPlugin = \"TestPlugin\"\n\n[Element.Config]\nlines = 5\nskull = \"pirate\"\n";
+    let elements = get_elements_from_config(config, &p);
+    let elements : Vec<_> = elements.iter().collect();
+    let settings = SwaystatusMainConfig::default();
+    let blocks = vec![
+        StatusBlock { full_text : String::from("a"), color : Some(String::from("#ff0000")), ..Default::default() },
+        StatusBlock::from(String::from("b"))
+    ];
+    let result = make_i3bar_blocks(&blocks, &settings, &Theme::default(), &elements);
+    //no separator block, as the first element doesn't want one.
+    assert_eq!(result.len(), 2);
+    assert_eq!(result[0].color.as_deref(), Some("#ff0000"));
+    assert_eq!(result[0].background.as_deref(), Some("#222222"));
+    assert_eq!(result[0].separator_block_width, 3);
+    assert!(matches!(result[0].min_width, Some(MinWidth::Pixels(50))));
+    assert_eq!(result[0].align, Some(Alignment::Right));
+    assert_eq!(result[1].color, None);
+
+    let mut out = Vec::new();
+    write_plain(&mut out, &blocks, &settings, &Theme::default(), &elements).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "ab\n");
+}
+
+#[test]
+fn state_color_is_between_plugin_and_element_color() {
+    let p = get_plugin_database_with_test_plugin();
+    let config = "[[Element]]\nPlugin = \"TestPlugin\"\n\n[Element.Config]\nlines = 2\nskull = \"bones\"\n\n[Element.General]\nForeground = \"#111111\"\n";
+    let elements = get_elements_from_config(config, &p);
+    let elements : Vec<_> = elements.iter().collect();
+    let general = elements[0].get_non_plugin_settings();
+    let idle = StatusBlock::from(String::from("a"));
+    let warning = StatusBlock { state : BlockState::Warning, ..idle.clone() };
+    let explicit = StatusBlock { color : Some(String::from("#ff0000")), ..warning.clone() };
+    let theme = Theme::default();
+    assert_eq!(apply_element_style(&idle, general, &theme).color.as_deref(), Some("#111111"));
+    assert_eq!(apply_element_style(&warning, general, &theme).color.as_deref(), theme.palette.state_color(BlockState::Warning));
+    assert_eq!(apply_element_style(&explicit, general, &theme).color.as_deref(), Some("#ff0000"));
+}
+
+#[test]
+fn theme_is_applied_to_elements_and_separators() {
+    let p = get_plugin_database_with_test_plugin();
+    let elements = get_elements_from_config(TWO_ELEMENTS, &p);
+    let elements : Vec<_> = elements.iter().collect();
+    let settings = SwaystatusMainConfig::default();
+    let theme = Theme::from(crate::theme::ThemeConfig { palette : crate::theme::BuiltinPalette::Gruvbox, icon_set : crate::theme::BuiltinIconSet::Ascii, ..Default::default() });
+    let blocks = vec![StatusBlock::from(String::from("{icon:volume_muted}")), StatusBlock::from(String::from("b"))];
+    let result = make_i3bar_blocks(&blocks, &settings, &theme, &elements);
+    assert_eq!(result[0].full_text, "<mute>");
+    assert_eq!(result[0].color, theme.palette.idle);
+    assert_eq!(result[1].color, theme.palette.separator);
+}
+
+#[test]
+fn texts_around_markup_are_escaped() {
+    let p = get_plugin_database_with_test_plugin();
+    let elements = get_elements_from_config(TWO_ELEMENTS, &p);
+    let general = elements[0].get_non_plugin_settings();
+    let icons = [(String::from("amp"), String::from("&"))].into_iter().collect();
+    let theme = Theme::from(crate::theme::ThemeConfig { icons, ..Default::default() });
+    let plain = StatusBlock::from(String::from("{icon:amp}"));
+    let markup = StatusBlock { full_text : String::from("<b>{icon:amp}</b>"), markup : true, ..Default::default() };
+    assert_eq!(apply_element_style(&plain, general, &theme).full_text, "<&>");
+    assert_eq!(apply_element_style(&markup, general, &theme).full_text, "&lt;<b>&amp;</b>&gt;");
+    assert_eq!(escape_markup("\"a\" & 'b'"), "&quot;a&quot; &amp; &apos;b&apos;");
+}
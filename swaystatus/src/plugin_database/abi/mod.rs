@@ -0,0 +1,268 @@
+//! Adapters that let the rest of the program use plugins with the stable interface, see
+//! `swaystatus_plugin::abi`, exactly like plugins that export Rust trait objects.
+
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::sync::Mutex;
+use serde::{Serialize, Serializer, Deserialize};
+use gettextrs::*;
+use swaystatus_plugin::abi::*;
+use crate::plugin;
+
+#[cfg(test)]
+mod tests;
+
+/// Modules of plugins built for version 1.0 end before `get_config_schema`.
+const MODULE_SIZE_1_0 : usize = std::mem::offset_of!(AbiModule, get_config_schema);
+/// Instances of plugins built for version 1.0 end before `validate`.
+const INSTANCE_SIZE_1_0 : usize = std::mem::offset_of!(AbiInstance, validate);
+/// Runnables haven't changed since version 1.0.
+const RUNNABLE_SIZE_1_0 : usize = std::mem::size_of::<AbiRunnable>();
+/// The senders of plugins built for version 1.0 end before `send_custom_message`.
+const FROM_MAIN_SIZE_1_0 : usize = std::mem::offset_of!(AbiFromMain, send_custom_message);
+
+/// Memory a plugin writes an object to. It's zeroed, so functions the plugin doesn't know about
+/// are None.
+struct OutBuffer<T>(MaybeUninit<T>);
+
+impl<T> OutBuffer<T> {
+    fn new() -> Self {
+        OutBuffer(MaybeUninit::zeroed())
+    }
+    fn as_mut_ptr(&mut self) -> *mut T {
+        self.0.as_mut_ptr()
+    }
+    /// Returns the object, if the plugin wrote at least `min_size` bytes of it, which have to
+    /// cover all functions that aren't optional.
+    ///
+    /// # Safety
+    /// `T` must be one of the objects of the interface, which all start with their size, and the
+    /// plugin must have kept the contract when writing it.
+    unsafe fn take(self, min_size : usize) -> Option<T> {
+        let size = self.0.as_ptr().cast::<usize>().read();
+        (size >= min_size).then(|| self.0.assume_init())
+    }
+}
+
+pub struct ImportedModule<'p> {
+    module : AbiModule,
+    name : String,
+    _library : PhantomData<&'p ()>,
+}
+
+impl<'p> ImportedModule<'p> {
+    /// Creates the module of a plugin with its exported `_swaystatus_abi_module_create()`. Returns
+    /// None if it lacks functions.
+    ///
+    /// # Safety
+    /// The module must follow the interface contract, and the library it came from must stay
+    /// loaded for `'p`.
+    pub unsafe fn new(create : unsafe extern "C" fn(*mut AbiModule)) -> Option<Self> {
+        let mut module = OutBuffer::new();
+        create(module.as_mut_ptr());
+        //A module that lacks functions can't even be destroyed, so it's leaked.
+        let module = module.take(MODULE_SIZE_1_0)?;
+        let name = (module.get_name)(module.data).to_str_lossy().into_owned();
+        Some(ImportedModule { module, name, _library : PhantomData })
+    }
+
+    fn wrap_instance(&self, instance : OutBuffer<AbiInstance>) -> Option<ImportedInstance<'_>> {
+        unsafe { instance.take(INSTANCE_SIZE_1_0) }.map(|instance| ImportedInstance { instance, _module : PhantomData })
+    }
+}
+
+impl<'p> Drop for ImportedModule<'p> {
+    fn drop(&mut self) {
+        unsafe { (self.module.destroy)(self.module.data) }
+    }
+}
+
+impl<'p> plugin::SwayStatusModule for ImportedModule<'p> {
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn deserialize_config<'de, 'q>(&'q self, deserializer : &mut (dyn erased_serde::Deserializer + 'de)) -> Result<Box<dyn plugin::SwayStatusModuleInstance + 'q>, erased_serde::Error> {
+        let config = serde_json::Value::deserialize(deserializer)?.to_string();
+        let mut error = String::new();
+        let mut instance = OutBuffer::new();
+        if !unsafe { (self.module.create_instance)(self.module.data, AbiStr::new(&config), AbiStringSink::for_string(&mut error), instance.as_mut_ptr()) } {
+            return Err(serde::de::Error::custom(error));
+        }
+        match self.wrap_instance(instance) {
+            Some(i) => Ok(Box::new(i)),
+            None => Err(serde::de::Error::custom(gettext!("Plugin {} returned an incomplete instance.", self.name)))
+        }
+    }
+    fn get_default_config<'q>(&'q self) -> Box<dyn plugin::SwayStatusModuleInstance + 'q> {
+        let mut instance = OutBuffer::new();
+        unsafe { (self.module.default_instance)(self.module.data, instance.as_mut_ptr()) };
+        match self.wrap_instance(instance) {
+            Some(i) => Box::new(i),
+            None => panic!("{}", gettext!("Plugin {} failed to create its default configuration.", self.name))
+        }
+    }
+    fn print_help(&self) {
+        unsafe { (self.module.print_help)(self.module.data) }
+    }
+    fn get_config_schema(&self) -> Option<String> {
+        let get_config_schema = self.module.get_config_schema?;
+        let mut json = String::new();
+        unsafe { get_config_schema(self.module.data, AbiStringSink::for_string(&mut json)) }.then_some(json)
+    }
+}
+
+struct ImportedInstance<'p> {
+    instance : AbiInstance,
+    _module : PhantomData<&'p ()>,
+}
+
+impl<'p> Drop for ImportedInstance<'p> {
+    fn drop(&mut self) {
+        unsafe { (self.instance.destroy)(self.instance.data) }
+    }
+}
+
+impl<'p> Serialize for ImportedInstance<'p> {
+    fn serialize<S : Serializer>(&self, serializer : S) -> Result<S::Ok, S::Error> {
+        let mut json = String::new();
+        if !unsafe { (self.instance.serialize_config)(self.instance.data, AbiStringSink::for_string(&mut json)) } {
+            return Err(serde::ser::Error::custom(gettext("The plugin failed to serialize its configuration.")));
+        }
+        let value : serde_json::Value = serde_json::from_str(&json).map_err(serde::ser::Error::custom)?;
+        value.serialize(serializer)
+    }
+}
+
+impl<'p> plugin::SwayStatusModuleInstance for ImportedInstance<'p> {
+    fn make_runnable<'q>(&'q self, to_main : Box<dyn plugin::MsgModuleToMain + 'q>) -> (Box<dyn plugin::SwayStatusModuleRunnable + 'q>, Box<dyn plugin::MsgMainToModule + 'q>) {
+        let to_main = AbiToMain {
+            size : std::mem::size_of::<AbiToMain>(),
+            data : Box::into_raw(Box::new(Mutex::new(to_main))) as *mut c_void,
+            send_update : to_main_send_update,
+            destroy : to_main_destroy,
+        };
+        let mut runnable = OutBuffer::new();
+        let mut from_main = OutBuffer::new();
+        unsafe { (self.instance.make_runnable)(self.instance.data, to_main, runnable.as_mut_ptr(), from_main.as_mut_ptr()) };
+        match unsafe { (runnable.take(RUNNABLE_SIZE_1_0), from_main.take(FROM_MAIN_SIZE_1_0)) } {
+            (Some(runnable), Some(from_main)) => (
+                Box::new(ImportedRunnable { runnable, _instance : PhantomData }),
+                Box::new(ImportedFromMain { from_main, _instance : PhantomData })
+            ),
+            //Can't be used, and the incomplete one can't even be destroyed. As it might still
+            //refer to the other one, both are leaked.
+            _ => (Box::new(IncompleteRunnable), Box::new(IncompleteFromMain))
+        }
+    }
+    fn validate(&self) -> Vec<String> {
+        let Some(validate) = self.instance.validate else {
+            return Vec::new();
+        };
+        let mut json = String::new();
+        if !unsafe { validate(self.instance.data, AbiStringSink::for_string(&mut json)) } {
+            return vec![gettext("The plugin failed to check its configuration.")];
+        }
+        serde_json::from_str(&json).unwrap_or_else(|e| vec![gettext!("The plugin reported its problems in an invalid format: {}", e)])
+    }
+}
+
+type BoxedToMain<'p> = Mutex<Box<dyn plugin::MsgModuleToMain + 'p>>;
+
+unsafe extern "C" fn to_main_send_update(data : *mut c_void, kind : AbiUpdateKind, payload : AbiStr) -> bool {
+    let to_main = &*(data as *const BoxedToMain);
+    let payload = payload.to_str_lossy().into_owned();
+    let update = match kind {
+        AbiUpdateKind::TEXT => Ok(plugin::StatusBlock::from(payload)),
+        AbiUpdateKind::BLOCK => serde_json::from_str(&payload)
+            .map_err(|e| plugin::PluginError::PrintToStdErr(gettext!("The plugin sent an invalid block: {}", e))),
+        AbiUpdateKind::PRINT_TO_STDERR => Err(plugin::PluginError::PrintToStdErr(payload)),
+        AbiUpdateKind::SHOW_INSTEAD_OF_TEXT => Err(plugin::PluginError::ShowInsteadOfText(payload)),
+        AbiUpdateKind(other) => Err(plugin::PluginError::PrintToStdErr(gettext!("The plugin sent an update of unknown kind {}.", other)))
+    };
+    to_main.lock().unwrap_or_else(|e| e.into_inner()).send_block_update(update).is_ok()
+}
+
+unsafe extern "C" fn to_main_destroy(data : *mut c_void) {
+    drop(Box::from_raw(data as *mut BoxedToMain));
+}
+
+struct ImportedRunnable<'p> {
+    runnable : AbiRunnable,
+    _instance : PhantomData<&'p ()>,
+}
+
+// The interface requires the runnable to be movable to a worker thread.
+unsafe impl<'p> Send for ImportedRunnable<'p> {}
+
+impl<'p> plugin::SwayStatusModuleRunnable for ImportedRunnable<'p> {
+    fn run(&self) {
+        if !unsafe { (self.runnable.run)(self.runnable.data) } {
+            //The plugin already reported the details. Only the crash itself is passed on.
+            std::panic::resume_unwind(Box::new(gettext("The plugin crashed.")));
+        }
+    }
+}
+
+impl<'p> Drop for ImportedRunnable<'p> {
+    fn drop(&mut self) {
+        unsafe { (self.runnable.destroy)(self.runnable.data) }
+    }
+}
+
+/// Stands in for a runnable that lacks functions. Reported as a crash of the element.
+struct IncompleteRunnable;
+
+impl plugin::SwayStatusModuleRunnable for IncompleteRunnable {
+    fn run(&self) {
+        panic!("{}", gettext("The plugin returned an incomplete runnable."));
+    }
+}
+
+struct IncompleteFromMain;
+
+impl plugin::MsgMainToModule for IncompleteFromMain {
+    fn send_quit(&self) -> Result<(),plugin::PluginCommunicationError> {
+        Err(plugin::PluginCommunicationError)
+    }
+    fn send_refresh(&self) -> Result<(),plugin::PluginCommunicationError> {
+        Err(plugin::PluginCommunicationError)
+    }
+}
+
+struct ImportedFromMain<'p> {
+    from_main : AbiFromMain,
+    _instance : PhantomData<&'p ()>,
+}
+
+impl<'p> ImportedFromMain<'p> {
+    fn result(ok : bool) -> Result<(), plugin::PluginCommunicationError> {
+        if ok { Ok(()) } else { Err(plugin::PluginCommunicationError) }
+    }
+}
+
+impl<'p> plugin::MsgMainToModule for ImportedFromMain<'p> {
+    fn send_quit(&self) -> Result<(),plugin::PluginCommunicationError> {
+        Self::result(unsafe { (self.from_main.send_quit)(self.from_main.data) })
+    }
+    fn send_refresh(&self) -> Result<(),plugin::PluginCommunicationError> {
+        Self::result(unsafe { (self.from_main.send_refresh)(self.from_main.data) })
+    }
+    fn send_click_event(&self, event : &plugin::ClickEvent) -> Result<(),plugin::PluginCommunicationError> {
+        let modifiers : Vec<AbiStr> = event.modifiers.iter().map(|m| AbiStr::new(m)).collect();
+        let event = export_click_event(event, &modifiers);
+        Self::result(unsafe { (self.from_main.send_click_event)(self.from_main.data, &event) })
+    }
+    fn send_custom_message(&self, message : &str) -> Result<(),plugin::PluginCommunicationError> {
+        match self.from_main.send_custom_message {
+            Some(send_custom_message) => Self::result(unsafe { send_custom_message(self.from_main.data, AbiStr::new(message)) }),
+            None => Ok(())
+        }
+    }
+}
+
+impl<'p> Drop for ImportedFromMain<'p> {
+    fn drop(&mut self) {
+        unsafe { (self.from_main.destroy)(self.from_main.data) }
+    }
+}
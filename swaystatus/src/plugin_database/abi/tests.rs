@@ -0,0 +1,204 @@
+use super::*;
+use std::sync::Arc;
+use plugin::SwayStatusModule;
+
+struct EchoPlugin;
+
+#[derive(Serialize, Deserialize)]
+struct EchoConfig {
+    text : String,
+    crash : bool,
+}
+
+struct EchoRunnable<'p> {
+    config : &'p EchoConfig,
+    to_main : Box<dyn plugin::MsgModuleToMain + 'p>,
+}
+
+struct EchoSender;
+
+impl plugin::SwayStatusModule for EchoPlugin {
+    fn get_name(&self) -> &str {
+        "EchoPlugin"
+    }
+    fn deserialize_config<'de>(&self, deserializer : &mut (dyn erased_serde::Deserializer + 'de)) -> Result<Box<dyn plugin::SwayStatusModuleInstance>, erased_serde::Error> {
+        let result : EchoConfig = erased_serde::deserialize(deserializer)?;
+        Ok(Box::new(result))
+    }
+    fn get_default_config(&self) -> Box<dyn plugin::SwayStatusModuleInstance> {
+        Box::new(EchoConfig { text : String::from("echo"), crash : false })
+    }
+    fn print_help(&self) {}
+    fn get_config_schema(&self) -> Option<String> {
+        Some(serde_json::json!({ "type" : "object", "required" : ["text", "crash"] }).to_string())
+    }
+}
+
+impl plugin::SwayStatusModuleInstance for EchoConfig {
+    fn make_runnable<'p>(&'p self, to_main : Box<dyn plugin::MsgModuleToMain + 'p>) -> (Box<dyn plugin::SwayStatusModuleRunnable + 'p>, Box<dyn plugin::MsgMainToModule + 'p>) {
+        (Box::new(EchoRunnable { config : self, to_main }), Box::new(EchoSender))
+    }
+}
+
+impl<'p> plugin::SwayStatusModuleRunnable for EchoRunnable<'p> {
+    fn run(&self) {
+        assert!(!self.config.crash, "crash requested");
+        self.to_main.send_update(Ok(self.config.text.clone())).unwrap();
+        self.to_main.send_block_update(Ok(plugin::StatusBlock { full_text : self.config.text.clone(), state : plugin::BlockState::Warning, ..Default::default() })).unwrap();
+        self.to_main.send_update(Err(plugin::PluginError::ShowInsteadOfText(String::from("bye")))).unwrap();
+    }
+}
+
+impl plugin::MsgMainToModule for EchoSender {
+    fn send_quit(&self) -> Result<(),plugin::PluginCommunicationError> {
+        Ok(())
+    }
+    fn send_refresh(&self) -> Result<(),plugin::PluginCommunicationError> {
+        Err(plugin::PluginCommunicationError)
+    }
+    fn send_click_event(&self, event : &plugin::ClickEvent) -> Result<(),plugin::PluginCommunicationError> {
+        if event.button == plugin::MouseButton::ScrollUp && event.modifiers == ["Shift"] && event.relative_x == 3 {
+            Ok(())
+        }
+        else {
+            Err(plugin::PluginCommunicationError)
+        }
+    }
+    fn send_custom_message(&self, message : &str) -> Result<(),plugin::PluginCommunicationError> {
+        if message == "ping" { Ok(()) } else { Err(plugin::PluginCommunicationError) }
+    }
+}
+
+struct CollectingSender {
+    blocks : Arc<Mutex<Vec<Result<plugin::StatusBlock, plugin::PluginError>>>>,
+}
+
+impl plugin::MsgModuleToMain for CollectingSender {
+    fn send_block_update(&self, block : Result<plugin::StatusBlock, plugin::PluginError>) -> Result<(),plugin::PluginCommunicationError> {
+        self.blocks.lock().unwrap().push(block);
+        Ok(())
+    }
+}
+
+unsafe extern "C" fn create_echo_module(module : *mut AbiModule) {
+    module.write(export_module(Box::new(EchoPlugin)));
+}
+
+/// Acts like a plugin built for version 1.0, which writes less than the main program expects.
+unsafe extern "C" fn create_echo_module_1_0(module : *mut AbiModule) {
+    let exported = export_module(Box::new(EchoPlugin));
+    std::ptr::copy_nonoverlapping(&exported as *const AbiModule as *const u8, module as *mut u8, MODULE_SIZE_1_0);
+    (*module).size = MODULE_SIZE_1_0;
+}
+
+fn import_echo_plugin() -> ImportedModule<'static> {
+    unsafe { ImportedModule::new(create_echo_module).unwrap() }
+}
+
+fn deserialize_echo_config<'p>(module : &'p ImportedModule, toml_text : &str) -> Result<Box<dyn plugin::SwayStatusModuleInstance + 'p>, erased_serde::Error> {
+    let value : toml::Value = toml::from_str(toml_text).unwrap();
+    module.deserialize_config(&mut <dyn erased_serde::Deserializer>::erase(value))
+}
+
+#[test]
+fn names_and_configs_pass_the_interface() {
+    let module = import_echo_plugin();
+    assert_eq!(module.get_name(), "EchoPlugin");
+    let instance = deserialize_echo_config(&module, "text = \"hi\"\ncrash = false").unwrap();
+    assert_eq!(serde_json::to_value(&instance).unwrap(), serde_json::json!({ "text" : "hi", "crash" : false }));
+    let default = module.get_default_config();
+    assert_eq!(serde_json::to_value(&default).unwrap(), serde_json::json!({ "text" : "echo", "crash" : false }));
+}
+
+#[test]
+fn schemas_pass_the_interface() {
+    let module = import_echo_plugin();
+    let schema : serde_json::Value = serde_json::from_str(&module.get_config_schema().unwrap()).unwrap();
+    assert_eq!(schema["required"], serde_json::json!(["text", "crash"]));
+    //A plugin built for 1.0 doesn't have the function yet.
+    let old = unsafe { ImportedModule::new(create_echo_module_1_0).unwrap() };
+    assert_eq!(old.get_name(), "EchoPlugin");
+    assert!(old.get_config_schema().is_none());
+}
+
+#[test]
+fn config_errors_are_reported() {
+    let module = import_echo_plugin();
+    let error = deserialize_echo_config(&module, "text = \"hi\"").err().unwrap();
+    assert!(error.to_string().contains("crash"));
+}
+
+#[test]
+fn updates_and_messages_pass_the_interface() {
+    let module = import_echo_plugin();
+    let instance = deserialize_echo_config(&module, "text = \"hi\"\ncrash = false").unwrap();
+    let blocks = Arc::new(Mutex::new(Vec::new()));
+    let (runnable, from_main) = instance.make_runnable(Box::new(CollectingSender { blocks : blocks.clone() }));
+    runnable.run();
+    let blocks = blocks.lock().unwrap();
+    assert_eq!(blocks.len(), 3);
+    assert_eq!(blocks[0].as_ref().unwrap(), &plugin::StatusBlock::from(String::from("hi")));
+    assert_eq!(blocks[1].as_ref().unwrap().state, plugin::BlockState::Warning);
+    assert!(matches!(&blocks[2], Err(plugin::PluginError::ShowInsteadOfText(t)) if t == "bye"));
+
+    assert!(from_main.send_quit().is_ok());
+    assert!(from_main.send_refresh().is_err());
+    let click = plugin::ClickEvent { button : plugin::MouseButton::ScrollUp, modifiers : vec![String::from("Shift")], x : 1, y : 2, relative_x : 3, relative_y : 4, width : 5, height : 6 };
+    assert!(from_main.send_click_event(&click).is_ok());
+    assert!(from_main.send_custom_message("ping").is_ok());
+    assert!(from_main.send_custom_message("pong").is_err());
+}
+
+#[test]
+fn blocks_are_read_as_i3bar_json() {
+    let blocks = Arc::new(Mutex::new(Vec::new()));
+    let to_main : BoxedToMain = Mutex::new(Box::new(CollectingSender { blocks : blocks.clone() }));
+    let data = &to_main as *const BoxedToMain as *mut c_void;
+    //What a plugin written in C would send, see swaybar-protocol(7).
+    let json = r##"{"full_text":"50%","color":"#00ff00","markup":"pango","min_width":"100%","align":"left","separator":true,"state":"Good"}"##;
+    assert!(unsafe { to_main_send_update(data, AbiUpdateKind::BLOCK, AbiStr::new(json)) });
+    assert!(unsafe { to_main_send_update(data, AbiUpdateKind::BLOCK, AbiStr::new(r#"{"full_text":"a","markup":true}"#)) });
+    let blocks = blocks.lock().unwrap();
+    assert_eq!(blocks[0].as_ref().unwrap(), &plugin::StatusBlock {
+        full_text : String::from("50%"),
+        color : Some(String::from("#00ff00")),
+        markup : true,
+        min_width : Some(plugin::MinWidth::Text(String::from("100%"))),
+        align : Some(plugin::Alignment::Left),
+        state : plugin::BlockState::Good,
+        ..Default::default()
+    });
+    assert!(matches!(&blocks[1], Err(plugin::PluginError::PrintToStdErr(_))));
+}
+
+/// The make_runnable of the exported EchoPlugin, wrapped by `truncated_make_runnable`.
+static EXPORTED_MAKE_RUNNABLE : std::sync::OnceLock<unsafe extern "C" fn(*mut c_void, AbiToMain, *mut AbiRunnable, *mut AbiFromMain)> = std::sync::OnceLock::new();
+
+/// Acts like a broken plugin, whose sender is too small to even hold the functions of 1.0.
+unsafe extern "C" fn truncated_make_runnable(data : *mut c_void, to_main : AbiToMain, runnable : *mut AbiRunnable, from_main : *mut AbiFromMain) {
+    EXPORTED_MAKE_RUNNABLE.get().unwrap()(data, to_main, runnable, from_main);
+    (*from_main).size = std::mem::offset_of!(AbiFromMain, send_click_event);
+}
+
+#[test]
+fn incomplete_senders_are_rejected() {
+    let module = import_echo_plugin();
+    let config = serde_json::json!({ "text" : "hi", "crash" : false }).to_string();
+    let mut instance = OutBuffer::new();
+    assert!(unsafe { (module.module.create_instance)(module.module.data, AbiStr::new(&config), AbiStringSink::for_string(&mut String::new()), instance.as_mut_ptr()) });
+    let mut instance = module.wrap_instance(instance).unwrap();
+    EXPORTED_MAKE_RUNNABLE.get_or_init(|| instance.instance.make_runnable);
+    instance.instance.make_runnable = truncated_make_runnable;
+    let (runnable, from_main) = plugin::SwayStatusModuleInstance::make_runnable(&instance, Box::new(CollectingSender { blocks : Arc::new(Mutex::new(Vec::new())) }));
+    assert!(from_main.send_quit().is_err());
+    assert!(from_main.send_refresh().is_err());
+    assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| runnable.run())).is_err());
+}
+
+#[test]
+fn crashes_are_passed_on() {
+    let module = import_echo_plugin();
+    let instance = deserialize_echo_config(&module, "text = \"hi\"\ncrash = true").unwrap();
+    let (runnable, _from_main) = instance.make_runnable(Box::new(CollectingSender { blocks : Arc::new(Mutex::new(Vec::new())) }));
+    assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| runnable.run())).is_err());
+}
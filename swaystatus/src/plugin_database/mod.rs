@@ -1,86 +1,329 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use super::plugin;
 use libloading::{Library};
 use gettextrs::*;
+use crate::exec::ExecPlugin;
+use crate::config::PluginSourceConfig;
+
+mod abi;
 
 pub struct PluginDatabase<'p> {
-   plugins : HashMap<String, Box<dyn plugin::SwayStatusModule + 'p>>
+   plugins : HashMap<String, Box<dyn plugin::SwayStatusModule + 'p>>,
+   /// The file each plugin was loaded from. Needed to load it again in a plugin host process.
+   library_paths : HashMap<String, std::path::PathBuf>,
+   /// The optional capabilities each plugin advertised.
+   features : HashMap<String, plugin::PluginFeatures>
 }
 
 impl<'a> PluginDatabase<'a> {
     pub fn get_plugin<'b>(&'a self, name : &'b str) -> Option<&(dyn plugin::SwayStatusModule + 'a)> {
         self.plugins.get(name).map(|x| &**x)
     }
+    pub fn get_library_path(&self, name : &str) -> Option<&std::path::Path> {
+        self.library_paths.get(name).map(|x| &**x)
+    }
+    /// Plugins that advertised nothing, or aren't known at all, get no optional capabilities.
+    pub fn get_features(&self, name : &str) -> plugin::PluginFeatures {
+        self.features.get(name).copied().unwrap_or_default()
+    }
     pub fn get_name_and_plugin_iterator(&'a self) -> impl Iterator<Item = (&'a String,&'a Box<dyn plugin::SwayStatusModule +'a>)> + 'a {
         self.plugins.iter()
     }
     pub fn new<'b : 'a>(libs : &'b Libraries) -> PluginDatabase<'a> {
-        PluginDatabase {
-            plugins : libs.libs.iter().filter_map(|(path, lib)| {
-                match get_plugin_from_library(lib) {
-                    Ok(x) => Some((String::from(x.get_name()), x)),
-                    Err(y) => {
-                        let lib_name = path.to_string_lossy();
-                        match y {
-                            PluginLoadingError::MissingVersionInformation => {
-                                eprintln!("{}", gettext!("Failed to load library {}, no version information found.", lib_name));
-                            },
-                            PluginLoadingError::WrongPluginVersion { expected, version } => {
-                                eprintln!("{}", gettext!("Failed to load library {}, it was built with the wrong plugin version. Expected version {}, found version {}", lib_name, expected, version));
-                            }
-                            PluginLoadingError::WrongRustcVersion { expected, version } => {
-                                eprintln!("{}", gettext!("Failed to load library {}, it was built with a different Rust version. Since there is no ABI stability guaranteed, this safeguard is required. Please make sure this program and all plugins use the same compiler version. Expected the Rust version {}, found version {}", lib_name, expected, version));
-                            }
-                            PluginLoadingError::NoConstructor => {
-
-                                eprintln!("{}", gettext!("Failed to load library {}, it does not export the _swaystatus_module_create() function.", lib_name));
-                            }
-                        }
-                        None
+        let mut database = PluginDatabase { plugins : HashMap::new(), library_paths : HashMap::new(), features : HashMap::new() };
+        for (path, lib) in &libs.libs {
+            match get_plugin_from_library(lib) {
+                Ok((x, f)) => {
+                    if let Some(exported) = libs.names.get(path).filter(|n| *n != x.get_name()) {
+                        eprintln!("{}", gettext!("The library {} exports the name {}, but contains the plugin {}. Configurations that use {} might not find it.", path.display(), exported, x.get_name(), x.get_name()));
+                    }
+                    //Libraries are sorted by precedence, the first plugin with a given name wins.
+                    if let Some(first) = database.library_paths.get(x.get_name()) {
+                        eprintln!("{}", gettext!("The plugin {} was found in both {} and {}. Using the one in {}. The [Plugins] section of the configuration can pick a library explicitly.", x.get_name(), first.display(), path.display(), first.display()));
+                        continue;
                     }
+                    database.insert(String::from(x.get_name()), x, f, Some(path.clone()));
+                },
+                Err(y) => print_plugin_loading_error(path, y)
+            }
+        }
+        //A library with the same name as a built-in plugin wins. That way a plugin can be updated
+        //without rebuilding the main program.
+        for (builtin, builtin_features) in builtin_plugins() {
+            if !database.plugins.contains_key(builtin.get_name()) {
+                database.insert(String::from(builtin.get_name()), builtin, builtin_features, None);
+            }
+        }
+        database
+    }
+    /// Like new(), but with the entries of the config's [Plugins] section on top. Each entry gets
+    /// its own module, so the same plugin can be used under several names.
+    pub fn with_sources<'b : 'a>(libs : &'b Libraries, sources : &BTreeMap<String, PluginSourceConfig>) -> PluginDatabase<'a> {
+        let mut database = PluginDatabase::new(libs);
+        for (name, source) in sources {
+            if let Some((module, features, path)) = database.create_from_source(libs, name, source) {
+                database.insert(name.clone(), module, features, path);
+            }
+        }
+        database
+    }
+    fn create_from_source<'b : 'a>(&self, libs : &'b Libraries, name : &str, source : &PluginSourceConfig) -> Option<(Box<dyn plugin::SwayStatusModule + 'a>, plugin::PluginFeatures, Option<std::path::PathBuf>)> {
+        let wanted = source.plugin.as_deref().unwrap_or(name);
+        let path = match source.library.as_ref().or_else(|| self.library_paths.get(wanted)) {
+            Some(path) => path,
+            None => {
+                let builtin = builtin_plugins().into_iter().find(|(b, _)| b.get_name() == wanted);
+                if builtin.is_none() {
+                    eprintln!("{}", gettext!("The plugin {}, that the entry {} of the [Plugins] section refers to, was not found.", wanted, name));
                 }
+                return builtin.map(|(b, f)| (b, f, None));
+            }
+        };
+        //If the library couldn't be loaded, that has already been reported.
+        let (_, lib) = libs.pinned.iter().chain(libs.libs.iter()).find(|(p, _)| p == path)?;
+        match get_plugin_from_library(lib) {
+            Ok((x, _)) if x.get_name() != wanted => {
+                eprintln!("{}", gettext!("The library {} contains the plugin {}, but the entry {} of the [Plugins] section expects the plugin {}.", path.display(), x.get_name(), name, wanted));
+                None
+            },
+            Ok((x, f)) => Some((x, f, Some(path.clone()))),
+            Err(y) => {
+                print_plugin_loading_error(path, y);
+                None
+            }
+        }
+    }
+    fn insert(&mut self, name : String, module : Box<dyn plugin::SwayStatusModule + 'a>, features : plugin::PluginFeatures, path : Option<std::path::PathBuf>) {
+        match path {
+            Some(path) => self.library_paths.insert(name.clone(), path),
+            None => self.library_paths.remove(&name)
+        };
+        self.features.insert(name.clone(), features);
+        self.plugins.insert(name, module);
+    }
+}
 
-            }).collect()
+fn print_plugin_loading_error(path : &std::path::Path, error : PluginLoadingError) {
+    let lib_name = path.to_string_lossy();
+    match error {
+        PluginLoadingError::MissingVersionInformation => {
+            eprintln!("{}", gettext!("Failed to load library {}, no version information found.", lib_name));
+        },
+        PluginLoadingError::WrongPluginVersion { expected, version } => {
+            eprintln!("{}", gettext!("Failed to load library {}, it was built against a different version of swaystatus-plugin. Plugins that export Rust trait objects need exactly the same version, since any change to the traits changes their layout. Expected version {}, found version {}", lib_name, expected, version));
+        }
+        PluginLoadingError::WrongRustcVersion { expected, version } => {
+            eprintln!("{}", gettext!("Failed to load library {}, it was built with a different Rust version. Since there is no ABI stability guaranteed, this safeguard is required. Please make sure this program and all plugins use the same compiler version. Expected the Rust version {}, found version {}", lib_name, expected, version));
+        }
+        PluginLoadingError::WrongAbiVersion { expected, version } => {
+            eprintln!("{}", gettext!("Failed to load library {}, it uses an incompatible version of the stable plugin interface. Expected version {}.{} or older, found version {}.{}", lib_name, expected.0, expected.1, version.0, version.1));
+        }
+        PluginLoadingError::IncompleteAbiModule => {
+            eprintln!("{}", gettext!("Failed to load library {}, the module it exports is incomplete.", lib_name));
+        }
+        PluginLoadingError::NoConstructor => {
+            eprintln!("{}", gettext!("Failed to load library {}, it exports neither the _swaystatus_module_create() nor the _swaystatus_abi_module_create() function.", lib_name));
         }
     }
 }
 
+/// Plugins compiled into this program. The Exec plugin is always there, the others depend on the
+/// builtin-* cargo features. Built-in plugins don't have a library, so they always run in a
+/// thread of the main process.
+fn builtin_plugins() -> Vec<(Box<dyn plugin::SwayStatusModule>, plugin::PluginFeatures)> {
+    #[allow(unused_mut)]
+    let mut result : Vec<(Box<dyn plugin::SwayStatusModule>, plugin::PluginFeatures)> = vec![(Box::new(ExecPlugin), ExecPlugin::FEATURES)];
+    #[cfg(feature = "builtin-clock")]
+    result.push((Box::new(swaystatus_clock::ClockPlugin::new()), swaystatus_clock::ClockPlugin::FEATURES));
+    #[cfg(feature = "builtin-pulse")]
+    result.push((Box::new(swaystatus_pulse::PulseVolumePlugin::new()), swaystatus_pulse::PulseVolumePlugin::FEATURES));
+    #[cfg(feature = "builtin-alsa")]
+    result.push((Box::new(swaystatus_alsa::AlsaVolumePlugin::new()), swaystatus_alsa::AlsaVolumePlugin::FEATURES));
+    result
+}
+
 pub struct Libraries {
-    libs : Vec<(std::path::PathBuf,Library)>
+    /// The libraries found in the plugin folders, in order of precedence.
+    libs : Vec<(std::path::PathBuf,Library)>,
+    /// Libraries named in the config's [Plugins] section. Only used for the entries naming them.
+    pinned : Vec<(std::path::PathBuf,Library)>,
+    /// The names the libraries in `libs` export, if they do.
+    names : HashMap<std::path::PathBuf, String>
 }
 impl Libraries {
-    pub fn load_from_folder(path : &std::path::Path) -> Result<Libraries, std::io::Error> {
-        Ok(Libraries {
-            libs : path.read_dir()?.filter_map(|f| {
-                match f {
-                    Err(e) => {
-                        eprintln!("{}", gettext!("File I/O error while iterating libraries: {}", e.to_string()));
-                        None
-                    },
-                    Ok(d) => unsafe {
-                        let p = d.path();
-                        match libloading::Library::new(&p) {
-                            Ok(x) => Some((p,x)),
-                            Err(_) => {
-                                eprintln!("{}", gettext!("Failed to load as library: {}", d.path().display()));
-                                None
-                            }
-                        }
+    /// Loads just the given library. Used by the plugin host process.
+    pub fn load_single(path : &std::path::Path) -> Result<Libraries, libloading::Error> {
+        let lib = unsafe { libloading::Library::new(path)? };
+        Ok(Libraries { libs : vec![(path.to_path_buf(), lib)], pinned : Vec::new(), names : HashMap::new() })
+    }
+    /// Loads the plugin libraries in the given folders and all their subfolders, in order of
+    /// precedence. With a selection of plugin names, libraries whose exported name doesn't match
+    /// are not loaded at all. Neither are libraries exporting a name that a library found earlier
+    /// already exports. A folder that can't be read is reported and skipped.
+    pub fn load_from_folders(folders : &[std::path::PathBuf], selection : &PluginSelection) -> Libraries {
+        let mut libs = Vec::new();
+        let mut names = HashMap::new();
+        //The library each exported name was first found in. Later ones are shadowed by it.
+        let mut exporters : HashMap<String, std::path::PathBuf> = HashMap::new();
+        for folder in folders {
+            //Not having a plugin folder is fine, there are built-in plugins.
+            if !folder.exists() {
+                continue;
+            }
+            let files = match find_library_files(folder) {
+                Ok(x) => x,
+                Err(e) => {
+                    eprintln!("{} {}", gettext!("Tried to load plugins from folder \"{}\", but failed. You might want to set a plugin directory on the command line. The actual error was:", folder.display()), e);
+                    continue;
+                }
+            };
+            for p in files {
+                let name = match read_exported_name(&p) {
+                    Ok(x) => x,
+                    //Not a library at all. Only worth a message if every library was asked for.
+                    Err(()) if matches!(selection, PluginSelection::Named(_)) => continue,
+                    Err(()) => None
+                };
+                if !selection.wants(name.as_deref()) {
+                    continue;
+                }
+                if let Some(name) = &name {
+                    if let Some(first) = exporters.get(name) {
+                        eprintln!("{}", gettext!("The plugin {} was found in both {} and {}. Using the one in {}. The [Plugins] section of the configuration can pick a library explicitly.", name, first.display(), p.display(), first.display()));
+                        continue;
                     }
                 }
-            }).collect()
-        })
+                let lib = match unsafe { libloading::Library::new(&p) } {
+                    Ok(x) => x,
+                    Err(_) => {
+                        eprintln!("{}", gettext!("Failed to load as library: {}", p.display()));
+                        continue;
+                    }
+                };
+                if let Some(name) = name {
+                    exporters.insert(name.clone(), p.clone());
+                    names.insert(p.clone(), name);
+                }
+                libs.push((p, lib));
+            }
+        }
+        Libraries { libs, pinned : Vec::new(), names }
     }
+    /// Loads the libraries the config's [Plugins] section names explicitly.
+    pub fn load_pinned(&mut self, sources : &BTreeMap<String, PluginSourceConfig>) {
+        for path in sources.values().filter_map(|s| s.library.as_ref()) {
+            if self.pinned.iter().any(|(p, _)| p == path) {
+                continue;
+            }
+            match unsafe { libloading::Library::new(path) } {
+                Ok(x) => self.pinned.push((path.clone(), x)),
+                Err(e) => eprintln!("{}", gettext!("Failed to load library {} from the [Plugins] section of the configuration: {}", path.display(), e))
+            }
+        }
+    }
+}
+
+/// All .so files in the folder and its subfolders, sorted by path so the order doesn't depend on
+/// the file system. Symbolic links to folders are not followed, to avoid loops.
+fn find_library_files(folder : &std::path::Path) -> Result<Vec<std::path::PathBuf>, std::io::Error> {
+    let mut result = Vec::new();
+    let mut entries : Vec<_> = folder.read_dir()?.filter_map(|f| {
+        match f {
+            Err(e) => {
+                eprintln!("{}", gettext!("File I/O error while iterating libraries: {}", e.to_string()));
+                None
+            },
+            Ok(d) => Some(d)
+        }
+    }).collect();
+    entries.sort_by_key(|d| d.path());
+    for entry in entries {
+        let p = entry.path();
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            match find_library_files(&p) {
+                Ok(files) => result.extend(files),
+                Err(e) => eprintln!("{}", gettext!("File I/O error while iterating libraries: {}", e.to_string()))
+            }
+        }
+        else if p.extension() == Some(std::ffi::OsStr::new("so")) {
+            result.push(p);
+        }
+    }
+    Ok(result)
+}
+
+/// Which plugin libraries to keep when loading a folder.
+pub enum PluginSelection {
+    All,
+    /// Only the plugins with these names. Used to skip everything the configuration doesn't need.
+    Named(HashSet<String>),
+}
+
+impl PluginSelection {
+    fn wants(&self, exported_name : Option<&str>) -> bool {
+        match self {
+            PluginSelection::All => true,
+            //Libraries built before the name was exported can only be identified by loading them.
+            PluginSelection::Named(names) => exported_name.is_none_or(|n| names.contains(n)),
+        }
+    }
+}
+
+/// Reads the name a plugin exports next to its constructor from the library file. Loading a
+/// library runs its initializers, which libraries the configuration doesn't need shouldn't get to
+/// do. The symbol is a byte array in the same place for both interfaces and never changes.
+/// Fails if the file can't be read or isn't an ELF file, and is None if the library doesn't
+/// export a name.
+fn read_exported_name(path : &std::path::Path) -> Result<Option<String>, ()> {
+    let data = std::fs::read(path).map_err(|_| ())?;
+    let elf = goblin::elf::Elf::parse(&data).map_err(|_| ())?;
+    Ok(find_exported_name(&elf, &data))
+}
+
+fn find_exported_name(elf : &goblin::elf::Elf, data : &[u8]) -> Option<String> {
+    let symbol = elf.dynsyms.iter().find(|s| !s.is_import() && elf.dynstrtab.get_at(s.st_name) == Some("_swaystatus_module_name"))?;
+    //The file offset follows from the segment the symbol gets loaded with.
+    let segment = elf.program_headers.iter().find(|h| {
+        h.p_type == goblin::elf::program_header::PT_LOAD && h.p_vaddr <= symbol.st_value && symbol.st_value + symbol.st_size <= h.p_vaddr + h.p_filesz
+    })?;
+    let start = usize::try_from(segment.p_offset + symbol.st_value - segment.p_vaddr).ok()?;
+    let bytes = data.get(start..start.checked_add(usize::try_from(symbol.st_size).ok()?)?)?;
+    let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+    Some(String::from_utf8_lossy(bytes).into_owned())
 }
+
 enum PluginLoadingError {
     MissingVersionInformation,
     WrongPluginVersion { expected: &'static str, version : String },
     WrongRustcVersion { expected: &'static str, version : String },
+    WrongAbiVersion { expected : (u32, u32), version : (u32, u32) },
+    IncompleteAbiModule,
     NoConstructor
 }
 
-fn get_plugin_from_library<'p>(lib : &'p Library) -> Result<Box<dyn plugin::SwayStatusModule + 'p>,PluginLoadingError> {
+/// The stable interface is compatible if the major version matches, and the plugin doesn't expect
+/// functions that were added after the minor version we know.
+fn is_compatible_abi_version(host : (u32, u32), plugin : (u32, u32)) -> bool {
+    host.0 == plugin.0 && host.1 >= plugin.1
+}
+
+fn get_plugin_from_library<'p>(lib : &'p Library) -> Result<(Box<dyn plugin::SwayStatusModule + 'p>, plugin::PluginFeatures),PluginLoadingError> {
     unsafe {
+        //Plugins using the stable interface don't care about the compiler version.
+        if let Ok(info_getter) = lib.get::<unsafe extern "C" fn() -> swaystatus_plugin::abi::AbiPluginInfo>(b"_swaystatus_abi_plugin_info") {
+            let info = info_getter();
+            let expected = (swaystatus_plugin::abi::ABI_VERSION_MAJOR, swaystatus_plugin::abi::ABI_VERSION_MINOR);
+            let version = (info.version_major, info.version_minor);
+            if !is_compatible_abi_version(expected, version) {
+                return Err(PluginLoadingError::WrongAbiVersion { expected, version });
+            }
+            let constructor = lib.get::<unsafe extern "C" fn(*mut swaystatus_plugin::abi::AbiModule)>(b"_swaystatus_abi_module_create")
+                .map_err(|_| PluginLoadingError::NoConstructor)?;
+            return match abi::ImportedModule::new(*constructor) {
+                Some(m) => Ok((Box::new(m), info.features)),
+                None => Err(PluginLoadingError::IncompleteAbiModule)
+            };
+        }
         let version_getter = lib.get::<unsafe extern fn() -> *const str>(b"_swaystatus_module_version");
         let rustc_version_getter = lib.get::<unsafe extern fn() -> *const str>(b"_swaystatus_rustc_version");
         if version_getter.is_err() || rustc_version_getter.is_err() {
@@ -88,18 +331,26 @@ fn get_plugin_from_library<'p>(lib : &'p Library) -> Result<Box<dyn plugin::Sway
         }
         let found_version = &*(version_getter.unwrap())();
         let found_rustc_version = &*(rustc_version_getter.unwrap())();
+        //Defaulted trait methods change the vtable, so even a compatible version bump breaks them.
         if found_version != swaystatus_plugin::MODULE_VERSION {
             return Err(PluginLoadingError::WrongPluginVersion { expected: swaystatus_plugin::MODULE_VERSION, version : String::from(found_version) });
         }
         if found_rustc_version != swaystatus_plugin::RUSTC_VERSION {
             return Err(PluginLoadingError::WrongRustcVersion { expected : swaystatus_plugin::RUSTC_VERSION, version : String::from(found_rustc_version)});
         }
+        //Plugins built before features existed don't export them.
+        let features = lib.get::<unsafe extern "C" fn() -> u64>(b"_swaystatus_module_features")
+            .map(|f| plugin::PluginFeatures(f()))
+            .unwrap_or_default();
         if let Ok(constructor) = lib.get::<unsafe extern fn() ->*mut dyn swaystatus_plugin::SwayStatusModule>(b"_swaystatus_module_create") {
-            return Ok(Box::from_raw(constructor()));
+            return Ok((Box::from_raw(constructor()), features));
         }
         Err(PluginLoadingError::NoConstructor)
    }
 }
 
+#[cfg(test)]
+mod tests;
+
 #[cfg(test)]
 pub mod test_helper; 
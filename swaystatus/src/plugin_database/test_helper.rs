@@ -9,8 +9,8 @@ pub fn get_plugin_database_with_test_plugin() -> PluginDatabase<'static> {
     m.insert(String::from(TestPlugin.get_name()),
         Box::new(TestPlugin) as Box<dyn SwayStatusModule>);
         m
-    }}
+    }, library_paths : HashMap::new(), features : HashMap::new() }
 }
 pub fn get_plugin_database_empty() -> PluginDatabase<'static> {
-    PluginDatabase { plugins : HashMap::new() }
+    PluginDatabase { plugins : HashMap::new(), library_paths : HashMap::new(), features : HashMap::new() }
 }
@@ -0,0 +1,55 @@
+use super::*;
+
+#[test]
+fn abi_versions_with_newer_minor_are_rejected() {
+    assert!(is_compatible_abi_version((1, 2), (1, 0)));
+    assert!(is_compatible_abi_version((1, 2), (1, 2)));
+    assert!(!is_compatible_abi_version((1, 2), (1, 3)));
+    assert!(!is_compatible_abi_version((2, 0), (1, 0)));
+}
+
+#[test]
+fn features_default_to_none() {
+    let plugins = test_helper::get_plugin_database_with_test_plugin();
+    assert_eq!(plugins.get_features("TestPlugin"), plugin::PluginFeatures::NONE);
+    assert!(plugin::PluginFeatures(3).contains(plugin::PluginFeatures::CLICK_EVENTS));
+    assert!(!plugin::PluginFeatures::STRUCTURED_UPDATES.contains(plugin::PluginFeatures::CLICK_EVENTS));
+}
+
+#[test]
+fn library_files_are_found_recursively_and_sorted() {
+    let root = std::env::temp_dir().join(format!("swaystatus-library-files-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("b/nested")).unwrap();
+    for file in ["c.so", "a.so", "b/nested/d.so", "b/readme.txt", "e.so.1"] {
+        std::fs::write(root.join(file), "").unwrap();
+    }
+    let files = find_library_files(&root).unwrap();
+    assert_eq!(files, [root.join("a.so"), root.join("b/nested/d.so"), root.join("c.so")]);
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn plugin_sources_add_names_for_builtin_plugins() {
+    let libs = Libraries { libs : Vec::new(), pinned : Vec::new(), names : HashMap::new() };
+    let mut sources = BTreeMap::new();
+    sources.insert(String::from("Command"), PluginSourceConfig { library : None, plugin : Some(String::from("Exec")) });
+    sources.insert(String::from("Missing"), PluginSourceConfig { library : None, plugin : Some(String::from("NoSuchPlugin")) });
+    let database = PluginDatabase::with_sources(&libs, &sources);
+    assert_eq!(database.get_plugin("Command").unwrap().get_name(), "Exec");
+    assert_eq!(database.get_features("Command"), ExecPlugin::FEATURES);
+    assert!(database.get_plugin("Exec").is_some());
+    assert!(database.get_plugin("Missing").is_none());
+    assert!(database.get_library_path("Command").is_none());
+}
+
+#[test]
+fn names_are_only_read_from_libraries() {
+    let junk = std::env::temp_dir().join(format!("swaystatus-junk-{}.so", std::process::id()));
+    std::fs::write(&junk, "not a library").unwrap();
+    assert!(read_exported_name(&junk).is_err());
+    std::fs::remove_file(junk).unwrap();
+    //The test program is an ELF file, but doesn't export a plugin name.
+    assert_eq!(read_exported_name(&std::env::current_exe().unwrap()), Ok(None));
+    assert!(PluginSelection::Named(HashSet::from([String::from("A")])).wants(None));
+    assert!(!PluginSelection::Named(HashSet::from([String::from("A")])).wants(Some("B")));
+}
@@ -0,0 +1,96 @@
+//! Helpers for reloading without restarting everything.
+//! A reload compares the elements of the new config with the running ones. Elements whose
+//! config didn't change keep their thread, only the others are stopped or started.
+
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+use crate::config::SwaystatusPluginConfig;
+
+#[cfg(test)]
+mod tests;
+
+/// Owns the configs of the elements a core loop started, indexed by element number. The threads
+/// of kept elements borrow their config across reloads, so a config has to outlive the reload
+/// that read it. Once an element is removed and its thread is gone, its config is dropped, and
+/// its number goes to the next new element.
+pub struct ElementStore<T> {
+    //Raw pointers instead of Boxes: A Box claims unique access to its contents whenever it's
+    //moved, as the Vec does when it grows, and that conflicts with the shared references handed
+    //out. The contents themselves stay at their address either way.
+    items : RefCell<Vec<Option<NonNull<T>>>>,
+    _owns : PhantomData<T>,
+}
+
+impl<T> Default for ElementStore<T> {
+    fn default() -> Self {
+        ElementStore { items : RefCell::new(Vec::new()), _owns : PhantomData }
+    }
+}
+
+impl<T> ElementStore<T> {
+    /// Stores the item under the lowest free number. Returns that number and the stored item.
+    pub fn add(&self, item : T) -> (usize, &T) {
+        let item = NonNull::from(Box::leak(Box::new(item)));
+        let mut items = self.items.borrow_mut();
+        let number = match items.iter().position(Option::is_none) {
+            Some(free) => {
+                items[free] = Some(item);
+                free
+            },
+            None => {
+                items.push(Some(item));
+                items.len() - 1
+            }
+        };
+        //The item stays at its address until it's removed, and is never handed out mutably.
+        (number, unsafe { item.as_ref() })
+    }
+
+    pub fn get(&self, number : usize) -> &T {
+        let item = self.items.borrow()[number].expect("Only numbers of stored elements are in use");
+        unsafe { item.as_ref() }
+    }
+
+    /// Drops the item with the given number, and frees the number.
+    ///
+    /// # Safety
+    /// Nothing that borrows from the item may be used afterwards. For an element config that
+    /// means all of these have to be gone:
+    /// - the references handed out by `add` and `get`, including copies in lists of elements,
+    /// - the runnable made from its instance, so its thread has to have finished,
+    /// - the sender made along with the runnable. For ABI plugins it wraps the plugin's message
+    ///   object, which the plugin interface requires to be destroyed before the instance.
+    pub unsafe fn remove(&self, number : usize) {
+        if let Some(item) = self.items.borrow_mut().get_mut(number).and_then(Option::take) {
+            drop(unsafe { Box::from_raw(item.as_ptr()) });
+        }
+    }
+}
+
+impl<T> Drop for ElementStore<T> {
+    fn drop(&mut self) {
+        for item in self.items.get_mut().drain(..).flatten() {
+            drop(unsafe { Box::from_raw(item.as_ptr()) });
+        }
+    }
+}
+
+/// What decides if an element can be kept: its whole config, serialized. None if the config
+/// can't be serialized, and such an element is never kept.
+pub fn element_identity(element : &SwaystatusPluginConfig) -> Option<String> {
+    serde_json::to_string(element).ok()
+}
+
+/// Pairs each element of the new config with a running element that has the same identity. The
+/// result has one entry per new element, holding the position of the running element to keep in
+/// its place. Every running element is kept at most once.
+pub fn match_elements(running : &[Option<String>], new : &[Option<String>]) -> Vec<Option<usize>> {
+    let mut taken = vec![false; running.len()];
+    new.iter().map(|identity| {
+        let identity = identity.as_ref()?;
+        let position = (0..running.len()).find(|p| !taken[*p] && running[*p].as_ref() == Some(identity))?;
+        taken[position] = true;
+        Some(position)
+    }).collect()
+}
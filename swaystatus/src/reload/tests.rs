@@ -0,0 +1,55 @@
+use super::*;
+use crate::plugin_database::test_helper::*;
+use crate::config::test_helper::*;
+
+fn identities(config : &str) -> Vec<Option<String>> {
+    let p = get_plugin_database_with_test_plugin();
+    let elements = get_elements_from_config(config, &p);
+    elements.iter().map(element_identity).collect()
+}
+
+const ELEMENT_A : &str = "[[Element]]\nPlugin = \"TestPlugin\"\n\n[Element.Config]\nlines = 2\nskull = \"bones\"\n\n";
+const ELEMENT_B : &str = "[[Element]]\nPlugin = \"TestPlugin\"\n\n[Element.Config]\nlines = 5\nskull = \"pirate\"\n\n";
+const ELEMENT_B_WITH_TEXT : &str = "[[Element]]\nPlugin = \"TestPlugin\"\n\n[Element.Config]\nlines = 5\nskull = \"pirate\"\n\n[Element.General]\nBeforeText = \"B: \"\n\n";
+
+#[test]
+fn unchanged_elements_are_kept_in_their_new_place() {
+    let running = identities(&[ELEMENT_A, ELEMENT_B].concat());
+    let new = identities(&[ELEMENT_B, ELEMENT_A, ELEMENT_A].concat());
+    assert_eq!(match_elements(&running, &new), vec![Some(1), Some(0), None]);
+}
+
+#[test]
+fn general_settings_are_part_of_the_identity() {
+    let running = identities(&[ELEMENT_A, ELEMENT_B].concat());
+    let new = identities(&[ELEMENT_A, ELEMENT_B_WITH_TEXT].concat());
+    assert_eq!(match_elements(&running, &new), vec![Some(0), None]);
+}
+
+#[test]
+fn unserializable_elements_are_never_kept() {
+    assert_eq!(match_elements(&[None], &[None]), vec![None]);
+}
+
+#[test]
+fn stored_elements_stay_put() {
+    let store = ElementStore::default();
+    let (number, first) = store.add(String::from("first"));
+    let others : Vec<_> = (0..100).map(|i| store.add(i.to_string()).1).collect();
+    assert_eq!((number, first), (0, &String::from("first")));
+    assert_eq!(others[99], "99");
+    assert_eq!(store.get(100), "99");
+}
+
+#[test]
+fn numbers_of_removed_elements_are_reused() {
+    let store = ElementStore::default();
+    for name in ["a", "b", "c"] {
+        store.add(String::from(name));
+    }
+    unsafe { store.remove(1) };
+    unsafe { store.remove(1) };
+    assert_eq!(store.add(String::from("d")).0, 1);
+    assert_eq!(store.add(String::from("e")).0, 3);
+    assert_eq!([store.get(0), store.get(1), store.get(2)], ["a", "d", "c"]);
+}
@@ -0,0 +1,89 @@
+//! Bookkeeping for restarting crashed elements.
+//! If a plugin panics, the element is restarted after a delay. The delay doubles with every crash
+//! of the same element, and after a configurable number of retries the element stays dead, so a
+//! plugin that crashes right away doesn't keep the main thread busy.
+
+use std::time::{Duration, Instant};
+use crate::config::SwaystatusMainConfig;
+
+#[cfg(test)]
+mod tests;
+
+pub struct RestartSchedule {
+    initial_delay : Duration,
+    max_retries : u32,
+    /// How often each element has already been restarted.
+    retries : Vec<u32>,
+    /// When each element is due to be restarted, if at all.
+    pending : Vec<Option<Instant>>,
+}
+
+impl RestartSchedule {
+    pub fn new(settings : &SwaystatusMainConfig, element_count : usize) -> Self {
+        RestartSchedule {
+            initial_delay : Duration::from_millis(settings.restart_delay_ms),
+            max_retries : settings.restart_max_retries,
+            retries : vec![0; element_count],
+            pending : vec![None; element_count],
+        }
+    }
+
+    /// Starts keeping track of another element. Its number is either new, or the number of an
+    /// element that a reload removed.
+    pub fn add_element(&mut self, element_number : usize) {
+        if element_number >= self.retries.len() {
+            self.retries.resize(element_number + 1, 0);
+            self.pending.resize(element_number + 1, None);
+        }
+        self.retries[element_number] = 0;
+        self.pending[element_number] = None;
+    }
+
+    /// Takes the delay and retry limit from the given settings, after a reload changed them.
+    pub fn apply_settings(&mut self, settings : &SwaystatusMainConfig) {
+        self.initial_delay = Duration::from_millis(settings.restart_delay_ms);
+        self.max_retries = settings.restart_max_retries;
+    }
+
+    /// Plans a restart of the given element. Returns the delay until the restart, or None if the
+    /// element has used up its retries.
+    pub fn schedule(&mut self, element_number : usize, now : Instant) -> Option<Duration> {
+        let retries = &mut self.retries[element_number];
+        if *retries >= self.max_retries {
+            return None;
+        }
+        let delay = self.initial_delay.saturating_mul(2u32.saturating_pow(*retries));
+        *retries += 1;
+        //A delay too long to be represented never ends.
+        self.pending[element_number] = now.checked_add(delay);
+        Some(delay)
+    }
+
+    /// The point in time at which the next restart is due.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.pending.iter().flatten().min().copied()
+    }
+
+    /// Returns the elements whose restart is due, and forgets about them.
+    pub fn take_due(&mut self, now : Instant) -> Vec<usize> {
+        self.pending.iter_mut().enumerate().filter_map(|(element_number, deadline)| {
+            match deadline {
+                Some(d) if *d <= now => {
+                    *deadline = None;
+                    Some(element_number)
+                },
+                _ => None
+            }
+        }).collect()
+    }
+
+    /// Drops the planned restart of an element, for instance because a reload removed it.
+    pub fn cancel(&mut self, element_number : usize) {
+        self.pending[element_number] = None;
+    }
+
+    /// Drops all planned restarts. Used when the program is about to quit or reload.
+    pub fn cancel_all(&mut self) {
+        self.pending.iter_mut().for_each(|d| *d = None);
+    }
+}
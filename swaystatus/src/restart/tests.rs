@@ -0,0 +1,64 @@
+use super::*;
+
+fn make_schedule(max_retries : u32) -> RestartSchedule {
+    let settings = SwaystatusMainConfig { restart_delay_ms : 100, restart_max_retries : max_retries, ..Default::default() };
+    RestartSchedule::new(&settings, 2)
+}
+
+#[test]
+fn delay_doubles_until_retries_are_used_up() {
+    let mut schedule = make_schedule(3);
+    let now = Instant::now();
+    assert_eq!(schedule.schedule(1, now), Some(Duration::from_millis(100)));
+    assert_eq!(schedule.schedule(1, now), Some(Duration::from_millis(200)));
+    assert_eq!(schedule.schedule(1, now), Some(Duration::from_millis(400)));
+    assert_eq!(schedule.schedule(1, now), None);
+    //other elements have their own count.
+    assert_eq!(schedule.schedule(0, now), Some(Duration::from_millis(100)));
+}
+
+#[test]
+fn only_due_restarts_are_taken() {
+    let mut schedule = make_schedule(5);
+    let now = Instant::now();
+    assert_eq!(schedule.next_deadline(), None);
+    schedule.schedule(0, now);
+    schedule.schedule(1, now);
+    schedule.schedule(1, now);
+    assert_eq!(schedule.next_deadline(), Some(now + Duration::from_millis(100)));
+    assert!(schedule.take_due(now).is_empty());
+    assert_eq!(schedule.take_due(now + Duration::from_millis(150)), vec![0]);
+    assert_eq!(schedule.next_deadline(), Some(now + Duration::from_millis(200)));
+    schedule.cancel_all();
+    assert_eq!(schedule.next_deadline(), None);
+}
+
+#[test]
+fn zero_retries_disables_restarts() {
+    let mut schedule = make_schedule(0);
+    assert_eq!(schedule.schedule(0, Instant::now()), None);
+}
+
+#[test]
+fn added_elements_can_be_restarted_and_cancelled() {
+    let mut schedule = make_schedule(5);
+    let now = Instant::now();
+    schedule.add_element(2);
+    schedule.schedule(2, now);
+    assert_eq!(schedule.next_deadline(), Some(now + Duration::from_millis(100)));
+    schedule.cancel(2);
+    assert_eq!(schedule.next_deadline(), None);
+    //A reused number starts over with its retries.
+    schedule.schedule(2, now);
+    schedule.add_element(2);
+    assert_eq!((schedule.next_deadline(), schedule.schedule(2, now)), (None, Some(Duration::from_millis(100))));
+}
+
+#[test]
+fn huge_delays_never_end() {
+    let settings = SwaystatusMainConfig { restart_delay_ms : u64::MAX, restart_max_retries : 20, ..Default::default() };
+    let mut schedule = RestartSchedule::new(&settings, 1);
+    //The delay doubles until it's too long for any clock.
+    while schedule.schedule(0, Instant::now()).is_some() {}
+    assert_eq!(schedule.next_deadline(), None);
+}
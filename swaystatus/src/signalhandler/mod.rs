@@ -1,33 +1,51 @@
 use crate::communication;
 use std::sync::mpsc;
-use signal_hook::iterator::Signals;
+use signal_hook::iterator::{Handle, Signals};
 use signal_hook::consts::*;
 use crossbeam_utils::thread::Scope;
 
 /// This function starts an endless loop, waiting for signals. The only ones that we explicitly
 /// handle are USR1 (immediate update), SIGPIPE (because that indicates nobody is listening to us
-/// any more), SIGHUP to trigger a reload, and the usual term signals. 
-pub fn handle_signals(scope : &Scope, sender : mpsc::Sender<communication::Message>) {
+/// any more), SIGHUP to trigger a reload, the usual term signals, and the real-time signals that
+/// refresh single elements.
+/// The loop keeps running until the core loop closes it with the returned handle, once it
+/// reloads or quits. Ending it from inside would leave a dangling handle behind.
+pub fn handle_signals(scope : &Scope, sender : mpsc::Sender<communication::Message>) -> Handle {
     //we mustn't forget that upon any terminating signals (including PIPE) and HUP we need to exit.
-    let mut signals = Signals::new(&[
+    let mut signals = Signals::new([
         signal::SIGTERM, //quit
         signal::SIGINT,  //quit as well
         //signal::SIGQUIT, //we don't do anything special here. Users _expect_ QUIT to make a dump.
         signal::SIGPIPE, //quit, because nobody's listening
         signal::SIGHUP,  //quit, but send the Reload message instead of the Quit one.
-        signal::SIGUSR1, //trigger a refresh. The ONLY one upon which we dont break the loop!
-    ]).unwrap_or_else(|_| {panic!("{}",gettextrs::gettext("Failed to register signal handler. Since without signal handler there's no proper way to cleanly exit any plugins, we bail now."))});
+        signal::SIGUSR1, //trigger a refresh.
+    ].into_iter().chain(element_signals())).unwrap_or_else(|_| {panic!("{}",gettextrs::gettext("Failed to register signal handler. Since without signal handler there's no proper way to cleanly exit any plugins, we bail now."))});
+    let handle = signals.handle();
 
     scope.spawn(move |_| {
         for signal in &mut signals {
             match signal {
                 signal::SIGUSR1 => send(&sender, communication::InternalMessage::Refresh),
-                signal::SIGHUP => { send(&sender, communication::InternalMessage::Reload); break; }
-                _=> { send(&sender, communication::InternalMessage::Quit); break;}
-
+                signal::SIGHUP => send(&sender, communication::InternalMessage::Reload),
+                s if element_signals().contains(&s) => {
+                    //Fails only if the core loop is already gone, then there's nothing to refresh.
+                    let _ = sender.send(communication::Message::Signal((s - libc::SIGRTMIN()) as u32));
+                },
+                _=> send(&sender, communication::InternalMessage::Quit),
             }
         }
     });
+    handle
+}
+
+/// The highest number an element can use as its `Signal`. The signal is SIGRTMIN plus the number.
+pub fn max_element_signal() -> u32 {
+    (libc::SIGRTMAX() - libc::SIGRTMIN()) as u32
+}
+
+/// SIGRTMIN itself is left out, like in i3blocks, so the numbers are the same there.
+fn element_signals() -> std::ops::RangeInclusive<libc::c_int> {
+    libc::SIGRTMIN() + 1..=libc::SIGRTMAX()
 }
 
 fn send(sender : &mpsc::Sender<communication::Message>, message : communication::InternalMessage) {
@@ -37,6 +37,9 @@ impl SwayStatusModuleInstance for TestConfig {
     fn make_runnable<'p>(&'p self, _to_main : Box<dyn MsgModuleToMain + 'p>) -> (Box<dyn SwayStatusModuleRunnable + 'p>, Box<dyn MsgMainToModule + 'p>) {
        return (Box::new(TestRunnable), Box::new(DeadEndSend)); 
     }
+    fn validate(&self) -> Vec<String> {
+        if self.lines == 0 { vec![String::from("no lines")] } else { Vec::new() }
+    }
 }
 
 impl SwayStatusModule for TestPlugin {
@@ -57,5 +60,8 @@ impl SwayStatusModule for TestPlugin {
     fn print_help(&self) {
         println!("Not implemented for a test plugin. Hey, this only exists to test serializaiont!");
     }
+    fn get_config_schema(&self) -> Option<String> {
+        Some(serde_json::json!({ "type" : "object", "required" : ["lines", "skull"] }).to_string())
+    }
 }
 
@@ -0,0 +1,215 @@
+//! Colors and icons that are shared by all elements.
+//! A theme consists of a palette, which maps element states (and the separator) to colors, and an
+//! icon set, which maps symbolic icon names to the actual text that gets shown. Plugins refer to
+//! icons by putting `{icon:name}` in their text, for instance in a `MuteSymbol`. The placeholders
+//! are replaced right before the status line is written, so switching icon sets only needs a
+//! single line in the `[Theme]` section.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use serde::{Serialize, Deserialize};
+use crate::plugin::BlockState;
+
+#[cfg(test)]
+mod tests;
+
+/**
+ * The `[Theme]` section of the config file. Selects a built-in palette and icon set, and allows
+ * to override single colors and icons of them.
+ */
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields, default, rename_all="PascalCase")]
+pub struct ThemeConfig {
+    pub palette : BuiltinPalette,
+    pub icon_set : BuiltinIconSet,
+    /// Colors that replace those of the selected palette.
+    pub colors : Palette,
+    /// Icons that replace or extend those of the selected icon set.
+    pub icons : BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BuiltinPalette {
+    #[default]
+    Default,
+    Solarized,
+    Gruvbox
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BuiltinIconSet {
+    Ascii,
+    NerdFont,
+    #[default]
+    Emoji
+}
+
+/**
+ * Colors for each element state, and for the separator. A color that's not set leaves the choice
+ * to the element configuration or the bar.
+ */
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields, default, rename_all="PascalCase")]
+pub struct Palette {
+    pub idle : Option<String>,
+    pub info : Option<String>,
+    pub good : Option<String>,
+    pub warning : Option<String>,
+    pub critical : Option<String>,
+    pub separator : Option<String>,
+    pub separator_background : Option<String>,
+}
+
+impl Palette {
+    /// The color for the given state. Idle is deliberately not included, as the element's own
+    /// foreground color should beat it.
+    pub fn state_color(&self, state : BlockState) -> Option<&str> {
+        match state {
+            BlockState::Idle => None,
+            BlockState::Info => self.info.as_deref(),
+            BlockState::Good => self.good.as_deref(),
+            BlockState::Warning => self.warning.as_deref(),
+            BlockState::Critical => self.critical.as_deref(),
+        }
+    }
+
+    /// Takes every color that's set in `overrides`, and keeps the own color otherwise.
+    fn merge(self, overrides : Palette) -> Palette {
+        Palette {
+            idle : overrides.idle.or(self.idle),
+            info : overrides.info.or(self.info),
+            good : overrides.good.or(self.good),
+            warning : overrides.warning.or(self.warning),
+            critical : overrides.critical.or(self.critical),
+            separator : overrides.separator.or(self.separator),
+            separator_background : overrides.separator_background.or(self.separator_background),
+        }
+    }
+}
+
+impl From<BuiltinPalette> for Palette {
+    fn from(p : BuiltinPalette) -> Self {
+        let colors = |idle : Option<&str>, info : &str, good : &str, warning : &str, critical : &str, separator : Option<&str>| Palette {
+            idle : idle.map(String::from),
+            info : Some(String::from(info)),
+            good : Some(String::from(good)),
+            warning : Some(String::from(warning)),
+            critical : Some(String::from(critical)),
+            separator : separator.map(String::from),
+            separator_background : None,
+        };
+        match p {
+            BuiltinPalette::Default => colors(None, "#00AAFF", "#00FF00", "#FFFF00", "#FF0000", None),
+            BuiltinPalette::Solarized => colors(Some("#839496"), "#268BD2", "#859900", "#B58900", "#DC322F", Some("#586E75")),
+            BuiltinPalette::Gruvbox => colors(Some("#EBDBB2"), "#83A598", "#B8BB26", "#FABD2F", "#FB4934", Some("#928374")),
+        }
+    }
+}
+
+impl BuiltinIconSet {
+    fn icons(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            BuiltinIconSet::Ascii => &[
+                ("volume_muted", "mute"),
+                ("volume_unmuted", "vol"),
+                ("volume_low", "vol-"),
+                ("volume_medium", "vol"),
+                ("volume_high", "vol+"),
+                ("balance_left", "|.."),
+                ("balance_center", ".|."),
+                ("balance_right", "..|"),
+            ],
+            BuiltinIconSet::NerdFont => &[
+                ("volume_muted", "\u{f0581}"),
+                ("volume_unmuted", "\u{f057e}"),
+                ("volume_low", "\u{f057f}"),
+                ("volume_medium", "\u{f0580}"),
+                ("volume_high", "\u{f057e}"),
+                ("balance_left", "|.."),
+                ("balance_center", ".|."),
+                ("balance_right", "..|"),
+            ],
+            BuiltinIconSet::Emoji => &[
+                ("volume_muted", "🔇"),
+                ("volume_unmuted", "🔊"),
+                ("volume_low", "🔈"),
+                ("volume_medium", "🔉"),
+                ("volume_high", "🔊"),
+                ("balance_left", "|.."),
+                ("balance_center", ".|."),
+                ("balance_right", "..|"),
+            ],
+        }
+    }
+}
+
+/**
+ * The theme as it's used at runtime, with the overrides from the config file already applied.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    pub palette : Palette,
+    icons : BTreeMap<String, String>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::from(ThemeConfig::default())
+    }
+}
+
+impl From<ThemeConfig> for Theme {
+    fn from(config : ThemeConfig) -> Self {
+        let mut icons : BTreeMap<String, String> = config.icon_set.icons().iter()
+            .map(|(name, icon)| (String::from(*name), String::from(*icon)))
+            .collect();
+        icons.extend(config.icons);
+        Theme {
+            palette : Palette::from(config.palette).merge(config.colors),
+            icons,
+        }
+    }
+}
+
+impl Theme {
+    const ICON_PREFIX : &'static str = "{icon:";
+
+    /// Replaces all `{icon:name}` placeholders in the text with the icons of this theme.
+    /// Placeholders with unknown names are left alone, so a typo is visible in the bar.
+    pub fn expand_icons<'a>(&self, text : &'a str) -> Cow<'a, str> {
+        self.expand_icons_with(text, |icon| Cow::Borrowed(icon))
+    }
+
+    /// Like `expand_icons`, but for Pango markup. The icons are escaped, so they are shown as
+    /// they are configured.
+    pub fn expand_icons_in_markup<'a>(&self, text : &'a str) -> Cow<'a, str> {
+        self.expand_icons_with(text, crate::output::escape_markup)
+    }
+
+    fn expand_icons_with<'a>(&self, text : &'a str, convert_icon : fn(&str) -> Cow<'_, str>) -> Cow<'a, str> {
+        if !text.contains(Self::ICON_PREFIX) {
+            return Cow::Borrowed(text);
+        }
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find(Self::ICON_PREFIX) {
+            let (before, placeholder) = rest.split_at(start);
+            result.push_str(before);
+            let icon = placeholder.find('}').and_then(|end| {
+                self.icons.get(&placeholder[Self::ICON_PREFIX.len()..end]).map(|icon| (icon, end))
+            });
+            match icon {
+                Some((icon, end)) => {
+                    result.push_str(&convert_icon(icon));
+                    rest = &placeholder[end + 1..];
+                },
+                None => {
+                    result.push_str(Self::ICON_PREFIX);
+                    rest = &placeholder[Self::ICON_PREFIX.len()..];
+                }
+            }
+        }
+        result.push_str(rest);
+        Cow::Owned(result)
+    }
+}
@@ -0,0 +1,27 @@
+use super::*;
+
+#[test]
+fn overrides_replace_builtin_values() {
+    let mut icons = BTreeMap::new();
+    icons.insert(String::from("volume_muted"), String::from("X"));
+    icons.insert(String::from("battery"), String::from("B"));
+    let config = ThemeConfig {
+        palette : BuiltinPalette::Solarized,
+        icon_set : BuiltinIconSet::Ascii,
+        colors : Palette { warning : Some(String::from("#123456")), ..Default::default() },
+        icons,
+    };
+    let theme = Theme::from(config);
+    assert_eq!(theme.palette.state_color(BlockState::Warning), Some("#123456"));
+    assert_eq!(theme.palette.state_color(BlockState::Critical), Some("#DC322F"));
+    assert_eq!(theme.palette.state_color(BlockState::Idle), None);
+    assert_eq!(theme.expand_icons("{icon:volume_muted} {icon:volume_high} {icon:battery}"), "X vol+ B");
+}
+
+#[test]
+fn expand_icons_leaves_unknown_placeholders_alone() {
+    let theme = Theme::default();
+    assert!(matches!(theme.expand_icons("no icons here"), Cow::Borrowed(_)));
+    assert_eq!(theme.expand_icons("{icon:nope}{icon:volume_muted}"), "{icon:nope}🔇");
+    assert_eq!(theme.expand_icons("{icon:volume_muted"), "{icon:volume_muted");
+}
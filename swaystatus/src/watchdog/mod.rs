@@ -0,0 +1,160 @@
+//! Keeps an eye on the element threads.
+//! There are two things to watch. While running, elements can opt in to a heartbeat check: If
+//! such an element doesn't send anything for too long, it's reported as unresponsive. While
+//! shutting down, every element gets a deadline to finish. Elements that miss it are reported as
+//! hung, and once only hung elements are left, the main thread stops waiting for them. Elements
+//! that a reload removes get the same deadline.
+//! Elements are identified by the number they were added with, which doesn't change if a reload
+//! moves them. Once an element is removed and its threads are gone, its number can be reused.
+
+use std::time::{Duration, Instant};
+use crate::config::{SwaystatusMainConfig, SwaystatusPluginConfig};
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum WatchdogEvent {
+    /// The element didn't send anything within its heartbeat timeout.
+    Unresponsive(usize),
+    /// The element didn't finish within its shutdown timeout.
+    Hung(usize),
+}
+
+pub struct Watchdog {
+    /// Number of running threads per element. Can briefly be 2 if a crashed element is restarted
+    /// before its old thread is fully gone.
+    running : Vec<u32>,
+    last_seen : Vec<Instant>,
+    heartbeat_timeouts : Vec<Option<Duration>>,
+    unresponsive : Vec<bool>,
+    shutdown_timeouts : Vec<Duration>,
+    /// When each element was told to finish, if it was.
+    stopping : Vec<Option<Instant>>,
+    shutting_down : bool,
+    hung : Vec<bool>,
+}
+
+impl Watchdog {
+    pub fn new(settings : &SwaystatusMainConfig, elements : &[&SwaystatusPluginConfig], now : Instant) -> Self {
+        let mut watchdog = Watchdog {
+            running : Vec::new(),
+            last_seen : Vec::new(),
+            heartbeat_timeouts : Vec::new(),
+            unresponsive : Vec::new(),
+            shutdown_timeouts : Vec::new(),
+            stopping : Vec::new(),
+            shutting_down : false,
+            hung : Vec::new(),
+        };
+        for (element_number, element) in elements.iter().enumerate() {
+            watchdog.add_element(element_number, settings, element, now);
+        }
+        watchdog
+    }
+
+    /// Starts keeping track of another element. Its number is either new, or the number of an
+    /// element whose threads are all gone.
+    pub fn add_element(&mut self, element_number : usize, settings : &SwaystatusMainConfig, element : &SwaystatusPluginConfig, now : Instant) {
+        if element_number >= self.running.len() {
+            let count = element_number + 1;
+            self.running.resize(count, 0);
+            self.last_seen.resize(count, now);
+            self.heartbeat_timeouts.resize(count, None);
+            self.unresponsive.resize(count, false);
+            self.shutdown_timeouts.resize(count, Duration::ZERO);
+            self.stopping.resize(count, None);
+            self.hung.resize(count, false);
+        }
+        self.last_seen[element_number] = now;
+        self.unresponsive[element_number] = false;
+        self.stopping[element_number] = None;
+        self.hung[element_number] = false;
+        self.configure(element_number, settings, element);
+    }
+
+    /// Takes the timeouts from the given settings. Used when a reload keeps the element, as the
+    /// global settings might have changed.
+    pub fn configure(&mut self, element_number : usize, settings : &SwaystatusMainConfig, element : &SwaystatusPluginConfig) {
+        let general = element.get_non_plugin_settings();
+        self.heartbeat_timeouts[element_number] = general.heartbeat_timeout_ms.map(Duration::from_millis);
+        self.shutdown_timeouts[element_number] = Duration::from_millis(general.shutdown_timeout_ms.unwrap_or(settings.shutdown_timeout_ms));
+    }
+
+    pub fn thread_started(&mut self, element_number : usize, now : Instant) {
+        self.running[element_number] += 1;
+        self.seen(element_number, now);
+    }
+
+    pub fn thread_finished(&mut self, element_number : usize) {
+        self.running[element_number] = self.running[element_number].saturating_sub(1);
+    }
+
+    pub fn is_running(&self, element_number : usize) -> bool {
+        self.running[element_number] > 0
+    }
+
+    /// Notes that the element sent something. Returns true if it was considered unresponsive
+    /// until now.
+    pub fn seen(&mut self, element_number : usize, now : Instant) -> bool {
+        self.last_seen[element_number] = now;
+        std::mem::replace(&mut self.unresponsive[element_number], false)
+    }
+
+    /// Starts the shutdown deadline of a single element, for instance one that a reload removed.
+    pub fn stop_element(&mut self, element_number : usize, now : Instant) {
+        self.stopping[element_number].get_or_insert(now);
+    }
+
+    pub fn begin_shutdown(&mut self, now : Instant) {
+        self.shutting_down = true;
+        self.stopping.iter_mut().for_each(|s| { s.get_or_insert(now); });
+    }
+
+    /// True once a shutdown has been requested and all element threads have finished.
+    pub fn is_shutdown_complete(&self) -> bool {
+        self.shutting_down && self.running.iter().all(|r| *r == 0)
+    }
+
+    /// True if a shutdown has been requested, and the only threads left are hung ones.
+    pub fn only_hung_elements_left(&self) -> bool {
+        self.shutting_down
+            && !self.is_shutdown_complete()
+            && self.running.iter().zip(&self.hung).all(|(r, h)| *r == 0 || *h)
+    }
+
+    /// The point in time at which `check` might have something new to say. Timeouts too long to
+    /// be represented never end.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        (0..self.running.len()).filter(|i| self.running[*i] > 0).filter_map(|i| match self.stopping[i] {
+            Some(started) => (!self.hung[i]).then(|| started.checked_add(self.shutdown_timeouts[i])).flatten(),
+            None => self.heartbeat_timeouts[i].filter(|_| !self.unresponsive[i]).and_then(|t| self.last_seen[i].checked_add(t))
+        }).min()
+    }
+
+    /// Returns the elements that missed a deadline since the last check.
+    pub fn check(&mut self, now : Instant) -> Vec<WatchdogEvent> {
+        let mut result = Vec::new();
+        for element_number in 0..self.running.len() {
+            if self.running[element_number] == 0 {
+                continue;
+            }
+            match self.stopping[element_number] {
+                Some(started) => {
+                    if !self.hung[element_number] && started.checked_add(self.shutdown_timeouts[element_number]).is_some_and(|d| d <= now) {
+                        self.hung[element_number] = true;
+                        result.push(WatchdogEvent::Hung(element_number));
+                    }
+                },
+                None => {
+                    let overdue = self.heartbeat_timeouts[element_number].and_then(|t| self.last_seen[element_number].checked_add(t)).is_some_and(|d| d <= now);
+                    if overdue && !self.unresponsive[element_number] {
+                        self.unresponsive[element_number] = true;
+                        result.push(WatchdogEvent::Unresponsive(element_number));
+                    }
+                }
+            }
+        }
+        result
+    }
+}
@@ -0,0 +1,65 @@
+use super::*;
+use crate::plugin_database::test_helper::*;
+use crate::config::test_helper::*;
+
+const TWO_ELEMENTS : &str = "[[Element]]\nPlugin = \"TestPlugin\"\n\n[Element.Config]\nlines = 2\nskull = \"bones\"\n\n[Element.General]\nHeartbeatTimeoutMs = 100\nShutdownTimeoutMs = 50\n\n[[Element]]\nPlugin = \"TestPlugin\"\n\n[Element.Config]\nlines = 5\nskull = \"pirate\"\n";
+
+#[test]
+fn heartbeat_is_only_checked_for_elements_that_want_it() {
+    let p = get_plugin_database_with_test_plugin();
+    let elements = get_elements_from_config(TWO_ELEMENTS, &p);
+    let elements : Vec<_> = elements.iter().collect();
+    let now = Instant::now();
+    let mut watchdog = Watchdog::new(&SwaystatusMainConfig::default(), &elements, now);
+    watchdog.thread_started(0, now);
+    watchdog.thread_started(1, now);
+    assert_eq!(watchdog.next_deadline(), Some(now + Duration::from_millis(100)));
+    assert!(watchdog.check(now + Duration::from_millis(50)).is_empty());
+    assert_eq!(watchdog.check(now + Duration::from_millis(100)), vec![WatchdogEvent::Unresponsive(0)]);
+    //reported only once
+    assert!(watchdog.check(now + Duration::from_millis(200)).is_empty());
+    assert_eq!(watchdog.next_deadline(), None);
+    assert!(watchdog.seen(0, now + Duration::from_millis(300)));
+    assert!(!watchdog.seen(0, now + Duration::from_millis(300)));
+}
+
+#[test]
+fn shutdown_waits_for_running_elements() {
+    let p = get_plugin_database_with_test_plugin();
+    let elements = get_elements_from_config(TWO_ELEMENTS, &p);
+    let elements : Vec<_> = elements.iter().collect();
+    let settings = SwaystatusMainConfig { shutdown_timeout_ms : 1000, ..Default::default() };
+    let now = Instant::now();
+    let mut watchdog = Watchdog::new(&settings, &elements, now);
+    watchdog.thread_started(0, now);
+    watchdog.thread_started(1, now);
+    assert!(!watchdog.is_shutdown_complete());
+    watchdog.begin_shutdown(now);
+    assert_eq!(watchdog.next_deadline(), Some(now + Duration::from_millis(50)));
+    assert_eq!(watchdog.check(now + Duration::from_millis(60)), vec![WatchdogEvent::Hung(0)]);
+    assert!(!watchdog.only_hung_elements_left());
+    watchdog.thread_finished(1);
+    assert!(watchdog.only_hung_elements_left());
+    assert!(!watchdog.is_shutdown_complete());
+    watchdog.thread_finished(0);
+    assert!(watchdog.is_shutdown_complete());
+    assert!(!watchdog.only_hung_elements_left());
+}
+
+#[test]
+fn removed_elements_get_a_shutdown_deadline() {
+    let p = get_plugin_database_with_test_plugin();
+    let elements = get_elements_from_config(TWO_ELEMENTS, &p);
+    let now = Instant::now();
+    let mut watchdog = Watchdog::new(&SwaystatusMainConfig::default(), &[&elements[1]], now);
+    watchdog.add_element(1, &SwaystatusMainConfig::default(), &elements[0], now);
+    watchdog.thread_started(0, now);
+    watchdog.thread_started(1, now);
+    watchdog.stop_element(1, now);
+    //The heartbeat doesn't matter any more once the element is told to stop.
+    assert_eq!(watchdog.next_deadline(), Some(now + Duration::from_millis(50)));
+    assert_eq!(watchdog.check(now + Duration::from_millis(60)), vec![WatchdogEvent::Hung(1)]);
+    watchdog.thread_finished(1);
+    assert!(!watchdog.is_shutdown_complete());
+    assert!(!watchdog.only_hung_elements_left());
+}